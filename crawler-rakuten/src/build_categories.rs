@@ -0,0 +1,53 @@
+use sale::domain::category::Category;
+use sale::domain::{category, time};
+use sale::{di, AppResult};
+use std::collections::HashMap;
+
+// 詳細クロールの後に全商品のパンくずからカテゴリの木を作り直す
+// 内容が変わったカテゴリと、所属が変わった商品だけを更新する
+pub async fn build() -> AppResult<()> {
+    let product_repo = di::DB_PRODUCT_REPOSITORY.get().await.clone();
+    let category_repo = di::DB_CATEGORY_REPOSITORY.get().await.clone();
+
+    let products = product_repo.find_all().await?;
+    let tree = category::build(&products, time::now());
+
+    let mut current: HashMap<category::Id, Category> = category_repo
+        .find_all()
+        .await?
+        .into_iter()
+        .map(|v| (v.id.clone(), v))
+        .collect();
+    let total = tree.categories.len();
+    for category in tree.categories {
+        let existing = current.remove(&category.id);
+        if let Some(category) = category.merge(existing.as_ref()) {
+            category_repo.put(category).await?;
+        }
+    }
+    for id in current.keys() {
+        category_repo.delete(id).await?;
+    }
+
+    let mut changed = 0;
+    for product in products {
+        let category_ids = tree
+            .product_paths
+            .get(&product.id)
+            .cloned()
+            .unwrap_or_default();
+        if product.category_ids != category_ids {
+            product_repo
+                .set_categories(&product.id, category_ids)
+                .await?;
+            changed += 1;
+        }
+    }
+    println!(
+        "カテゴリを更新しました カテゴリ数: {}, 削除: {}, 更新した商品数: {}",
+        total,
+        current.len(),
+        changed
+    );
+    Ok(())
+}
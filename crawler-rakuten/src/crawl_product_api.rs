@@ -0,0 +1,59 @@
+use sale::bus;
+use sale::crawl::rakuten_api::{SearchParams, MAX_HITS};
+use sale::domain::time;
+use sale::errors::NotFoundToNone;
+use sale::{di, AppResult};
+use std::collections::{HashMap, HashSet};
+
+// 楽天市場商品検索APIで1ページ分の商品を登録し、次のページ番号を返す
+// 詳細クロールで取る項目(パンくずなど)は登録済みの値を引き継ぐ
+pub async fn crawl(genre_id: &str, page: u32) -> AppResult<Option<u32>> {
+    let product_repo = di::DB_PRODUCT_REPOSITORY.get().await.clone();
+    let shop_repo = di::DB_SHOP_REPOSITORY.get().await.clone();
+    let api = di::RAKUTEN_API.get().await.clone();
+    let scorer = di::DEAL_SCORER.get().await.clone();
+    let bus = di::EVENT_BUS.get().await.clone();
+
+    println!("商品検索API ジャンルID: {}, ページ: {}", genre_id, page);
+    let res = api
+        .search(&SearchParams {
+            genre_id: genre_id.to_string(),
+            page,
+            hits: MAX_HITS,
+        })
+        .await?;
+    println!(
+        "商品検索API 件数: {}, ページ: {}/{}",
+        res.count, res.page, res.page_count
+    );
+
+    let mut shop_ids = HashSet::new();
+    let mut products = vec![];
+    let mut currents = HashMap::new();
+    let next_page = res.next_page();
+    for item in res.items {
+        let shop = item.shop(time::now());
+        if shop_ids.insert(shop.id.clone()) {
+            shop_repo.put_if_absent(shop.clone()).await?;
+            if let Some(name) = shop.name.clone() {
+                shop_repo.rename(&shop.id, name, time::now()).await?;
+            }
+        }
+
+        let current = product_repo
+            .get(&item.product_id())
+            .await
+            .not_found_to_none()?;
+        if let Some(current) = current.clone() {
+            currents.insert(current.id.clone(), current);
+        }
+        products.push(item.into_product(current, time::now()));
+    }
+
+    for product in scorer.score(products, time::now()).await? {
+        product_repo.put(product.clone()).await?;
+        bus::publish_changes(&bus, currents.get(&product.id), &product).await;
+    }
+
+    Ok(next_page)
+}
@@ -1,115 +1,381 @@
-use encoding_rs::EUC_JP;
-use encoding_rs_io::DecodeReaderBytesBuilder;
-use reqwest::Client;
-use sale::domain::product::{Product, Source, Status};
+use crate::structured_data;
+use crate::structured_data::{Availability, StructuredProduct};
+use chrono::Duration;
+use futures::{stream, StreamExt};
+use sale::bus;
+use sale::crawl::failure;
+use sale::crawl::fetcher::{FetchOptions, Outcome, Page, Validators};
+use sale::crawl::image_mirror;
+use sale::domain::crawl_failure::CrawlFailure;
+use sale::domain::product;
+use sale::domain::product::{Checked, Product, RecrawlPolicy, Source, Status};
 use sale::domain::time;
-use sale::errors::Kind::Internal;
+use sale::errors::AppError;
+use sale::errors::Kind::{Internal, Unavailable};
 use sale::infra::aws::ddb::cursor::Cursor;
+use sale::infra::aws::lambda::types::crawler_rakuten::{MirrorImagesRequest, Request, RequestBody};
 use sale::{di, AppResult};
 use scraper::{Html, Selector};
-use std::io::Read;
-use std::time::Duration;
-use tokio::time::sleep;
+use std::collections::HashMap;
+use std::time::Instant;
 
-pub async fn crawl(cursor: Option<Cursor>, only_preparing: bool) -> AppResult<Option<String>> {
+// 詳細クロールの続き
+pub enum Progress {
+    // 全ての商品を処理し終えた
+    Done,
+    // このカーソルから再開する。None の場合は先頭から
+    Continue(Option<String>),
+}
+
+// 期限まで商品ごとにクロールし、続きのカーソルを返す
+// 期限が無い場合(ローカル実行)は1ページで終了する
+// 期限切れになった場合は、処理し終えたページの末尾か途中のページの先頭から再開する
+pub async fn crawl(
+    cursor: Option<Cursor>,
+    only_preparing: bool,
+    deadline: Option<Instant>,
+) -> AppResult<Progress> {
     let product_repo = di::DB_PRODUCT_REPOSITORY.get().await.clone();
+    let envs = di::ENVIRONMENTS.clone();
+    let page_size = Some(envs.crawler_detail_page_size);
+    let policy = recrawl_policy();
 
-    let products = if only_preparing {
-        product_repo
-            .find_by_source_status(Source::Rakuten, Status::Prepare, cursor.clone(), Some(10))
-            .await?
-    } else {
-        product_repo
-            .find_by_source(Source::Rakuten, cursor.clone(), Some(10))
-            .await?
-    };
-    if products.is_empty() {
-        return Ok(None);
-    }
+    let mut cursor = cursor;
+    loop {
+        let products = if only_preparing {
+            product_repo
+                .find_by_source_status(Source::Rakuten, Status::Prepare, cursor.clone(), page_size)
+                .await?
+        } else {
+            product_repo
+                .find_by_source(Source::Rakuten, cursor.clone(), page_size)
+                .await?
+        };
+        let Some(last) = products.last().map(|v| v.cursor.clone()) else {
+            return Ok(Progress::Done);
+        };
 
-    println!(
-        "詳細クロールする商品数: {}, cursor: {}",
-        products.len(),
-        cursor.map(|v| v.to_string()).unwrap_or_default()
-    );
+        println!(
+            "詳細クロールする商品数: {}, cursor: {}",
+            products.len(),
+            cursor.as_ref().map(|v| v.to_string()).unwrap_or_default()
+        );
 
-    let mut cursor: Option<Cursor> = None;
+        // 相手側の制限によるものなので商品の失敗とはせず、このページの先頭から再開する
+        // 処理済みの商品は確認予定が先になっているため、やり直しても取得はしない
+        // 再開位置が無い場合はエラーとしてLambdaの再試行に任せる
+        let products = products.into_iter().map(|v| v.entity).collect();
+        match crawl_page(products, &policy, envs.crawler_detail_concurrency, deadline).await {
+            Ok(true) => {}
+            // ページの途中で期限に達した場合も同様にこのページの先頭から再開する
+            Ok(false) => {
+                println!("実行期限に達したため中断します");
+                return Ok(Progress::Continue(cursor.map(|v| v.to_string())));
+            }
+            Err(err) => {
+                return match cursor {
+                    Some(cursor) if err.kind == Unavailable => {
+                        eprintln!("クロールを中断します: {:?}", err);
+                        Ok(Progress::Continue(Some(cursor.to_string())))
+                    }
+                    _ => Err(err),
+                };
+            }
+        }
+
+        cursor = Some(last);
+        match deadline {
+            Some(v) if Instant::now() < v => {}
+            _ => return Ok(Progress::Continue(cursor.map(|v| v.to_string()))),
+        }
+    }
+}
+
+// 同じ店舗の商品は順番に、異なる店舗の商品は並行してクロールする
+// ホストごとのリクエスト間隔はレート制限で守られる
+// 期限に達した場合は残りの商品を取得せず false を返す
+async fn crawl_page(
+    products: Vec<Product>,
+    policy: &RecrawlPolicy,
+    concurrency: usize,
+    deadline: Option<Instant>,
+) -> AppResult<bool> {
+    let now = time::now();
+    let mut groups: HashMap<String, Vec<Product>> = HashMap::new();
     for product in products {
-        if detect_brandavenue(product.entity.detail_url.clone()) {
-            match collect_brandavenue(product.entity.clone()).await {
-                Ok(product) => {
-                    product_repo.put(product).await?;
+        // 確認予定を過ぎていない商品は取得しない
+        if !product.is_due(now) {
+            continue;
+        }
+        groups
+            .entry(shop_key(&product.detail_url))
+            .or_default()
+            .push(product);
+    }
+
+    let failure_repo = &di::DB_CRAWL_FAILURE_REPOSITORY.get().await.clone();
+    let results: Vec<AppResult<bool>> = stream::iter(groups.into_values())
+        .map(|products| async move {
+            for product in products {
+                if deadline.is_some_and(|v| Instant::now() >= v) {
+                    return Ok(false);
                 }
-                Err(err) => {
-                    eprintln!(
-                        "[brandavenue] 商品詳細のエラー: {:?}, 商品ID: {}",
-                        err,
-                        product.entity.id.as_str()
-                    );
+                let id = product.id.clone();
+                if let Crawled::Failed(err) = crawl_product(product, policy).await? {
+                    failure::record_product(failure_repo, &id, &err).await?;
                 }
             }
-        } else {
-            match collect(product.entity.clone()).await {
-                Ok(product) => {
-                    product_repo.put(product).await?;
+            Ok(true)
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    // 相手側の制限を優先して返し、呼び出し元で中断できるようにする
+    let mut completed = true;
+    let mut errors = vec![];
+    for result in results {
+        match result {
+            Ok(v) => completed &= v,
+            Err(err) => errors.push(err),
+        }
+    }
+    errors.sort_by_key(|v| v.kind != Unavailable);
+    errors.into_iter().next().map_or(Ok(completed), Err)
+}
+
+// https://item.rakuten.co.jp/tab11/john013/ の場合は item.rakuten.co.jp/tab11
+fn shop_key(url: &url::Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.path_segments().and_then(|mut v| v.next()) {
+        Some(shop) if host == "item.rakuten.co.jp" => format!("{}/{}", host, shop),
+        _ => host.to_string(),
+    }
+}
+
+// 失敗した商品の再実行用。確認予定に関わらずクロールする
+// 失敗した場合はエラーを返し、呼び出し元で元の失敗の試行回数として記録させる
+pub async fn crawl_one(id: &product::Id) -> AppResult<()> {
+    let product_repo = di::DB_PRODUCT_REPOSITORY.get().await.clone();
+    let product = product_repo.get(id).await?;
+    match crawl_product(product, &recrawl_policy()).await? {
+        Crawled::Saved => Ok(()),
+        Crawled::Failed(err) => Err(err),
+    }
+}
+
+fn recrawl_policy() -> RecrawlPolicy {
+    let envs = di::ENVIRONMENTS.clone();
+    RecrawlPolicy {
+        min_interval: Duration::hours(envs.recrawl_min_interval_hours),
+        max_interval: Duration::hours(envs.recrawl_max_interval_hours),
+    }
+}
+
+// 1商品のクロールの結果
+enum Crawled {
+    Saved,
+    // 商品には失敗を記録済み。失敗の記録(CrawlFailure)は呼び出し元が行う
+    Failed(AppError),
+}
+
+// 1商品をクロールして保存する
+// 相手側の制限(Unavailable)の場合のみエラーを返し、それ以外の失敗は結果として返す
+async fn crawl_product(current: Product, policy: &RecrawlPolicy) -> AppResult<Crawled> {
+    let product_repo = di::DB_PRODUCT_REPOSITORY.get().await.clone();
+    let failure_repo = di::DB_CRAWL_FAILURE_REPOSITORY.get().await.clone();
+    let shop_repo = di::DB_SHOP_REPOSITORY.get().await.clone();
+    let scorer = di::DEAL_SCORER.get().await.clone();
+    let bus = di::EVENT_BUS.get().await.clone();
+
+    let (label, result) = if detect_brandavenue(current.detail_url.clone()) {
+        ("[brandavenue] ", collect_brandavenue(current.clone()).await)
+    } else {
+        ("", collect(current.clone()).await)
+    };
+    match result {
+        Ok((crawled, seller)) => {
+            if let (Some(shop_id), Some(name)) = (current.shop_id.as_ref(), seller) {
+                shop_repo.rename(shop_id, name, time::now()).await?;
+            }
+            let checked = current.clone().check(crawled, policy, time::now());
+            let recovered = checked.recovered(&current);
+            match checked {
+                // 失敗から回復した場合は索引の属性やお得度も変わるため、変更と同じく保存する
+                Checked::Unchanged(product) if recovered => {
+                    let product = scorer.score_one(product, time::now()).await?;
+                    product_repo.put(product.clone()).await?;
+                    bus::publish_changes(&bus, Some(&current), &product).await;
+                }
+                Checked::Changed(product) => {
+                    let needs_mirroring = product.needs_mirroring();
+                    let id = product.id.clone();
+                    let product = scorer.score_one(product, time::now()).await?;
+                    product_repo.put(product.clone()).await?;
+                    bus::publish_changes(&bus, Some(&current), &product).await;
+                    if needs_mirroring {
+                        request_mirror_images(&id).await?;
+                    }
                 }
-                Err(err) => {
-                    eprintln!(
-                        "商品詳細のエラー: {:?}, 商品ID: {}",
-                        err,
-                        product.entity.id.as_str()
-                    );
+                Checked::Unchanged(product) => {
+                    println!("{}変更なし, 商品ID: {}", label, product.id.as_str());
+                    product_repo.touch(&product).await?;
                 }
             }
+            if current.failure_count > 0 {
+                failure::resolve(&failure_repo, &CrawlFailure::id_of_product(&current.id)).await?;
+            }
+        }
+        Err(err) if err.kind == Unavailable => return Err(err),
+        Err(err) => {
+            eprintln!(
+                "{}商品詳細のエラー: {:?}, 商品ID: {}",
+                label,
+                err,
+                current.id.as_str()
+            );
+            let product = scorer
+                .score_one(current.fail(time::now()), time::now())
+                .await?;
+            product_repo.put(product).await?;
+            return Ok(Crawled::Failed(err));
         }
+    }
+    Ok(Crawled::Saved)
+}
 
-        cursor = Some(product.cursor);
+// Lambdaでは別メッセージに分け、詳細クロールの時間を画像の処理に使わない
+async fn request_mirror_images(id: &product::Id) -> AppResult<()> {
+    let envs = di::ENVIRONMENTS.clone();
+    if !envs.with_lambda {
+        return mirror_images(id).await;
+    }
+
+    let sns = di::SNS_ADAPTER.get().await.clone();
+    sns.publish(
+        Request {
+            body: RequestBody::MirrorImages(MirrorImagesRequest {
+                product_id: id.as_str().to_string(),
+            }),
+            failure_id: None,
+        },
+        envs.crawler_rakuten_sns_arn.clone(),
+    )
+    .await
+}
 
-        sleep(Duration::from_secs(1)).await;
+pub async fn mirror_images(id: &product::Id) -> AppResult<()> {
+    let product_repo = di::DB_PRODUCT_REPOSITORY.get().await.clone();
+    let fetcher = di::CRAWL_FETCHER.get().await.clone();
+    let storage = di::IMAGE_STORAGE.get().await.clone();
+
+    let product = product_repo.get(id).await?;
+    if !product.needs_mirroring() {
+        return Ok(());
     }
+    let images = image_mirror::mirror(&fetcher, storage.as_ref(), &product).await?;
+    println!(
+        "画像を複製しました 商品ID: {}, 枚数: {}",
+        id.as_str(),
+        images.len()
+    );
+    product_repo.set_mirrored_images(id, images).await
+}
+
+// クロールした商品と、構造化データにあった販売者名
+type Collected = (Product, Option<String>);
 
-    Ok(cursor.map(|v| v.to_string()))
+enum Fetched {
+    Page(Box<Page>),
+    NotModified,
+    NotFound,
 }
 
-fn detect_brandavenue(url: url::Url) -> bool {
-    url.host_str() == Some("brandavenue.rakuten.co.jp")
-        || url.path_segments().map_or(false, |mut segments| {
-            segments.any(|s| s.contains("stylife"))
-        })
+// 一度取得済みの商品は条件付きGETで確認する
+async fn fetch(product: &Product) -> AppResult<Fetched> {
+    let fetcher = di::CRAWL_FETCHER.get().await.clone();
+    let validators = Validators {
+        etag: product.etag.clone(),
+        last_modified: product.last_modified.clone(),
+    };
+    let opts = FetchOptions {
+        follow_redirects: true,
+        validators: Some(validators).filter(|v| product.fingerprint.is_some() && !v.is_empty()),
+    };
+    match fetcher.fetch(&product.detail_url, opts).await? {
+        Outcome::NotModified => Ok(Fetched::NotModified),
+        Outcome::NotFound(_) => Ok(Fetched::NotFound),
+        outcome => outcome
+            .into_page(&product.detail_url)
+            .map(|v| Fetched::Page(Box::new(v))),
+    }
 }
 
-// https://brandavenue.rakuten.co.jp/item/JD3262
-async fn collect_brandavenue(product: Product) -> AppResult<Product> {
-    println!("[brandavenue] 商品詳細URL: {}", product.detail_url.as_str());
-    let body = Client::new()
-        .get(product.detail_url.as_str())
-        .send()
-        .await
-        .map_err(Internal.from_srcf())?
-        .text()
-        .await
-        .map_err(Internal.from_srcf())?;
-    let document = Html::parse_document(&body);
+// 購入ボタン周りの要素。販売終了・在庫切れの文言はここにだけ表示される
+const PURCHASE_SELECTOR: &str =
+    "#rakutenLimitedId_cart, .cart-button-container, .normal_reserve_item_stock, form[name='itemForm']";
+const BRANDAVENUE_PURCHASE_SELECTOR: &str = ".item-cart, .item-purchase, .item-stock";
 
-    // let mut file = File::create("test.html").map_err(Internal.from_srcf())?;
-    // file.write_all(body.as_bytes())
-    //     .map_err(Internal.from_srcf())?;
+// 販売終了・在庫切れを判定する。構造化データの在庫状況を優先し、
+// なければ購入ボタン周りの文言で判定する(ページ全体だと商品説明やおすすめ商品の文言に反応してしまう)
+fn detect_closed(
+    product: &Product,
+    document: &Html,
+    structured: &StructuredProduct,
+    purchase_selector: &str,
+) -> Option<Product> {
+    const EXPIRED_WORDS: [&str; 3] = ["販売期間外", "販売期間が終了", "販売を終了"];
+    const SOLD_OUT_WORDS: [&str; 3] = ["売り切れ", "在庫切れ", "SOLD OUT"];
 
-    // タイトル
-    let selector = Selector::parse(".item-name").unwrap();
-    let title = document
+    match structured.availability {
+        Some(Availability::OutOfStock | Availability::SoldOut) => {
+            return Some(product.clone().sold_out(time::now()))
+        }
+        Some(Availability::Discontinued) => return Some(product.clone().expire(time::now())),
+        Some(Availability::InStock | Availability::PreOrder) => return None,
+        Some(Availability::Other) | None => (),
+    }
+
+    let selector = Selector::parse(purchase_selector).unwrap();
+    let text = document
+        .select(&selector)
+        .flat_map(|v| v.text())
+        .collect::<String>();
+    if EXPIRED_WORDS.iter().any(|v| text.contains(v)) {
+        return Some(product.clone().expire(time::now()));
+    }
+    if SOLD_OUT_WORDS.iter().any(|v| text.contains(v)) {
+        return Some(product.clone().sold_out(time::now()));
+    }
+    None
+}
+
+// 構造化データにしかない項目を反映し、在庫状況があればステータスに反映する
+fn apply_structured(product: Product, structured: StructuredProduct) -> Product {
+    let product = product.describe(
+        structured.brand,
+        structured.currency,
+        structured.rating,
+        structured.review_count,
+    );
+    match structured.availability {
+        Some(Availability::OutOfStock | Availability::SoldOut) => product.sold_out(time::now()),
+        Some(Availability::Discontinued) => product.expire(time::now()),
+        _ => product,
+    }
+}
+
+fn select_text(document: &Html, selector: &str) -> Option<String> {
+    let selector = Selector::parse(selector).unwrap();
+    document
         .select(&selector)
         .next()
-        .ok_or(Internal.with("タイトルが見つかりませんでした"))?
-        .text()
-        .collect::<Vec<_>>()
-        .concat()
-        .trim()
-        .to_string();
+        .map(|v| v.text().collect::<Vec<_>>().concat().trim().to_string())
+}
 
-    // 画像
+fn select_image_urls(document: &Html, selector: &str) -> AppResult<Vec<url::Url>> {
     let mut image_urls: Vec<url::Url> = vec![];
-    let selector = Selector::parse("ul.item-images-list li.item-images-item img").unwrap();
+    let selector = Selector::parse(selector).unwrap();
     for element in document.select(&selector) {
         let mut image_url = element
             .value()
@@ -121,145 +387,194 @@ async fn collect_brandavenue(product: Product) -> AppResult<Product> {
         }
         image_urls.push(url::Url::parse(image_url.as_str()).map_err(Internal.from_srcf())?);
     }
+    Ok(image_urls)
+}
 
-    // 定価
-    let selector = Selector::parse(".item-price-retail-value").unwrap();
-    let retail_price = document
+fn select_breadcrumb(document: &Html, selector: &str) -> Vec<String> {
+    let selector = Selector::parse(selector).unwrap();
+    document
         .select(&selector)
-        .next()
-        .map(|v| v.text().collect::<Vec<_>>().concat().trim().to_string());
+        .map(|v| v.text().collect::<Vec<_>>().concat().trim().to_string())
+        .collect()
+}
+
+fn detect_brandavenue(url: url::Url) -> bool {
+    url.host_str() == Some("brandavenue.rakuten.co.jp")
+        || url
+            .path_segments()
+            .is_some_and(|mut segments| segments.any(|s| s.contains("stylife")))
+}
+
+// https://brandavenue.rakuten.co.jp/item/JD3262
+// 構造化データがあればCSSセレクタより優先する
+async fn collect_brandavenue(product: Product) -> AppResult<Collected> {
+    println!("[brandavenue] 商品詳細URL: {}", product.detail_url.as_str());
+    let page = match fetch(&product).await? {
+        Fetched::Page(page) => page,
+        // 変更がなければ現在の内容をそのまま返し、呼び出し元で変更なしと判定させる
+        Fetched::NotModified => return Ok((product, None)),
+        Fetched::NotFound => return Ok((product.remove(time::now()), None)),
+    };
+    let decoded = page.decode();
+    let product = product
+        .with_charset(decoded.encoding.name())
+        .with_validators(
+            page.validators.etag.clone(),
+            page.validators.last_modified.clone(),
+        );
+    let body = decoded.text;
+    let document = Html::parse_document(&body);
+    let structured = structured_data::extract(&document, &page.url);
+    if let Some(product) = detect_closed(
+        &product,
+        &document,
+        &structured,
+        BRANDAVENUE_PURCHASE_SELECTOR,
+    ) {
+        return Ok((product, None));
+    }
+
+    // let mut file = File::create("test.html").map_err(Internal.from_srcf())?;
+    // file.write_all(body.as_bytes())
+    //     .map_err(Internal.from_srcf())?;
+
+    // タイトル
+    let title = structured
+        .name
+        .clone()
+        .or_else(|| select_text(&document, ".item-name"))
+        .ok_or(Internal.with("タイトルが見つかりませんでした"))?;
+
+    // 画像
+    let image_urls = if structured.images.is_empty() {
+        select_image_urls(&document, "ul.item-images-list li.item-images-item img")?
+    } else {
+        structured.images.clone()
+    };
+
+    // 定価
+    let retail_price = select_text(&document, ".item-price-retail-value");
 
     // 値段
-    let selector = Selector::parse(".item-price-actual-value").unwrap();
-    let actual_price = document
-        .select(&selector)
-        .next()
-        .ok_or(Internal.with("値段が見つかりませんでした"))?
-        .text()
-        .collect::<Vec<_>>()
-        .concat()
-        .trim()
-        .to_string();
+    let actual_price = structured
+        .price
+        .clone()
+        .or_else(|| select_text(&document, ".item-price-actual-value"))
+        .ok_or(Internal.with("値段が見つかりませんでした"))?;
 
     // 割引率
-    let selector = Selector::parse(".item-price-retail-off").unwrap();
-    let retail_off = document
-        .select(&selector)
-        .next()
-        .map(|v| v.text().collect::<Vec<_>>().concat().trim().to_string());
+    let retail_off = select_text(&document, ".item-price-retail-off");
 
     // ポイント
     // JSで動的に生成されているため、取得できない
 
     // パンクズ
-    let mut breadcrumb: Vec<String> = vec![];
-    let selector = Selector::parse("ul.breadcrumb-list li.breadcrumb-item a").unwrap();
-    for element in document.select(&selector) {
-        let v = element
-            .text()
-            .collect::<Vec<_>>()
-            .concat()
-            .trim()
-            .to_string();
-        breadcrumb.push(v);
-    }
+    let breadcrumb = if structured.breadcrumb.is_empty() {
+        select_breadcrumb(&document, "ul.breadcrumb-list li.breadcrumb-item a")
+    } else {
+        structured.breadcrumb.clone()
+    };
 
-    Ok(product.clone().update(
-        Some(title),
-        image_urls,
-        retail_price,
-        Some(actual_price),
-        retail_off,
-        breadcrumb,
-        product.points,
-        time::now(),
-    ))
+    let product = product
+        .clone()
+        .update(
+            Some(title),
+            image_urls,
+            retail_price,
+            Some(actual_price),
+            retail_off,
+            breadcrumb,
+            product.points,
+            time::now(),
+        )
+        .activate(time::now());
+    let seller = structured.seller.clone();
+    Ok((apply_structured(product, structured), seller))
 }
 
 // https://item.rakuten.co.jp/tab11/john013/
-async fn collect(product: Product) -> AppResult<Product> {
+// 構造化データがあればCSSセレクタより優先する
+async fn collect(product: Product) -> AppResult<Collected> {
     println!("商品詳細URL: {}", product.detail_url.as_str());
-    let response = Client::new()
-        .get(product.detail_url.as_str())
-        .send()
-        .await
-        .map_err(Internal.from_srcf())?;
-    let bytes = response.bytes().await.map_err(Internal.from_srcf())?;
-    let mut decoder = DecodeReaderBytesBuilder::new()
-        .encoding(Some(EUC_JP))
-        .build(bytes.as_ref());
-    let mut body = String::new();
-    decoder.read_to_string(&mut body).unwrap();
+    let page = match fetch(&product).await? {
+        Fetched::Page(page) => page,
+        // 変更がなければ現在の内容をそのまま返し、呼び出し元で変更なしと判定させる
+        Fetched::NotModified => return Ok((product, None)),
+        Fetched::NotFound => return Ok((product.remove(time::now()), None)),
+    };
+    let decoded = page.decode();
+    let product = product
+        .with_charset(decoded.encoding.name())
+        .with_validators(
+            page.validators.etag.clone(),
+            page.validators.last_modified.clone(),
+        );
+    let body = decoded.text;
     let document = Html::parse_document(&body);
+    let structured = structured_data::extract(&document, &page.url);
+    if let Some(product) = detect_closed(&product, &document, &structured, PURCHASE_SELECTOR) {
+        return Ok((product, None));
+    }
 
     // let mut file = File::create("test.html").map_err(Internal.from_srcf())?;
     // file.write_all(body.as_bytes())
     //     .map_err(Internal.from_srcf())?;
 
     // タイトル
-    let selector = Selector::parse(".normal_reserve_item_name").unwrap();
-    let title = document
-        .select(&selector)
-        .next()
-        .ok_or(Internal.with("タイトルが見つかりませんでした"))?
-        .text()
-        .collect::<Vec<_>>()
-        .concat()
-        .trim()
-        .to_string();
+    let title = structured
+        .name
+        .clone()
+        .or_else(|| select_text(&document, ".normal_reserve_item_name"))
+        .ok_or(Internal.with("タイトルが見つかりませんでした"))?;
 
     // 画像
-    let mut image_urls: Vec<url::Url> = vec![];
-    let selector = Selector::parse(".sale_desc img").unwrap();
-    for element in document.select(&selector) {
-        let mut image_url = element
-            .value()
-            .attr("src")
-            .ok_or(Internal.with("画像URLが見つかりませんでした"))?
-            .to_string();
-        if image_url.starts_with("//") {
-            image_url = format!("https:{}", image_url);
-        }
-        image_urls.push(url::Url::parse(image_url.as_str()).map_err(Internal.from_srcf())?);
-    }
+    let image_urls = if structured.images.is_empty() {
+        select_image_urls(&document, ".sale_desc img")?
+    } else {
+        structured.images.clone()
+    };
 
     // 定価、割引率は見当たらない
 
     // 値段
-    let selector = Selector::parse("#priceCalculationConfig").unwrap();
-    let actual_price = document
-        .select(&selector)
-        .next()
-        .ok_or(Internal.with("値段が見つかりませんでした"))?
-        .value()
-        .attr("data-price")
-        .ok_or(Internal.with("値段が見つかりませんでした"))?
-        .to_string();
+    let actual_price = match structured.price.clone() {
+        Some(v) => v,
+        None => {
+            let selector = Selector::parse("#priceCalculationConfig").unwrap();
+            document
+                .select(&selector)
+                .next()
+                .ok_or(Internal.with("値段が見つかりませんでした"))?
+                .value()
+                .attr("data-price")
+                .ok_or(Internal.with("値段が見つかりませんでした"))?
+                .to_string()
+        }
+    };
 
     // ポイント
     // JSで動的に生成されているため、取得できない
 
     // パンクズ
-    let mut breadcrumb: Vec<String> = vec![];
-    let selector = Selector::parse(".sdtext a").unwrap();
-    for element in document.select(&selector) {
-        let v = element
-            .text()
-            .collect::<Vec<_>>()
-            .concat()
-            .trim()
-            .to_string();
-        breadcrumb.push(v);
-    }
+    let breadcrumb = if structured.breadcrumb.is_empty() {
+        select_breadcrumb(&document, ".sdtext a")
+    } else {
+        structured.breadcrumb.clone()
+    };
 
-    Ok(product.clone().update(
-        Some(title),
-        image_urls,
-        None,
-        Some(actual_price),
-        None,
-        breadcrumb,
-        product.points,
-        time::now(),
-    ))
+    let product = product
+        .clone()
+        .update(
+            Some(title),
+            image_urls,
+            None,
+            Some(actual_price),
+            None,
+            breadcrumb,
+            product.points,
+            time::now(),
+        )
+        .activate(time::now());
+    let seller = structured.seller.clone();
+    Ok((apply_structured(product, structured), seller))
 }
@@ -1,20 +1,34 @@
-use reqwest::Client;
+use sale::bus;
+use sale::crawl::fetcher::{FetchOptions, Outcome};
+use sale::domain::shop::Shop;
 use sale::domain::time;
 use sale::errors::Kind::Internal;
 use sale::errors::NotFoundToNone;
 use sale::{di, domain, AppResult};
 use scraper::{Html, Selector};
-use tokio::time::{sleep, Duration};
+use std::collections::{HashMap, HashSet};
 
 pub async fn crawl(url: &url::Url) -> AppResult<Option<u32>> {
     let product_repo = di::DB_PRODUCT_REPOSITORY.get().await.clone();
+    let shop_repo = di::DB_SHOP_REPOSITORY.get().await.clone();
+    let scorer = di::DEAL_SCORER.get().await.clone();
+    let bus = di::EVENT_BUS.get().await.clone();
 
     let products = collect(url).await?;
     if products.is_empty() {
         return Ok(None);
     }
-    for product in products {
+    let genre_id = genre_id_of(url);
+    let mut shop_ids = HashSet::new();
+    let mut updated = vec![];
+    let mut currents = HashMap::new();
+    for (product, shop) in products {
+        if shop_ids.insert(shop.id.clone()) {
+            shop_repo.put_if_absent(shop).await?;
+        }
+        let product = product.with_genre(genre_id.clone());
         if let Some(current) = product_repo.get(&product.id).await.not_found_to_none()? {
+            currents.insert(current.id.clone(), current.clone());
             let current = current.clone().update(
                 current.title,
                 current.image_urls,
@@ -25,10 +39,18 @@ pub async fn crawl(url: &url::Url) -> AppResult<Option<u32>> {
                 product.points,
                 time::now(),
             );
-            product_repo.put(current.clone()).await?;
+            let current = match (product.shop_id, product.item_id) {
+                (Some(shop_id), Some(item_id)) => current.with_shop(shop_id, item_id),
+                _ => current,
+            };
+            updated.push(current.with_genre(genre_id.clone()));
             continue;
         }
+        updated.push(product);
+    }
+    for product in scorer.score(updated, time::now()).await? {
         product_repo.put(product.clone()).await?;
+        bus::publish_changes(&bus, currents.get(&product.id), &product).await;
     }
 
     let page = url
@@ -38,26 +60,27 @@ pub async fn crawl(url: &url::Url) -> AppResult<Option<u32>> {
         .ok_or(Internal.with("pが見つかりませんでした"))?;
     let page = page.parse::<u32>().map_err(Internal.from_srcf())?;
 
-    sleep(Duration::from_secs(1)).await;
-
     Ok(Some(page + 1))
 }
 
-async fn collect(url: &url::Url) -> AppResult<Vec<domain::product::Product>> {
+// https://search.rakuten.co.jp/search/mall/-/551177/?p=1 の 551177
+pub fn genre_id_of(url: &url::Url) -> Option<String> {
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    segments
+        .windows(2)
+        .find(|v| v[0] == "-" && !v[1].is_empty() && v[1].chars().all(|c| c.is_ascii_digit()))
+        .map(|v| v[1].to_string())
+}
+
+async fn collect(url: &url::Url) -> AppResult<Vec<(domain::product::Product, Shop)>> {
     println!("商品一覧URL: {}", url.as_str());
-    let client = Client::builder()
-        .redirect(reqwest::redirect::Policy::none())
-        .build()
-        .map_err(Internal.from_srcf())?;
-    let response = client
-        .get(url.as_str())
-        .send()
-        .await
-        .map_err(Internal.from_srcf())?;
-    if response.status().is_redirection() {
-        return Ok(vec![]);
-    }
-    let body = response.text().await.map_err(Internal.from_srcf())?;
+    let fetcher = di::CRAWL_FETCHER.get().await.clone();
+    let page = match fetcher.fetch(url, FetchOptions::default()).await? {
+        // 最終ページを超えるとリダイレクトされる
+        Outcome::Redirect { .. } => return Ok(vec![]),
+        outcome => outcome.into_page(url)?,
+    };
+    let body = page.decode().text;
     let document = Html::parse_document(&body);
 
     // let mut file = File::create("test.html").map_err(Internal.from_srcf())?;
@@ -68,20 +91,8 @@ async fn collect(url: &url::Url) -> AppResult<Vec<domain::product::Product>> {
     let url_selector = Selector::parse(".image-link-wrapper--3P6dv").unwrap();
     let points_selector = Selector::parse(".points--AHzKn span").unwrap();
 
-    let mut products: Vec<domain::product::Product> = vec![];
+    let mut products = vec![];
     for element in document.select(&item_selector) {
-        // ID
-        let item_id = element
-            .value()
-            .attr("data-id")
-            .ok_or(Internal.with("data-idが見つかりませんでした"))?
-            .to_string();
-        let shop_id = element
-            .value()
-            .attr("data-shop-id")
-            .ok_or(Internal.with("data-shop-idが見つかりませんでした"))?
-            .to_string();
-
         // 詳細URL
         let e_ref = element
             .select(&url_selector)
@@ -94,6 +105,25 @@ async fn collect(url: &url::Url) -> AppResult<Vec<domain::product::Product>> {
             .to_string();
         let url = url::Url::parse(&url).map_err(Internal.from_srcf())?;
 
+        // ID
+        // APIモードと同じ商品IDになるよう、数値の data-shop-id・data-id ではなく詳細URLの店舗コード・商品番号を使う
+        // 詳細URLが楽天市場の商品ページでない場合のみ data-shop-id・data-id を使う
+        let (shop_id, item_id) = match domain::product::Product::codes_of(&url) {
+            Some(v) => v,
+            None => (
+                element
+                    .value()
+                    .attr("data-shop-id")
+                    .ok_or(Internal.with("data-shop-idが見つかりませんでした"))?
+                    .to_string(),
+                element
+                    .value()
+                    .attr("data-id")
+                    .ok_or(Internal.with("data-idが見つかりませんでした"))?
+                    .to_string(),
+            ),
+        };
+
         // ポイント(詳細では静的に取れない)
         let e_ref = element
             .select(&points_selector)
@@ -102,13 +132,15 @@ async fn collect(url: &url::Url) -> AppResult<Vec<domain::product::Product>> {
         let points = e_ref.text().collect::<Vec<_>>().concat().trim().to_string();
 
         let source = domain::product::Source::Rakuten;
+        let shop = Shop::new(source, shop_id.clone(), Shop::url_of(&url), time::now());
         let product = domain::product::Product::new(
-            domain::product::Id::new(format!("{}-{}-{}", source, shop_id, item_id)),
+            domain::product::Product::id_of(source, &shop_id, &item_id),
             source,
             url,
             time::now(),
-        );
-        products.push(product.update(
+        )
+        .with_shop(shop.id.clone(), item_id);
+        let product = product.update(
             None,
             vec![],
             None,
@@ -117,7 +149,8 @@ async fn collect(url: &url::Url) -> AppResult<Vec<domain::product::Product>> {
             vec![],
             Some(points),
             time::now(),
-        ));
+        );
+        products.push((product, shop));
     }
 
     Ok(products)
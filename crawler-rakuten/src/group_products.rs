@@ -0,0 +1,59 @@
+use sale::domain::product::{Product, Status};
+use sale::domain::product_group::ProductGroup;
+use sale::domain::{product_group, time};
+use sale::{di, AppResult};
+use std::collections::{HashMap, HashSet};
+
+// 詳細クロールの後に全商品から同じ商品のまとまりを計算し直す
+// 販売中の商品だけをまとめ、所属が変わった商品とグループだけを更新する
+pub async fn group() -> AppResult<()> {
+    let product_repo = di::DB_PRODUCT_REPOSITORY.get().await.clone();
+    let group_repo = di::DB_PRODUCT_GROUP_REPOSITORY.get().await.clone();
+    let now = time::now();
+
+    let products = product_repo.find_all().await?;
+    let active: Vec<Product> = products
+        .iter()
+        .filter(|v| v.status == Status::Active)
+        .cloned()
+        .collect();
+    let groups: Vec<ProductGroup> = product_group::cluster(&active)
+        .into_iter()
+        .map(|v| ProductGroup::new(v, now))
+        .collect();
+
+    let mut current: HashMap<product_group::Id, ProductGroup> = group_repo
+        .find_all()
+        .await?
+        .into_iter()
+        .map(|v| (v.id.clone(), v))
+        .collect();
+    let mut group_ids = HashMap::new();
+    for group in groups {
+        for product_id in group.product_ids.iter() {
+            group_ids.insert(product_id.clone(), group.id.clone());
+        }
+        let existing = current.remove(&group.id);
+        if let Some(group) = group.merge(existing.as_ref()) {
+            group_repo.put(group).await?;
+        }
+    }
+    for id in current.keys() {
+        group_repo.delete(id).await?;
+    }
+
+    let mut changed = 0;
+    for product in products {
+        let group_id = group_ids.get(&product.id).cloned();
+        if product.group_id != group_id {
+            product_repo.set_group(&product.id, group_id).await?;
+            changed += 1;
+        }
+    }
+    println!(
+        "商品のグループを更新しました グループ数: {}, 更新した商品数: {}",
+        group_ids.values().collect::<HashSet<_>>().len(),
+        changed
+    );
+    Ok(())
+}
@@ -1,17 +1,34 @@
 use anyhow::anyhow;
-use lambda_runtime::{service_fn, Error, LambdaEvent};
+use lambda_runtime::{service_fn, Context, Error, LambdaEvent};
+use sale::crawl::failure;
+use sale::domain::{crawl_failure, product};
+use sale::env::RakutenCrawlerMode;
 use sale::errors::Kind::Internal;
 use sale::infra::aws::ddb::cursor::Cursor;
 use sale::infra::aws::lambda::types::crawler_rakuten::{
-    CrawlDetailRequest, CrawlListRequest, Request, RequestBody,
+    CrawlApiRequest, CrawlDetailRequest, CrawlListRequest, Request, RequestBody,
 };
 use sale::infra::aws::lambda::types::sns::EventData;
 use sale::{di, AppResult};
 use serde_json::Value;
 use std::env;
+use std::time::{Duration, Instant, SystemTime};
 
+mod build_categories;
+mod crawl_product_api;
 mod crawl_product_detail;
 mod crawl_product_list;
+mod group_products;
+mod score_deals;
+mod structured_data;
+
+use crawl_product_detail::Progress;
+
+// 一覧はこのページまで取得する
+const MAX_LIST_PAGE: u32 = 2;
+
+// 実行中の商品の取得や続きの発行のため、Lambdaの実行期限より前に切り上げる
+const DEADLINE_MARGIN: Duration = Duration::from_secs(60);
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
@@ -30,8 +47,8 @@ async fn main() -> Result<(), Error> {
     } else {
         let args: Vec<String> = env::args().collect();
         let json = args.get(1).expect("json params is required");
-        let body: Request = serde_json::from_str(&json).unwrap();
-        if let Err(err) = handler(body).await {
+        let body: Request = serde_json::from_str(json).unwrap();
+        if let Err(err) = handler(body, None, None).await {
             eprintln!("error: {:?}", err);
             return Err(anyhow!(err).into());
         }
@@ -41,13 +58,15 @@ async fn main() -> Result<(), Error> {
 }
 
 async fn bridge(event: LambdaEvent<Value>) -> Result<(), Error> {
+    let deadline = deadline_of(&event.context);
+
     // SNS経由
     let data: Result<EventData, String> =
         serde_json::from_value(event.payload.clone()).map_err(|v| v.to_string());
     if let Ok(data) = data {
-        let request: Request =
-            serde_json::from_str(&data.records.first().unwrap().sns.message).unwrap();
-        let result = handler(request).await;
+        let sns = &data.records.first().unwrap().sns;
+        let request: Request = serde_json::from_str(&sns.message).unwrap();
+        let result = handler(request, sns.message_id.clone(), Some(deadline)).await;
         return match result {
             Ok(_) => Ok(()),
             Err(err) => {
@@ -61,7 +80,7 @@ async fn bridge(event: LambdaEvent<Value>) -> Result<(), Error> {
     let data: Result<Request, String> =
         serde_json::from_value(event.payload.clone()).map_err(|v| v.to_string());
     if let Ok(data) = data {
-        let result = handler(data).await;
+        let result = handler(data, Some(event.context.request_id.clone()), Some(deadline)).await;
         return match result {
             Ok(_) => Ok(()),
             Err(err) => {
@@ -74,7 +93,43 @@ async fn bridge(event: LambdaEvent<Value>) -> Result<(), Error> {
     Err(anyhow!("不正なペイロードです").into())
 }
 
-async fn handler(req: Request) -> AppResult<()> {
+// Lambdaの実行期限から余裕を引いた時刻
+fn deadline_of(context: &Context) -> Instant {
+    let remaining = context
+        .deadline()
+        .duration_since(SystemTime::now())
+        .unwrap_or_default();
+    Instant::now() + remaining.saturating_sub(DEADLINE_MARGIN)
+}
+
+// 失敗したメッセージは再実行できるように記録する
+// delivery_id はLambdaの再試行でも変わらない配信のIDで、同じ配信の失敗を二重に数えないために使う
+// deadline が無い場合(ローカル実行)は詳細クロールを1ページで終了する
+async fn handler(
+    req: Request,
+    delivery_id: Option<String>,
+    deadline: Option<Instant>,
+) -> AppResult<()> {
+    let failure_repo = di::DB_CRAWL_FAILURE_REPOSITORY.get().await.clone();
+    match dispatch(req.clone(), deadline).await {
+        Ok(_) => {
+            if let Some(id) = req.failure_id {
+                failure::resolve(&failure_repo, &crawl_failure::Id::new(id)).await?;
+            }
+            Ok(())
+        }
+        Err(err) => {
+            if let Err(record_err) =
+                failure::record_message(&failure_repo, &req, delivery_id, &err).await
+            {
+                eprintln!("失敗したメッセージの記録に失敗しました: {:?}", record_err);
+            }
+            Err(err)
+        }
+    }
+}
+
+async fn dispatch(req: Request, deadline: Option<Instant>) -> AppResult<()> {
     let sns_arn = di::ENVIRONMENTS.crawler_rakuten_sns_arn.clone();
     let sns = di::SNS_ADAPTER.get().await.clone();
     let with_lambda = di::ENVIRONMENTS.with_lambda;
@@ -82,11 +137,21 @@ async fn handler(req: Request) -> AppResult<()> {
     match req.body {
         RequestBody::CrawlEntrypoint => {
             for url in LIST_URLS.into_iter() {
+                let body = match di::ENVIRONMENTS.rakuten_crawler_mode {
+                    RakutenCrawlerMode::Html => RequestBody::CrawlList(CrawlListRequest {
+                        url: url.to_string(),
+                    }),
+                    RakutenCrawlerMode::Api => {
+                        let url = url::Url::parse(url).map_err(Internal.from_srcf())?;
+                        let genre_id = crawl_product_list::genre_id_of(&url)
+                            .ok_or(Internal.with(format!("ジャンルIDが見つかりません: {}", url)))?;
+                        RequestBody::CrawlApi(CrawlApiRequest { genre_id, page: 1 })
+                    }
+                };
                 sns.publish(
                     Request {
-                        body: RequestBody::CrawlList(CrawlListRequest {
-                            url: url.to_string(),
-                        }),
+                        body,
+                        failure_id: None,
                     },
                     sns_arn.clone(),
                 )
@@ -94,6 +159,31 @@ async fn handler(req: Request) -> AppResult<()> {
             }
             Ok(())
         }
+        RequestBody::CrawlApi(body) => {
+            if let Some(next_page) = crawl_product_api::crawl(&body.genre_id, body.page).await? {
+                if !with_lambda {
+                    println!("ローカル実行により終了");
+                    return Ok(());
+                }
+
+                if next_page <= MAX_LIST_PAGE {
+                    sns.publish(
+                        Request {
+                            body: RequestBody::CrawlApi(CrawlApiRequest {
+                                genre_id: body.genre_id,
+                                page: next_page,
+                            }),
+                            failure_id: None,
+                        },
+                        sns_arn.clone(),
+                    )
+                    .await?;
+                } else {
+                    println!("ジャンル{}がページ上限を超えました", body.genre_id);
+                }
+            }
+            Ok(())
+        }
         RequestBody::CrawlList(body) => {
             let mut url = url::Url::parse(&body.url.clone()).map_err(Internal.from_srcf())?;
             if let Some(next_page) = crawl_product_list::crawl(&url).await? {
@@ -102,7 +192,7 @@ async fn handler(req: Request) -> AppResult<()> {
                     return Ok(());
                 }
 
-                if next_page <= 2 {
+                if next_page <= MAX_LIST_PAGE {
                     let query: Vec<(String, String)> = url
                         .query_pairs()
                         .filter(|(name, _)| name != "p")
@@ -118,6 +208,7 @@ async fn handler(req: Request) -> AppResult<()> {
                             body: RequestBody::CrawlList(CrawlListRequest {
                                 url: url.to_string(),
                             }),
+                            failure_id: None,
                         },
                         sns_arn.clone(),
                     )
@@ -130,32 +221,59 @@ async fn handler(req: Request) -> AppResult<()> {
             Ok(())
         }
         RequestBody::CrawlDetail(body) => {
-            let next_cursor = crawl_product_detail::crawl(
-                body.cursor.map(|v| Cursor::from(v)),
+            let progress = crawl_product_detail::crawl(
+                body.cursor.map(Cursor::from),
                 body.only_preparing,
+                deadline,
             )
             .await?;
             if !with_lambda {
-                println!("ローカル実行により終了 next_cursor: {:?}", next_cursor);
+                if let Progress::Continue(cursor) = progress {
+                    println!("ローカル実行により終了 next_cursor: {:?}", cursor);
+                }
                 return Ok(());
             }
 
-            if let Some(cursor) = next_cursor {
+            if let Progress::Continue(cursor) = progress {
                 sns.publish(
                     Request {
                         body: RequestBody::CrawlDetail(CrawlDetailRequest {
-                            cursor: Some(cursor),
+                            cursor,
                             only_preparing: body.only_preparing,
                         }),
+                        failure_id: None,
                     },
                     sns_arn.clone(),
                 )
                 .await?;
             } else {
                 println!("全ての商品詳細のクロールが完了しました");
+                for body in [
+                    RequestBody::GroupProducts,
+                    RequestBody::BuildCategories,
+                    RequestBody::ScoreDeals,
+                ] {
+                    sns.publish(
+                        Request {
+                            body,
+                            failure_id: None,
+                        },
+                        sns_arn.clone(),
+                    )
+                    .await?;
+                }
             }
             Ok(())
         }
+        RequestBody::CrawlProduct(body) => {
+            crawl_product_detail::crawl_one(&product::Id::new(body.product_id)).await
+        }
+        RequestBody::MirrorImages(body) => {
+            crawl_product_detail::mirror_images(&product::Id::new(body.product_id)).await
+        }
+        RequestBody::GroupProducts => group_products::group().await,
+        RequestBody::BuildCategories => build_categories::build().await,
+        RequestBody::ScoreDeals => score_deals::score().await,
     }
 }
 
@@ -0,0 +1,43 @@
+use sale::bus;
+use sale::domain::product::DealScore;
+use sale::domain::time;
+use sale::{di, AppResult};
+
+// 1回の人気の読み込みでまとめる商品数
+const CHUNK_SIZE: usize = 100;
+// 全商品を読み込まず、セグメント並列のScanでページごとに処理する
+const SCAN_SEGMENTS: i32 = 4;
+
+// 新しさと人気は時間とともに変わるため、詳細クロールの後に全商品のお得度を計算し直す
+// 値が一定以上変わった商品だけを更新する
+pub async fn score() -> AppResult<()> {
+    let product_repo = di::DB_PRODUCT_REPOSITORY.get().await.clone();
+    let scorer = di::DEAL_SCORER.get().await.clone();
+    let bus = di::EVENT_BUS.get().await.clone();
+
+    let mut rx = product_repo.scan(SCAN_SEGMENTS);
+    let mut total = 0;
+    let mut changed = 0;
+    while let Some(page) = rx.recv().await {
+        let page = page?;
+        total += page.len();
+        for chunk in page.chunks(CHUNK_SIZE) {
+            let current = chunk.to_vec();
+            let scored = scorer.score(current.clone(), time::now()).await?;
+            for (current, scored) in current.iter().zip(scored) {
+                if DealScore::changed_significantly(current.deal_score, scored.deal_score) {
+                    product_repo
+                        .set_deal_score(&scored.id, scored.deal_score)
+                        .await?;
+                    bus::publish_changes(&bus, Some(current), &scored).await;
+                    changed += 1;
+                }
+            }
+        }
+    }
+    println!(
+        "お得度を更新しました 商品数: {}, 更新した商品数: {}",
+        total, changed
+    );
+    Ok(())
+}
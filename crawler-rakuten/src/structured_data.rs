@@ -0,0 +1,271 @@
+use scraper::{ElementRef, Html, Selector};
+use serde_json::{Map, Value};
+
+// schema.orgのProduct/BreadcrumbListから取り出した値
+// JSON-LD、microdataの順に優先し、足りない項目だけを後から埋める
+#[derive(Debug, Clone, Default)]
+pub struct StructuredProduct {
+    pub name: Option<String>,
+    pub images: Vec<url::Url>,
+    pub price: Option<String>,
+    pub currency: Option<String>,
+    pub availability: Option<Availability>,
+    pub brand: Option<String>,
+    pub rating: Option<f64>,
+    pub review_count: Option<u32>,
+    pub breadcrumb: Vec<String>,
+    // 販売者(店舗)の名前
+    pub seller: Option<String>,
+}
+impl StructuredProduct {
+    fn merge(self, other: Self) -> Self {
+        Self {
+            name: self.name.or(other.name),
+            images: if self.images.is_empty() {
+                other.images
+            } else {
+                self.images
+            },
+            price: self.price.or(other.price),
+            currency: self.currency.or(other.currency),
+            availability: self.availability.or(other.availability),
+            brand: self.brand.or(other.brand),
+            rating: self.rating.or(other.rating),
+            review_count: self.review_count.or(other.review_count),
+            breadcrumb: if self.breadcrumb.is_empty() {
+                other.breadcrumb
+            } else {
+                self.breadcrumb
+            },
+            seller: self.seller.or(other.seller),
+        }
+    }
+}
+
+// https://schema.org/ItemAvailability
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Availability {
+    InStock,
+    OutOfStock,
+    SoldOut,
+    Discontinued,
+    PreOrder,
+    Other,
+}
+impl Availability {
+    fn parse(v: &str) -> Self {
+        match v.rsplit('/').next().unwrap_or(v) {
+            "InStock" | "LimitedAvailability" | "OnlineOnly" | "InStoreOnly" => Self::InStock,
+            "OutOfStock" | "BackOrder" => Self::OutOfStock,
+            "SoldOut" => Self::SoldOut,
+            "Discontinued" => Self::Discontinued,
+            "PreOrder" | "PreSale" => Self::PreOrder,
+            _ => Self::Other,
+        }
+    }
+}
+
+pub fn extract(document: &Html, base_url: &url::Url) -> StructuredProduct {
+    let json_ld = from_nodes(json_ld_nodes(document), base_url);
+    let microdata = from_nodes(microdata_nodes(document), base_url);
+    json_ld.merge(microdata)
+}
+
+fn from_nodes(nodes: Vec<Value>, base_url: &url::Url) -> StructuredProduct {
+    let mut product = StructuredProduct::default();
+    for node in nodes {
+        if has_type(&node, "Product") {
+            product = product.merge(from_product(&node, base_url));
+        } else if has_type(&node, "BreadcrumbList") && product.breadcrumb.is_empty() {
+            product.breadcrumb = from_breadcrumb(&node);
+        }
+    }
+    product
+}
+
+fn from_product(node: &Value, base_url: &url::Url) -> StructuredProduct {
+    // Offer/AggregateOfferのどちらでも、配列の場合は先頭を使う
+    let offer = first(node.get("offers"));
+    let rating = first(node.get("aggregateRating"));
+
+    StructuredProduct {
+        name: node.get("name").and_then(as_string),
+        images: as_list(node.get("image"))
+            .iter()
+            .filter_map(|v| as_string(v).or_else(|| v.get("url").and_then(as_string)))
+            .filter_map(|v| base_url.join(&v).ok())
+            .collect(),
+        price: offer.and_then(|v| {
+            v.get("price")
+                .or_else(|| v.get("lowPrice"))
+                .and_then(as_string)
+        }),
+        currency: offer
+            .and_then(|v| v.get("priceCurrency"))
+            .and_then(as_string),
+        availability: offer
+            .and_then(|v| v.get("availability"))
+            .and_then(as_string)
+            .map(|v| Availability::parse(&v)),
+        brand: first(node.get("brand"))
+            .and_then(|v| as_string(v).or_else(|| v.get("name").and_then(as_string))),
+        rating: rating
+            .and_then(|v| v.get("ratingValue"))
+            .and_then(as_string)
+            .and_then(|v| v.parse().ok()),
+        review_count: rating
+            .and_then(|v| v.get("reviewCount").or_else(|| v.get("ratingCount")))
+            .and_then(as_string)
+            .and_then(|v| v.parse().ok()),
+        breadcrumb: vec![],
+        seller: offer
+            .and_then(|v| first(v.get("seller")))
+            .and_then(|v| as_string(v).or_else(|| v.get("name").and_then(as_string))),
+    }
+}
+
+fn from_breadcrumb(node: &Value) -> Vec<String> {
+    let mut items: Vec<(i64, String)> = as_list(node.get("itemListElement"))
+        .iter()
+        .filter_map(|v| {
+            let position = v
+                .get("position")
+                .and_then(as_string)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default();
+            let name = v
+                .get("name")
+                .and_then(as_string)
+                .or_else(|| v.get("item")?.get("name").and_then(as_string))?;
+            Some((position, name))
+        })
+        .collect();
+    items.sort_by_key(|(position, _)| *position);
+    items.into_iter().map(|(_, name)| name).collect()
+}
+
+fn json_ld_nodes(document: &Html) -> Vec<Value> {
+    let selector = Selector::parse(r#"script[type="application/ld+json"]"#).unwrap();
+    let mut nodes = vec![];
+    for element in document.select(&selector) {
+        let text = element.text().collect::<Vec<_>>().concat();
+        // 壊れたJSON-LDは無視してCSSセレクタにフォールバックする
+        match serde_json::from_str::<Value>(text.trim()) {
+            Ok(v) => flatten(v, &mut nodes),
+            Err(err) => eprintln!("JSON-LDの解析に失敗しました: {:?}", err),
+        }
+    }
+    nodes
+}
+
+// 配列や@graphにまとめられたノードを平らにする
+fn flatten(v: Value, nodes: &mut Vec<Value>) {
+    match v {
+        Value::Array(list) => list.into_iter().for_each(|v| flatten(v, nodes)),
+        Value::Object(mut map) => {
+            if let Some(graph) = map.remove("@graph") {
+                flatten(graph, nodes);
+            }
+            nodes.push(Value::Object(map));
+        }
+        _ => {}
+    }
+}
+
+// microdataをJSON-LDと同じ形に変換して扱う
+fn microdata_nodes(document: &Html) -> Vec<Value> {
+    let selector = Selector::parse("[itemscope][itemtype]:not([itemprop])").unwrap();
+    document.select(&selector).map(microdata_item).collect()
+}
+
+fn microdata_item(element: ElementRef) -> Value {
+    let mut map = Map::new();
+    if let Some(item_type) = element.value().attr("itemtype") {
+        let types: Vec<Value> = item_type
+            .split_whitespace()
+            .map(|v| Value::String(v.rsplit('/').next().unwrap_or(v).to_string()))
+            .collect();
+        map.insert("@type".into(), Value::Array(types));
+    }
+    microdata_props(element, &mut map);
+    Value::Object(map)
+}
+
+fn microdata_props(element: ElementRef, map: &mut Map<String, Value>) {
+    for child in element.children().filter_map(ElementRef::wrap) {
+        let is_scope = child.value().attr("itemscope").is_some();
+        if let Some(names) = child.value().attr("itemprop") {
+            let value = if is_scope {
+                microdata_item(child)
+            } else {
+                Value::String(microdata_value(child))
+            };
+            for name in names.split_whitespace() {
+                match map.remove(name) {
+                    None => map.insert(name.into(), value.clone()),
+                    Some(Value::Array(mut list)) => {
+                        list.push(value.clone());
+                        map.insert(name.into(), Value::Array(list))
+                    }
+                    Some(prev) => map.insert(name.into(), Value::Array(vec![prev, value.clone()])),
+                };
+            }
+        }
+        // 入れ子のitemscopeは別のアイテムのプロパティになる
+        if !is_scope {
+            microdata_props(child, map);
+        }
+    }
+}
+
+fn microdata_value(element: ElementRef) -> String {
+    let e = element.value();
+    let attr = match e.name() {
+        "meta" => e.attr("content"),
+        "img" | "audio" | "video" | "source" | "iframe" | "embed" => e.attr("src"),
+        "a" | "link" | "area" => e.attr("href"),
+        "object" => e.attr("data"),
+        "data" | "meter" => e.attr("value"),
+        "time" => e.attr("datetime"),
+        _ => e.attr("content"),
+    };
+    attr.map(|v| v.to_string()).unwrap_or_else(|| {
+        element
+            .text()
+            .collect::<Vec<_>>()
+            .concat()
+            .trim()
+            .to_string()
+    })
+}
+
+fn has_type(node: &Value, name: &str) -> bool {
+    as_list(node.get("@type"))
+        .iter()
+        .filter_map(|v| v.as_str())
+        .any(|v| v.rsplit('/').next() == Some(name))
+}
+
+fn as_list(v: Option<&Value>) -> Vec<&Value> {
+    match v {
+        Some(Value::Array(list)) => list.iter().collect(),
+        Some(v) => vec![v],
+        None => vec![],
+    }
+}
+
+fn first(v: Option<&Value>) -> Option<&Value> {
+    as_list(v).into_iter().next()
+}
+
+// 数値で書かれていることもあるので文字列に揃える。1980.0のような整数値は小数点を付けない
+fn as_string(v: &Value) -> Option<String> {
+    match v {
+        Value::String(v) => Some(v.trim().to_string()).filter(|v| !v.is_empty()),
+        Value::Number(v) => Some(match v.as_f64() {
+            Some(f) if f.fract() == 0.0 && f.abs() < 1e15 => format!("{}", f as i64),
+            _ => v.to_string(),
+        }),
+        _ => None,
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{Entity, Format};
+use sale::errors::Kind::{BadRequest, Internal};
+use sale::AppResult;
+use serde_json::{Map, Value};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::Path;
+
+pub type Record = Map<String, Value>;
+
+#[derive(Debug, Clone, Copy)]
+pub enum ColumnType {
+    S,
+    N,
+    Json,
+}
+
+// CSVはフラットな表現のため、エンティティごとに列と型を定義する
+fn columns(entity: Entity) -> &'static [(&'static str, ColumnType)] {
+    match entity {
+        Entity::Product => &[
+            ("pk", ColumnType::S),
+            ("sk", ColumnType::S),
+            ("source", ColumnType::S),
+            ("shopId", ColumnType::S),
+            ("itemId", ColumnType::S),
+            ("status", ColumnType::S),
+            ("detailUrl", ColumnType::S),
+            ("affiliateUrl", ColumnType::S),
+            ("title", ColumnType::S),
+            ("imageUrls", ColumnType::Json),
+            ("retailPrice", ColumnType::S),
+            ("actualPrice", ColumnType::S),
+            ("retailOff", ColumnType::S),
+            ("breadcrumb", ColumnType::Json),
+            ("points", ColumnType::S),
+            ("lowestPrice", ColumnType::N),
+            ("dealScore", ColumnType::N),
+            ("brand", ColumnType::S),
+            ("currency", ColumnType::S),
+            ("rating", ColumnType::N),
+            ("reviewCount", ColumnType::N),
+            ("failureCount", ColumnType::N),
+            ("expiresAt", ColumnType::N),
+            ("charset", ColumnType::S),
+            ("fingerprint", ColumnType::S),
+            ("etag", ColumnType::S),
+            ("lastModified", ColumnType::S),
+            ("lastCheckedAt", ColumnType::N),
+            ("nextCheckAt", ColumnType::N),
+            ("unchangedCount", ColumnType::N),
+            ("mirroredImages", ColumnType::Json),
+            ("groupId", ColumnType::S),
+            ("genreId", ColumnType::S),
+            ("categoryIds", ColumnType::Json),
+            ("createdAt", ColumnType::N),
+            ("updatedAt", ColumnType::N),
+        ],
+        Entity::Shop => &[
+            ("pk", ColumnType::S),
+            ("sk", ColumnType::S),
+            ("source", ColumnType::S),
+            ("code", ColumnType::S),
+            ("name", ColumnType::S),
+            ("url", ColumnType::S),
+            ("createdAt", ColumnType::N),
+            ("updatedAt", ColumnType::N),
+        ],
+        Entity::Category => &[
+            ("pk", ColumnType::S),
+            ("sk", ColumnType::S),
+            ("name", ColumnType::S),
+            ("parentId", ColumnType::S),
+            ("path", ColumnType::Json),
+            ("genreId", ColumnType::S),
+            ("productCount", ColumnType::N),
+            ("createdAt", ColumnType::N),
+            ("updatedAt", ColumnType::N),
+        ],
+        Entity::ProductGroup => &[
+            ("pk", ColumnType::S),
+            ("sk", ColumnType::S),
+            ("productIds", ColumnType::Json),
+            ("createdAt", ColumnType::N),
+            ("updatedAt", ColumnType::N),
+        ],
+        Entity::ProductStat => &[
+            ("pk", ColumnType::S),
+            ("sk", ColumnType::S),
+            ("views", ColumnType::N),
+            ("clicks", ColumnType::N),
+            ("favorites", ColumnType::N),
+            ("score", ColumnType::N),
+        ],
+        Entity::CrawlFailure => &[
+            ("pk", ColumnType::S),
+            ("sk", ColumnType::S),
+            ("target", ColumnType::S),
+            ("payload", ColumnType::S),
+            ("productId", ColumnType::S),
+            ("error", ColumnType::S),
+            ("attempts", ColumnType::N),
+            ("status", ColumnType::S),
+            ("createdAt", ColumnType::N),
+            ("updatedAt", ColumnType::N),
+            ("replayedAt", ColumnType::N),
+            ("lastDeliveryId", ColumnType::S),
+        ],
+        Entity::Migration => &[
+            ("pk", ColumnType::S),
+            ("sk", ColumnType::S),
+            ("name", ColumnType::S),
+            ("version", ColumnType::N),
+            ("dryRun", ColumnType::Json),
+            ("status", ColumnType::S),
+            ("cursor", ColumnType::S),
+            ("chunks", ColumnType::N),
+            ("processed", ColumnType::N),
+            ("touched", ColumnType::N),
+            ("error", ColumnType::S),
+            ("startedAt", ColumnType::N),
+            ("updatedAt", ColumnType::N),
+            ("completedAt", ColumnType::N),
+            ("revision", ColumnType::N),
+        ],
+        Entity::ApiKey => &[
+            ("pk", ColumnType::S),
+            ("sk", ColumnType::S),
+            ("name", ColumnType::S),
+            ("secretHash", ColumnType::S),
+            ("scopes", ColumnType::Json),
+            ("status", ColumnType::S),
+            ("createdAt", ColumnType::N),
+            ("updatedAt", ColumnType::N),
+            ("lastUsedAt", ColumnType::N),
+            ("revokedAt", ColumnType::N),
+        ],
+        Entity::AuditEvent => &[
+            ("pk", ColumnType::S),
+            ("sk", ColumnType::S),
+            ("actor", ColumnType::S),
+            ("apiKeyId", ColumnType::S),
+            ("operationName", ColumnType::S),
+            ("fields", ColumnType::Json),
+            ("arguments", ColumnType::S),
+            ("result", ColumnType::S),
+            ("errors", ColumnType::Json),
+            ("durationMs", ColumnType::N),
+            ("createdAt", ColumnType::N),
+        ],
+        Entity::ProductEvent => &[
+            ("pk", ColumnType::S),
+            ("sk", ColumnType::S),
+            ("event", ColumnType::Json),
+            ("createdAt", ColumnType::N),
+        ],
+    }
+}
+
+pub enum RecordWriter {
+    Ndjson(BufWriter<File>),
+    Csv(Box<csv::Writer<File>>, Entity),
+}
+impl RecordWriter {
+    pub fn create(path: &Path, format: Format, entity: Entity) -> AppResult<Self> {
+        let file = File::create(path).map_err(Internal.from_srcf())?;
+        Ok(match format {
+            Format::Ndjson => Self::Ndjson(BufWriter::new(file)),
+            Format::Csv => {
+                let mut w = csv::Writer::from_writer(file);
+                w.write_record(columns(entity).iter().map(|(name, _)| *name))
+                    .map_err(Internal.from_srcf())?;
+                Self::Csv(Box::new(w), entity)
+            }
+        })
+    }
+
+    pub fn write(&mut self, record: Record) -> AppResult<()> {
+        match self {
+            Self::Ndjson(w) => {
+                serde_json::to_writer(&mut *w, &record).map_err(Internal.from_srcf())?;
+                w.write_all(b"\n").map_err(Internal.from_srcf())?;
+            }
+            Self::Csv(w, entity) => {
+                let row = columns(*entity)
+                    .iter()
+                    .map(|(name, _)| match record.get(*name) {
+                        None | Some(Value::Null) => String::new(),
+                        Some(Value::String(v)) => v.clone(),
+                        Some(Value::Number(v)) => v.to_string(),
+                        Some(v) => v.to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                w.write_record(row).map_err(Internal.from_srcf())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> AppResult<()> {
+        match self {
+            Self::Ndjson(mut w) => w.flush().map_err(Internal.from_srcf()),
+            Self::Csv(mut w, _) => w.flush().map_err(Internal.from_srcf()),
+        }
+    }
+}
+
+pub enum RecordReader {
+    Ndjson(Lines<BufReader<File>>),
+    Csv(csv::StringRecordsIntoIter<File>, Vec<(String, ColumnType)>),
+}
+impl RecordReader {
+    pub fn open(path: &Path, format: Format, entity: Entity) -> AppResult<Self> {
+        let file = File::open(path).map_err(Internal.from_srcf())?;
+        Ok(match format {
+            Format::Ndjson => Self::Ndjson(BufReader::new(file).lines()),
+            Format::Csv => {
+                let mut r = csv::Reader::from_reader(file);
+                let header = r
+                    .headers()
+                    .map_err(BadRequest.from_srcf())?
+                    .iter()
+                    .map(|name| {
+                        columns(entity)
+                            .iter()
+                            .find(|(v, _)| *v == name)
+                            .map(|(_, t)| (name.to_string(), *t))
+                            .ok_or_else(|| BadRequest.with(format!("unknown column: {}", name)))
+                    })
+                    .collect::<AppResult<Vec<_>>>()?;
+                Self::Csv(r.into_records(), header)
+            }
+        })
+    }
+}
+impl Iterator for RecordReader {
+    type Item = AppResult<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Ndjson(lines) => loop {
+                let line = match lines.next()? {
+                    Ok(v) => v,
+                    Err(err) => return Some(Err(Internal.from_src(err))),
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                return Some(serde_json::from_str(&line).map_err(BadRequest.from_srcf()));
+            },
+            Self::Csv(records, header) => {
+                let row = match records.next()? {
+                    Ok(v) => v,
+                    Err(err) => return Some(Err(BadRequest.from_src(err))),
+                };
+                Some(
+                    header
+                        .iter()
+                        .zip(row.iter())
+                        .filter(|(_, cell)| !cell.is_empty())
+                        .map(|((name, t), cell)| {
+                            let v = match t {
+                                ColumnType::S => Value::String(cell.to_string()),
+                                ColumnType::N | ColumnType::Json => serde_json::from_str(cell)
+                                    .map_err(|e| {
+                                        BadRequest.with(format!("column {}: {}", name, e))
+                                    })?,
+                            };
+                            Ok((name.clone(), v))
+                        })
+                        .collect(),
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::codec::RecordWriter;
+use crate::{temp_path, Entity, ExportRequest, Location};
+use sale::di;
+use sale::errors::Kind::Internal;
+use sale::infra::aws::ddb::types::item_to_json;
+use sale::infra::aws::ddb::{TableRepository, Transferable};
+use sale::AppResult;
+use std::path::PathBuf;
+
+const DEFAULT_SEGMENTS: i32 = 8;
+
+pub async fn export(req: ExportRequest) -> AppResult<()> {
+    let (path, upload_key) = match req.dest {
+        Location::File { path } => (PathBuf::from(path), None),
+        Location::S3 { key } => (temp_path(), Some(key)),
+    };
+
+    let mut writer = RecordWriter::create(&path, req.format, req.entity)?;
+    let segments = req.segments.unwrap_or(DEFAULT_SEGMENTS);
+    let w = &mut writer;
+    let count = match req.entity {
+        Entity::Product => export_table(di::DB_PRODUCT_REPOSITORY.get().await, segments, w).await,
+        Entity::Shop => export_table(di::DB_SHOP_REPOSITORY.get().await, segments, w).await,
+        Entity::Category => export_table(di::DB_CATEGORY_REPOSITORY.get().await, segments, w).await,
+        Entity::ProductGroup => {
+            export_table(di::DB_PRODUCT_GROUP_REPOSITORY.get().await, segments, w).await
+        }
+        Entity::ProductStat => {
+            export_table(di::DB_PRODUCT_STAT_REPOSITORY.get().await, segments, w).await
+        }
+        Entity::CrawlFailure => {
+            export_table(di::DB_CRAWL_FAILURE_REPOSITORY.get().await, segments, w).await
+        }
+        Entity::Migration => {
+            export_table(di::DB_MIGRATION_REPOSITORY.get().await, segments, w).await
+        }
+        Entity::ApiKey => export_table(di::DB_API_KEY_REPOSITORY.get().await, segments, w).await,
+        Entity::AuditEvent => {
+            export_table(di::DB_AUDIT_EVENT_REPOSITORY.get().await, segments, w).await
+        }
+        Entity::ProductEvent => {
+            export_table(di::DB_PRODUCT_EVENT_REPOSITORY.get().await, segments, w).await
+        }
+    }?;
+    writer.finish()?;
+
+    if let Some(key) = upload_key {
+        let s3 = di::S3_ADAPTER.get().await.clone();
+        s3.upload_file(&key, &path).await?;
+        std::fs::remove_file(&path).map_err(Internal.from_srcf())?;
+        println!(
+            "{}を{}件エクスポートしました: s3://{}",
+            req.entity, count, key
+        );
+    } else {
+        println!(
+            "{}を{}件エクスポートしました: {}",
+            req.entity,
+            count,
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+async fn export_table<E: Transferable>(
+    repo: &TableRepository<E>,
+    segments: i32,
+    writer: &mut RecordWriter,
+) -> AppResult<usize> {
+    let mut count: usize = 0;
+    let mut rx = repo.scan_all(segments);
+    while let Some(page) = rx.recv().await {
+        for entity in page? {
+            let item = entity.into_item().map_err(Internal.withf())?;
+            writer.write(item_to_json(item).map_err(Internal.withf())?)?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
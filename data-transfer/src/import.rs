@@ -0,0 +1,62 @@
+use crate::codec::RecordReader;
+use crate::{temp_path, Entity, ImportRequest, Location};
+use sale::di;
+use sale::errors::Kind::{BadRequest, Internal};
+use sale::infra::aws::ddb::types::item_from_json;
+use sale::infra::aws::ddb::{TableRepository, Transferable};
+use sale::AppResult;
+use std::path::PathBuf;
+
+const REPORT_INTERVAL: usize = 1000;
+
+pub async fn import(req: ImportRequest) -> AppResult<()> {
+    let (path, downloaded) = match req.src {
+        Location::File { path } => (PathBuf::from(path), false),
+        Location::S3 { key } => {
+            let path = temp_path();
+            let s3 = di::S3_ADAPTER.get().await.clone();
+            s3.download_file(&key, &path).await?;
+            (path, true)
+        }
+    };
+
+    let r = RecordReader::open(&path, req.format, req.entity)?;
+    let count = match req.entity {
+        Entity::Product => import_table(di::DB_PRODUCT_REPOSITORY.get().await, r).await,
+        Entity::Shop => import_table(di::DB_SHOP_REPOSITORY.get().await, r).await,
+        Entity::Category => import_table(di::DB_CATEGORY_REPOSITORY.get().await, r).await,
+        Entity::ProductGroup => import_table(di::DB_PRODUCT_GROUP_REPOSITORY.get().await, r).await,
+        Entity::ProductStat => import_table(di::DB_PRODUCT_STAT_REPOSITORY.get().await, r).await,
+        Entity::CrawlFailure => import_table(di::DB_CRAWL_FAILURE_REPOSITORY.get().await, r).await,
+        Entity::Migration => import_table(di::DB_MIGRATION_REPOSITORY.get().await, r).await,
+        Entity::ApiKey => import_table(di::DB_API_KEY_REPOSITORY.get().await, r).await,
+        Entity::AuditEvent => import_table(di::DB_AUDIT_EVENT_REPOSITORY.get().await, r).await,
+        Entity::ProductEvent => import_table(di::DB_PRODUCT_EVENT_REPOSITORY.get().await, r).await,
+    }?;
+
+    if downloaded {
+        std::fs::remove_file(&path).map_err(Internal.from_srcf())?;
+    }
+    println!("{}を{}件インポートしました", req.entity, count);
+
+    Ok(())
+}
+
+async fn import_table<E: Transferable>(
+    repo: &TableRepository<E>,
+    reader: RecordReader,
+) -> AppResult<usize> {
+    let mut count: usize = 0;
+    let mut reported: usize = 0;
+    for (line, record) in reader.enumerate() {
+        let entity = E::from_item(item_from_json(record?))
+            .map_err(|v| BadRequest.with(format!("record {}: {}", line + 1, v)))?;
+        repo.restore(entity).await?;
+        count += 1;
+        if count - reported >= REPORT_INTERVAL {
+            reported = count;
+            println!("{}件インポートしました", count);
+        }
+    }
+    Ok(count)
+}
@@ -0,0 +1,112 @@
+use anyhow::anyhow;
+use sale::domain::generate_id_str;
+use sale::{di, AppResult};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::path::PathBuf;
+
+mod codec;
+mod export;
+mod import;
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    di::SSM_ADAPTER
+        .get()
+        .await
+        .load_dotenv()
+        .await
+        .expect("failed to load ssm parameter store");
+
+    let args: Vec<String> = env::args().collect();
+    let json = args.get(1).expect("json params is required");
+    let body: Request = serde_json::from_str(json).unwrap();
+    if let Err(err) = handler(body).await {
+        eprintln!("error: {:?}", err);
+        return Err(anyhow!(err));
+    }
+
+    Ok(())
+}
+
+async fn handler(req: Request) -> AppResult<()> {
+    match req.command {
+        Command::Export(body) => export::export(body).await,
+        Command::Import(body) => import::import(body).await,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Request {
+    pub command: Command,
+}
+#[derive(Serialize, Deserialize, Debug)]
+pub enum Command {
+    Export(ExportRequest),
+    Import(ImportRequest),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExportRequest {
+    pub entity: Entity,
+    pub format: Format,
+    pub dest: Location,
+    pub segments: Option<i32>,
+}
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ImportRequest {
+    pub entity: Entity,
+    pub format: Format,
+    pub src: Location,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    strum_macros::EnumString,
+    strum_macros::Display,
+    Serialize,
+    Deserialize,
+)]
+pub enum Entity {
+    Product,
+    Shop,
+    Category,
+    ProductGroup,
+    ProductStat,
+    CrawlFailure,
+    Migration,
+    ApiKey,
+    AuditEvent,
+    ProductEvent,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    strum_macros::EnumString,
+    strum_macros::Display,
+    Serialize,
+    Deserialize,
+)]
+pub enum Format {
+    Ndjson,
+    Csv,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum Location {
+    File { path: String },
+    S3 { key: String },
+}
+
+// S3との受け渡しはローカルの一時ファイルを経由する
+fn temp_path() -> PathBuf {
+    env::temp_dir().join(format!("data-transfer-{}", generate_id_str()))
+}
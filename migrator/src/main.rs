@@ -0,0 +1,170 @@
+use anyhow::anyhow;
+use lambda_runtime::{service_fn, Error, LambdaEvent};
+use sale::domain::migration::{Migration, Status};
+use sale::errors::Kind::{Conflict, Internal};
+use sale::infra::aws::lambda::types::migrator::{Request, RequestBody, RunMigrationRequest};
+use sale::infra::aws::lambda::types::sns::EventData;
+use sale::migration::{RunOptions, Start, DEFAULT_CHUNK_SIZE};
+use sale::{di, migration, AppResult};
+use serde_json::Value;
+use std::env;
+use std::time::Duration;
+
+// Lambdaのタイムアウト(900秒)より前にチャンクの区切りで止めて、続きは再発行したメッセージで処理する
+const TIME_BUDGET: Duration = Duration::from_secs(600);
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    di::SSM_ADAPTER
+        .get()
+        .await
+        .load_dotenv()
+        .await
+        .expect("failed to load ssm parameter store");
+
+    let envs = di::ENVIRONMENTS.clone();
+
+    if envs.with_lambda {
+        let handler = service_fn(bridge);
+        lambda_runtime::run(handler).await?;
+    } else {
+        let args: Vec<String> = env::args().collect();
+        let json = args.get(1).expect("json params is required");
+        let body: Request = serde_json::from_str(json).unwrap();
+        if let Err(err) = handler(body).await {
+            eprintln!("error: {:?}", err);
+            return Err(anyhow!(err).into());
+        }
+    }
+
+    Ok(())
+}
+
+async fn bridge(event: LambdaEvent<Value>) -> Result<(), Error> {
+    // SNS経由
+    let data: Result<EventData, String> =
+        serde_json::from_value(event.payload.clone()).map_err(|v| v.to_string());
+    if let Ok(data) = data {
+        let request: Request =
+            serde_json::from_str(&data.records.first().unwrap().sns.message).unwrap();
+        let result = handler(request).await;
+        return match result {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                eprintln!("error: {:?}", err);
+                Err(anyhow!(err).into())
+            }
+        };
+    }
+
+    // 直接Invoke経由
+    let data: Result<Request, String> =
+        serde_json::from_value(event.payload.clone()).map_err(|v| v.to_string());
+    if let Ok(data) = data {
+        let result = handler(data).await;
+        return match result {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                eprintln!("error: {:?}", err);
+                Err(anyhow!(err).into())
+            }
+        };
+    }
+
+    Err(anyhow!("不正なペイロードです").into())
+}
+
+async fn handler(req: Request) -> AppResult<()> {
+    let sns = di::SNS_ADAPTER.get().await.clone();
+    let migration_repo = di::DB_MIGRATION_REPOSITORY.get().await.clone();
+    let with_lambda = di::ENVIRONMENTS.with_lambda;
+
+    match req.body {
+        RequestBody::RunMigration(body) => {
+            let def = migration::find_definition(&body.name)?;
+            let migration = match body.revision {
+                // API や前の実行が実行権を得た記録を引き継ぐ。古いメッセージや重複した配信は無視する
+                Some(revision) => {
+                    let id = Migration::id_of(def.name(), def.version(), body.dry_run);
+                    let current = migration_repo.get(&id).await?;
+                    if current.revision != revision
+                        || !matches!(current.status, Status::Running | Status::Paused)
+                    {
+                        println!(
+                            "{}は既に他の実行が引き継いでいます: status={}",
+                            current.id.as_str(),
+                            current.status
+                        );
+                        return Ok(());
+                    }
+                    current
+                }
+                None => {
+                    match migration::start(def.as_ref(), &migration_repo, body.dry_run).await? {
+                        Start::Started(v) => v,
+                        Start::Existing(v) => {
+                            println!(
+                                "{}は実行中か完了済みです: status={}",
+                                v.id.as_str(),
+                                v.status
+                            );
+                            return Ok(());
+                        }
+                    }
+                }
+            };
+            let result = match migration::run(
+                def.as_ref(),
+                &migration_repo,
+                migration,
+                RunOptions {
+                    dry_run: body.dry_run,
+                    chunk_size: body.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE),
+                    time_budget: if with_lambda { Some(TIME_BUDGET) } else { None },
+                },
+            )
+            .await
+            {
+                Ok(v) => v,
+                Err(err) if err.kind == Conflict => {
+                    println!("{}", err);
+                    return Ok(());
+                }
+                Err(err) => return Err(err),
+            };
+
+            match result.status {
+                Status::Running | Status::Paused => {
+                    let sns_arn = di::ENVIRONMENTS
+                        .migration_sns_arn
+                        .clone()
+                        .ok_or_else(|| Internal.with("MIGRATION_SNS_ARN is not set"))?;
+                    sns.publish(
+                        Request {
+                            body: RequestBody::RunMigration(RunMigrationRequest {
+                                name: body.name,
+                                dry_run: body.dry_run,
+                                chunk_size: body.chunk_size,
+                                revision: Some(result.revision),
+                            }),
+                        },
+                        sns_arn,
+                    )
+                    .await?;
+                }
+                Status::Completed => {
+                    println!("{}が完了しました", result.id.as_str());
+                }
+                Status::Failed => {
+                    return Err(Internal.with(format!(
+                        "{}が失敗しました: {}",
+                        result.id.as_str(),
+                        result.error.unwrap_or_default()
+                    )));
+                }
+            }
+
+            Ok(())
+        }
+    }
+}
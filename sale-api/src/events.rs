@@ -0,0 +1,82 @@
+use crate::rate_limit;
+use actix_web::{web, HttpRequest, HttpResponse};
+use sale::domain::product_stat::EventKind;
+use sale::domain::time;
+use sale::errors::Kind::NotFound;
+use sale::{di, domain};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+const DAY_SECS: f64 = 24.0 * 60.0 * 60.0;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventRequest {
+    pub product_id: String,
+    // View, Click, Favorite
+    pub kind: String,
+}
+
+// 商品の閲覧・クリック・お気に入りを日別の件数に加算する
+pub async fn handle(http_req: HttpRequest, body: web::Json<EventRequest>) -> HttpResponse {
+    let body = body.into_inner();
+    let Ok(kind) = EventKind::from_str(&body.kind) else {
+        return HttpResponse::BadRequest().finish();
+    };
+    let id = domain::product::Id::new(body.product_id);
+
+    // 存在しない商品の件数でランキングの候補が埋まらないようにする
+    let product_repo = di::DB_PRODUCT_REPOSITORY.get().await;
+    match product_repo.get(&id).await {
+        Ok(_) => {}
+        Err(err) if err.kind == NotFound => return HttpResponse::NotFound().finish(),
+        Err(err) => {
+            eprintln!("商品の取得に失敗しました: {}", err);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
+    record(&http_req, &id, kind).await;
+    HttpResponse::Accepted().finish()
+}
+
+// 同じクライアントからの同じ商品・種類のイベントは1日1回だけ数える
+// 記録に失敗しても呼び出し元の処理は続ける
+pub async fn record(http_req: &HttpRequest, id: &domain::product::Id, kind: EventKind) {
+    let now = time::now();
+    if !is_first_of_day(http_req, id, kind, now).await {
+        return;
+    }
+    let stat_repo = di::DB_PRODUCT_STAT_REPOSITORY.get().await;
+    if let Err(err) = stat_repo.record(id, kind, now).await {
+        eprintln!(
+            "イベントの記録に失敗しました: product_id={} kind={} {}",
+            id.as_str(),
+            kind,
+            err
+        );
+    }
+}
+
+// 日付を含むキーで容量1のバケットを使い、その日の2回目以降を弾く
+// IPはそのまま保存しないようハッシュにする
+async fn is_first_of_day(
+    http_req: &HttpRequest,
+    id: &domain::product::Id,
+    kind: EventKind,
+    now: time::LocalDateTime,
+) -> bool {
+    let limiter = di::API_RATE_LIMITER.get().await;
+    let client = Sha256::digest(rate_limit::client_ip(http_req).as_bytes());
+    let key = format!(
+        "api:event:{}:{}:{}:{:x}",
+        kind,
+        id.as_str(),
+        now.date_naive(),
+        client
+    );
+    rate_limit::try_acquire(limiter, &key, 1.0 / DAY_SECS, 1.0)
+        .await
+        .is_none()
+}
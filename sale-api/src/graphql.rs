@@ -1,6 +1,8 @@
 mod connection;
 pub mod data_loader;
 pub mod errors;
+mod limit;
 pub mod master;
+mod persisted_query;
 pub mod service;
 pub mod shared;
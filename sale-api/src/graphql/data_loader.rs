@@ -1,4 +1,4 @@
-use async_graphql::dataloader::Loader;
+use async_graphql::dataloader::{DataLoader, Loader};
 use sale::domain;
 use sale::errors::AppError;
 use sale::infra::aws::ddb::prelude::*;
@@ -7,8 +7,8 @@ use std::hash::Hash;
 use std::sync::Arc;
 
 #[derive(Clone)]
-pub struct DataLoader<E, K>(pub Arc<dyn BatchGet<E, K> + Send + Sync>);
-impl<E, K> Loader<K> for DataLoader<E, K>
+pub struct BatchLoader<E, K>(pub Arc<dyn BatchGet<E, K> + Send + Sync>);
+impl<E, K> Loader<K> for BatchLoader<E, K>
 where
     E: Send + Sync + Clone + 'static,
     K: Hash + Eq + Send + Sync + Clone + 'static,
@@ -21,4 +21,27 @@ where
     }
 }
 
-pub type ProductLoader = DataLoader<domain::product::Product, domain::product::Id>;
+// 同じタイミングで要求されたキーをまとめて取得するため、DataLoader を通して読み込む
+pub fn loader_of<E, K>(
+    batch: impl BatchGet<E, K> + Send + Sync + 'static,
+) -> Arc<DataLoader<BatchLoader<E, K>>>
+where
+    E: Send + Sync + Clone + 'static,
+    K: Hash + Eq + Send + Sync + Clone + 'static,
+{
+    Arc::new(DataLoader::new(BatchLoader(Arc::new(batch)), tokio::spawn))
+}
+
+pub type ProductLoader =
+    Arc<DataLoader<BatchLoader<domain::product::Product, domain::product::Id>>>;
+pub type ProductGroupLoader =
+    Arc<DataLoader<BatchLoader<domain::product_group::ProductGroup, domain::product_group::Id>>>;
+pub type ShopLoader = Arc<DataLoader<BatchLoader<domain::shop::Shop, domain::shop::Id>>>;
+pub type PopularityLoader = Arc<
+    DataLoader<
+        BatchLoader<
+            domain::product_stat::Popularity,
+            (domain::product::Id, domain::product_stat::Window),
+        >,
+    >,
+>;
@@ -10,9 +10,9 @@ pub enum Error {
     App(AppError),
     Field(FieldError),
 }
-impl Into<FieldError> for Error {
-    fn into(self) -> FieldError {
-        let err = match self {
+impl From<Error> for FieldError {
+    fn from(v: Error) -> Self {
+        let err = match v {
             Error::App(v) => v,
             Error::Field(v) => Internal.from_src(FieldStdErr(v)),
         };
@@ -34,6 +34,11 @@ impl Into<FieldError> for Error {
                 .msg
                 .clone()
                 .unwrap_or_else(|| "指定されたリソースが見つかりません".into()),
+            Conflict => err
+                .msg
+                .clone()
+                .unwrap_or_else(|| "リソースが競合しています".into()),
+            Unavailable => "混み合っています。しばらくしてから再度お試しください".into(), // 内部の詳細は伏せる
             Internal => "内部エラーが発生しました".into(), // 内部エラーはユーザーには詳細を伏せる
         })
         .extend_with(|_, ext| {
@@ -44,6 +49,8 @@ impl Into<FieldError> for Error {
                     Unauthorized => "UNAUTHORIZED",
                     Forbidden => "FORBIDDEN",
                     NotFound => "NOT_FOUND",
+                    Conflict => "CONFLICT",
+                    Unavailable => "UNAVAILABLE",
                     Internal => "INTERNAL",
                 }
                 .to_string(),
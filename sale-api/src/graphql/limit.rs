@@ -0,0 +1,31 @@
+use async_graphql::{ObjectType, SchemaBuilder, SubscriptionType};
+use sale::env::Environments;
+
+// 一覧の件数を省略した場合の件数と、指定できる件数の上限
+pub const DEFAULT_LIMIT: i32 = 20;
+pub const MAX_LIMIT: i32 = 100;
+
+// 指定された件数を上限までに収める
+pub fn limit_of(limit: Option<i32>) -> i32 {
+    limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+}
+
+// 一覧のフィールドは返す件数の分だけ子のフィールドを解決するものとして数える
+pub fn complexity_of(limit: Option<i32>, child_complexity: usize) -> usize {
+    limit_of(limit) as usize * child_complexity
+}
+
+// 入れ子の深いクエリや大量の解決が必要なクエリを実行前に拒否する
+pub fn apply<Q, M, S>(
+    builder: SchemaBuilder<Q, M, S>,
+    envs: &Environments,
+) -> SchemaBuilder<Q, M, S>
+where
+    Q: ObjectType + 'static,
+    M: ObjectType + 'static,
+    S: SubscriptionType + 'static,
+{
+    builder
+        .limit_depth(envs.graphql_max_depth)
+        .limit_complexity(envs.graphql_max_complexity)
+}
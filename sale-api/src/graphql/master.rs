@@ -1,30 +1,88 @@
+use crate::graphql::connection::connection_from;
 use crate::graphql::errors;
+use crate::graphql::limit;
+use crate::graphql::master::types::api_key::{ApiKey, ApiKeyScope, IssuedApiKey};
+use crate::graphql::master::types::audit_event::AuditEvent;
+use crate::graphql::master::types::crawl_failure::{CrawlFailure, CrawlFailureStatus};
+use crate::graphql::master::types::migration::{Migration, MigrationDefinition};
+use crate::graphql::master::types::product::Product;
+use crate::rate_limit;
 use actix_web::http::header::{HeaderMap, HeaderValue};
 use actix_web::HttpRequest;
-use async_graphql::{Context, EmptySubscription, Object};
+use async_graphql::connection::Connection;
+use async_graphql::{Context, EmptySubscription, Guard, Object, ID};
 use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse};
 use async_trait::async_trait;
-use sale::errors::Kind::{BadRequest, Unauthorized};
-use sale::infra::aws::ddb;
-use sale::{di, AppResult};
+use sale::domain::api_key::{constant_time_eq, Scope};
+use sale::domain::time;
+use sale::env::Environments;
+use sale::errors::Kind::{BadRequest, Conflict, Forbidden, Unauthorized};
+use sale::errors::NotFoundToNone;
+use sale::infra::aws::ddb::cursor::Cursor;
+use sale::infra::aws::lambda::types::migrator;
+use sale::infra::aws::{ddb, sns};
+use sale::migration::{RunOptions, Start};
+use sale::{crawl, di, domain, migration, AppResult};
+use std::collections::HashMap;
+use std::time::Duration;
+
+mod audit;
+mod types;
 
 #[derive(Debug, Clone)]
-pub struct Authorized {}
+pub struct Authorized {
+    // マスタートークンの場合はNone
+    pub api_key_id: Option<domain::api_key::Id>,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+impl Authorized {
+    pub fn actor(&self) -> String {
+        match &self.api_key_id {
+            Some(id) => format!("{}({})", self.name, id.as_str()),
+            None => self.name.clone(),
+        }
+    }
+
+    // 自分の持たない権限は付与できない。マスタートークンはすべての権限を付与できる
+    fn grantable(&self, scopes: &[Scope]) -> AppResult<()> {
+        if self.api_key_id.is_none() {
+            return Ok(());
+        }
+        match scopes.iter().find(|v| !self.scopes.contains(v)) {
+            Some(scope) => Err(Forbidden.with(format!("権限がありません: {}", scope))),
+            None => Ok(()),
+        }
+    }
+}
+
+// 必要な権限を持つAPIキーにだけ解決を許す
+struct ScopeGuard(Scope);
+impl Guard for ScopeGuard {
+    async fn check(&self, ctx: &Context<'_>) -> async_graphql::Result<()> {
+        let authorized = ctx.verified()?;
+        if !authorized.scopes.contains(&self.0) {
+            return Err(errors::Error::from(
+                Forbidden.with(format!("権限がありません: {}", self.0)),
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
 
 #[async_trait]
 trait AppContext {
     fn verified(&self) -> Result<Authorized, errors::Error>;
 }
 #[async_trait]
-impl<'a> AppContext for Context<'_> {
+impl AppContext for Context<'_> {
     fn verified(&self) -> Result<Authorized, errors::Error> {
         match self.data::<AppResult<Authorized>>()? {
             Ok(v) => Ok(v.clone()),
-            Err(err) => Err(match err.kind {
-                _ => Unauthorized
-                    .with(format!("authorization error: {}", err))
-                    .into(),
-            }),
+            Err(err) => Err(Unauthorized
+                .with(format!("authorization error: {}", err))
+                .into()),
         }
     }
 }
@@ -35,72 +93,499 @@ pub type Schema = async_graphql::Schema<Query, Mutation, EmptySubscription>;
 pub struct HttpHandler {
     pub schema: Schema,
     pub master_api_token: String,
+    pub api_key_repo: ddb::types::api_key::Repository,
 }
 
 impl HttpHandler {
     pub async fn new() -> Self {
         let envs = di::ENVIRONMENTS.clone();
 
-        let schema = Schema::build(Query::default(), Mutation::default(), EmptySubscription)
+        let builder = limit::apply(Schema::build(Query, Mutation, EmptySubscription), &envs);
+        let schema = builder
             .data(di::DB_PRODUCT_REPOSITORY.get().await.clone())
+            .data(di::DB_MIGRATION_REPOSITORY.get().await.clone())
+            .data(di::DB_CRAWL_FAILURE_REPOSITORY.get().await.clone())
+            .data(di::DB_API_KEY_REPOSITORY.get().await.clone())
+            .data(di::DB_AUDIT_EVENT_REPOSITORY.get().await.clone())
+            .extension(audit::Audit)
+            .data(di::SNS_ADAPTER.get().await.clone())
+            .data(envs.clone())
             .finish();
 
         HttpHandler {
             schema,
             master_api_token: envs.master_api_token,
+            api_key_repo: di::DB_API_KEY_REPOSITORY.get().await.clone(),
         }
     }
 
     pub async fn handle(&self, http_req: HttpRequest, gql_req: GraphQLRequest) -> GraphQLResponse {
         let mut gql_req = gql_req.into_inner();
-        let headers: HeaderMap = HeaderMap::from_iter(http_req.headers().clone().into_iter());
+        let headers: HeaderMap = HeaderMap::from_iter(http_req.headers().clone());
 
         gql_req = gql_req.data(match headers.get("authorization") {
             None => Err(Unauthorized.into()),
-            Some(hv) => verify_token(hv, &self.master_api_token).await,
+            Some(hv) => {
+                let res = verify_token(hv, &self.master_api_token, &self.api_key_repo).await;
+                // 保存先の障害で管理者を締め出さないよう、トークンの誤りだけを数える
+                if let Err(err) = &res {
+                    if err.kind == BadRequest {
+                        rate_limit::record_auth_failure(&http_req);
+                    }
+                }
+                res
+            }
         });
 
         self.schema.execute(gql_req).await.into()
     }
 }
 
-async fn verify_token(hv: &HeaderValue, master_api_token: &String) -> AppResult<Authorized> {
+async fn verify_token(
+    hv: &HeaderValue,
+    master_api_token: &str,
+    api_key_repo: &ddb::types::api_key::Repository,
+) -> AppResult<Authorized> {
     let token_str = hv
         .to_str()
         .map_err(BadRequest.from_srcf())?
         .strip_prefix("Bearer ")
         .ok_or_else(|| BadRequest.with("invalid authorization header"))?;
 
-    if token_str.to_string() != master_api_token.to_string() {
-        return Err(BadRequest.with("invalid token"));
+    // 最初のAPIキーの発行に使う。APIキーの発行・失効の権限だけを持ち、他の操作は発行したキーで行う
+    // 空にした場合は無効になる
+    if !master_api_token.is_empty()
+        && constant_time_eq(token_str.as_bytes(), master_api_token.as_bytes())
+    {
+        return Ok(Authorized {
+            api_key_id: None,
+            name: "master".to_string(),
+            scopes: vec![Scope::KeysWrite],
+        });
+    }
+
+    let (id, secret) = domain::api_key::ApiKey::parse_token(token_str)
+        .ok_or_else(|| BadRequest.with("invalid token"))?;
+    let key = api_key_repo
+        .get(&id)
+        .await
+        .not_found_to_none()?
+        .filter(|v| v.verify(secret))
+        .ok_or_else(|| BadRequest.with("invalid token"))?;
+
+    let now = time::now();
+    if key.should_touch(now) {
+        if let Err(err) = api_key_repo.touch(&key.id, now).await {
+            eprintln!(
+                "APIキーの最終利用日時の更新に失敗しました: {}, {}",
+                key.id.as_str(),
+                err
+            );
+        }
     }
 
-    Ok(Authorized {})
+    Ok(Authorized {
+        api_key_id: Some(key.id),
+        name: key.name,
+        scopes: key.scopes,
+    })
 }
 
 #[derive(Default)]
 pub struct Query;
 #[Object]
 impl Query {
-    async fn hello(&self, ctx: &Context<'_>) -> Result<bool, errors::Error> {
-        ctx.verified()?;
+    #[graphql(guard = "ScopeGuard(Scope::Read)")]
+    async fn hello(&self) -> Result<bool, errors::Error> {
         Ok(true)
     }
+
+    #[graphql(guard = "ScopeGuard(Scope::Read)")]
+    async fn migrations(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Vec<MigrationDefinition>, errors::Error> {
+        let migration_repo = ctx.data::<ddb::types::migration::Repository>()?;
+
+        let mut records = migration_repo
+            .find_all()
+            .await?
+            .into_iter()
+            .map(|v| (v.id.clone(), v))
+            .collect::<HashMap<_, _>>();
+
+        Ok(migration::definitions()
+            .into_iter()
+            .map(|def| MigrationDefinition {
+                name: def.name().to_string(),
+                version: def.version(),
+                description: def.description().to_string(),
+                last_run: records
+                    .remove(&domain::migration::Migration::id_of(
+                        def.name(),
+                        def.version(),
+                        false,
+                    ))
+                    .map(Migration::from),
+                last_dry_run: records
+                    .remove(&domain::migration::Migration::id_of(
+                        def.name(),
+                        def.version(),
+                        true,
+                    ))
+                    .map(Migration::from),
+            })
+            .collect())
+    }
+
+    #[graphql(guard = "ScopeGuard(Scope::Read)")]
+    async fn migration(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        #[graphql(default = false)] dry_run: bool,
+    ) -> Result<Migration, errors::Error> {
+        let migration_repo = ctx.data::<ddb::types::migration::Repository>()?;
+
+        let def = migration::find_definition(&name)?;
+        let id = domain::migration::Migration::id_of(def.name(), def.version(), dry_run);
+        Ok(migration_repo.get(&id).await?.into())
+    }
+
+    #[graphql(
+        guard = "ScopeGuard(Scope::Read)",
+        complexity = "limit::complexity_of(limit, child_complexity)"
+    )]
+    async fn crawl_failures(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "CrawlFailureStatus::Pending")] status: CrawlFailureStatus,
+        cursor: Option<String>,
+        limit: Option<i32>,
+    ) -> Result<Connection<String, CrawlFailure>, errors::Error> {
+        let failure_repo = ctx.data::<ddb::types::crawl_failure::Repository>()?;
+
+        let failures = failure_repo
+            .find_by_status(
+                status.into(),
+                cursor.map(Cursor::from),
+                Some(limit::limit_of(limit)),
+            )
+            .await?;
+        Ok(connection_from(failures, CrawlFailure::from))
+    }
+
+    #[graphql(guard = "ScopeGuard(Scope::Read)")]
+    async fn crawl_failure(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> Result<CrawlFailure, errors::Error> {
+        let failure_repo = ctx.data::<ddb::types::crawl_failure::Repository>()?;
+
+        let id = domain::crawl_failure::Id::new(id.0);
+        Ok(failure_repo.get(&id).await?.into())
+    }
+
+    // 商品のクロールの状態を返す
+    #[graphql(guard = "ScopeGuard(Scope::Read)")]
+    async fn product(&self, ctx: &Context<'_>, id: ID) -> Result<Product, errors::Error> {
+        let product_repo = ctx.data::<ddb::types::product::Repository>()?;
+
+        let id = domain::product::Id::new(id.0);
+        Ok(product_repo.get(&id).await?.into())
+    }
+
+    // ミューテーションの実行記録を新しい順に返す
+    #[graphql(
+        guard = "ScopeGuard(Scope::Read)",
+        complexity = "limit::complexity_of(limit, child_complexity)"
+    )]
+    async fn audit_events(
+        &self,
+        ctx: &Context<'_>,
+        cursor: Option<String>,
+        limit: Option<i32>,
+    ) -> Result<Connection<String, AuditEvent>, errors::Error> {
+        let audit_event_repo = ctx.data::<ddb::types::audit_event::Repository>()?;
+
+        let events = audit_event_repo
+            .find_latest(cursor.map(Cursor::from), Some(limit::limit_of(limit)))
+            .await?;
+        Ok(connection_from(events, AuditEvent::from))
+    }
+
+    #[graphql(guard = "ScopeGuard(Scope::Read)")]
+    async fn api_keys(&self, ctx: &Context<'_>) -> Result<Vec<ApiKey>, errors::Error> {
+        let api_key_repo = ctx.data::<ddb::types::api_key::Repository>()?;
+        Ok(api_key_repo
+            .find_all()
+            .await?
+            .into_iter()
+            .map(ApiKey::from)
+            .collect())
+    }
 }
 
 #[derive(Default)]
 pub struct Mutation;
 #[Object]
 impl Mutation {
+    #[graphql(
+        guard = "ScopeGuard(Scope::Migrate)",
+        deprecation = "use runMigration(name: \"refresh-product-attributes\")"
+    )]
     async fn migrate(&self, ctx: &Context<'_>) -> Result<bool, errors::Error> {
-        ctx.verified()?;
-        let product_repo = ctx.data::<ddb::types::product::Repository>()?;
+        let migration_repo = ctx.data::<ddb::types::migration::Repository>()?;
 
-        let products = product_repo.find_all().await?;
-        for product in products {
-            product_repo.put(product.clone()).await?;
+        let def = migration::find_definition("refresh-product-attributes")?;
+        if let Start::Started(current) =
+            migration::start(def.as_ref(), migration_repo, false).await?
+        {
+            migration::run(
+                def.as_ref(),
+                migration_repo,
+                current,
+                RunOptions {
+                    dry_run: false,
+                    chunk_size: migration::DEFAULT_CHUNK_SIZE,
+                    time_budget: Some(INLINE_TIME_BUDGET),
+                },
+            )
+            .await?;
         }
 
         Ok(true)
     }
+
+    // MIGRATION_SNS_ARNが設定されていればSNS経由でバックグラウンド実行し、
+    // なければ時間の許す範囲でこのリクエスト内で実行する(再度呼び出すと続きから再開する)
+    #[graphql(guard = "ScopeGuard(Scope::Migrate)")]
+    async fn run_migration(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        #[graphql(default = false)] dry_run: bool,
+        chunk_size: Option<i32>,
+    ) -> Result<Migration, errors::Error> {
+        let migration_repo = ctx.data::<ddb::types::migration::Repository>()?;
+        let envs = ctx.data::<Environments>()?;
+
+        let def = migration::find_definition(&name)?;
+        let chunk_size = chunk_size.unwrap_or(migration::DEFAULT_CHUNK_SIZE);
+        if chunk_size <= 0 {
+            return Err(BadRequest.with("chunkSize must be positive").into());
+        }
+
+        // 実行中または完了済みの場合は新たに始めず、その記録を返す
+        let current = match migration::start(def.as_ref(), migration_repo, dry_run).await? {
+            Start::Started(v) => v,
+            Start::Existing(v) => return Ok(v.into()),
+        };
+        let result = match envs.migration_sns_arn.clone() {
+            Some(sns_arn) => {
+                let sns = ctx.data::<sns::Adapter>()?;
+                sns.publish(
+                    migrator::Request {
+                        body: migrator::RequestBody::RunMigration(migrator::RunMigrationRequest {
+                            name: def.name().to_string(),
+                            dry_run,
+                            chunk_size: Some(chunk_size),
+                            revision: Some(current.revision),
+                        }),
+                    },
+                    sns_arn,
+                )
+                .await?;
+                current
+            }
+            None => {
+                migration::run(
+                    def.as_ref(),
+                    migration_repo,
+                    current,
+                    RunOptions {
+                        dry_run,
+                        chunk_size,
+                        time_budget: Some(INLINE_TIME_BUDGET),
+                    },
+                )
+                .await?
+            }
+        };
+
+        Ok(result.into())
+    }
+
+    #[graphql(guard = "ScopeGuard(Scope::CrawlTrigger)")]
+    async fn replay_crawl_failure(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> Result<CrawlFailure, errors::Error> {
+        let failure_repo = ctx.data::<ddb::types::crawl_failure::Repository>()?;
+
+        let failure = failure_repo
+            .get(&domain::crawl_failure::Id::new(id.0))
+            .await?;
+        replay_crawl_failure(ctx, failure).await
+    }
+
+    // idsを指定しなければ未解決(Pending)の失敗を新しい順にlimit件まで再実行する
+    #[graphql(guard = "ScopeGuard(Scope::CrawlTrigger)")]
+    async fn replay_crawl_failures(
+        &self,
+        ctx: &Context<'_>,
+        ids: Option<Vec<ID>>,
+        #[graphql(default = 20)] limit: i32,
+    ) -> Result<Vec<CrawlFailure>, errors::Error> {
+        let failure_repo = ctx.data::<ddb::types::crawl_failure::Repository>()?;
+
+        if !(1..=MAX_BULK_REPLAY).contains(&limit) {
+            return Err(BadRequest
+                .with(format!("limit must be between 1 and {}", MAX_BULK_REPLAY))
+                .into());
+        }
+        let failures = match ids {
+            Some(ids) => {
+                if ids.len() > MAX_BULK_REPLAY as usize {
+                    return Err(BadRequest
+                        .with(format!("ids must be at most {}", MAX_BULK_REPLAY))
+                        .into());
+                }
+                let mut failures = vec![];
+                for id in ids {
+                    failures.push(
+                        failure_repo
+                            .get(&domain::crawl_failure::Id::new(id.0))
+                            .await?,
+                    );
+                }
+                failures
+            }
+            None => failure_repo
+                .find_by_status(domain::crawl_failure::Status::Pending, None, Some(limit))
+                .await?
+                .into_iter()
+                .map(|v| v.entity)
+                .collect(),
+        };
+
+        let mut replayed = vec![];
+        for failure in failures {
+            replayed.push(replay_crawl_failure(ctx, failure).await?);
+        }
+        Ok(replayed)
+    }
+
+    // トークンはこのレスポンスでしか確認できない
+    #[graphql(guard = "ScopeGuard(Scope::KeysWrite)")]
+    async fn issue_api_key(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        scopes: Vec<ApiKeyScope>,
+    ) -> Result<IssuedApiKey, errors::Error> {
+        let api_key_repo = ctx.data::<ddb::types::api_key::Repository>()?;
+
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err(BadRequest.with("name must not be empty").into());
+        }
+        if scopes.is_empty() {
+            return Err(BadRequest.with("scopes must not be empty").into());
+        }
+        let scopes: Vec<Scope> = scopes.into_iter().map(Scope::from).collect();
+        let authorized = ctx.verified()?;
+        authorized.grantable(&scopes)?;
+
+        let (key, token) = domain::api_key::ApiKey::issue(name, scopes, time::now());
+        api_key_repo.put(key.clone()).await?;
+        println!(
+            "APIキーを発行しました: {}({}) by {}",
+            key.name,
+            key.id.as_str(),
+            authorized.actor()
+        );
+        Ok(IssuedApiKey {
+            api_key: key.into(),
+            token,
+        })
+    }
+
+    #[graphql(guard = "ScopeGuard(Scope::KeysWrite)")]
+    async fn update_api_key_scopes(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        scopes: Vec<ApiKeyScope>,
+    ) -> Result<ApiKey, errors::Error> {
+        let api_key_repo = ctx.data::<ddb::types::api_key::Repository>()?;
+
+        if scopes.is_empty() {
+            return Err(BadRequest.with("scopes must not be empty").into());
+        }
+        let scopes: Vec<Scope> = scopes.into_iter().map(Scope::from).collect();
+        let authorized = ctx.verified()?;
+        authorized.grantable(&scopes)?;
+
+        let key = api_key_repo.get(&domain::api_key::Id::new(id.0)).await?;
+        if key.status != domain::api_key::Status::Active {
+            return Err(Conflict.with("revoked api key").into());
+        }
+
+        let key = key.with_scopes(scopes, time::now());
+        api_key_repo.put(key.clone()).await?;
+        println!(
+            "APIキーの権限を変更しました: {}({}) by {}",
+            key.name,
+            key.id.as_str(),
+            authorized.actor()
+        );
+        Ok(key.into())
+    }
+
+    #[graphql(guard = "ScopeGuard(Scope::KeysWrite)")]
+    async fn revoke_api_key(&self, ctx: &Context<'_>, id: ID) -> Result<ApiKey, errors::Error> {
+        let api_key_repo = ctx.data::<ddb::types::api_key::Repository>()?;
+
+        let key = api_key_repo.get(&domain::api_key::Id::new(id.0)).await?;
+        if key.status != domain::api_key::Status::Active {
+            return Ok(key.into());
+        }
+
+        let key = key.revoke(time::now());
+        api_key_repo.put(key.clone()).await?;
+        println!(
+            "APIキーを失効しました: {}({}) by {}",
+            key.name,
+            key.id.as_str(),
+            ctx.verified()?.actor()
+        );
+        Ok(key.into())
+    }
 }
+
+async fn replay_crawl_failure(
+    ctx: &Context<'_>,
+    failure: domain::crawl_failure::CrawlFailure,
+) -> Result<CrawlFailure, errors::Error> {
+    let failure_repo = ctx.data::<ddb::types::crawl_failure::Repository>()?;
+    let sns = ctx.data::<sns::Adapter>()?;
+    let envs = ctx.data::<Environments>()?;
+
+    let failure = crawl::failure::replay(
+        failure_repo,
+        sns,
+        envs.crawler_rakuten_sns_arn.clone(),
+        failure,
+    )
+    .await?;
+    Ok(failure.into())
+}
+
+// 一括再実行の件数の上限
+const MAX_BULK_REPLAY: i32 = 100;
+
+// API Gatewayのタイムアウト(29秒)に収まるように打ち切る
+const INLINE_TIME_BUDGET: Duration = Duration::from_secs(20);
@@ -0,0 +1,183 @@
+use crate::graphql::master::Authorized;
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextExecute, NextParseQuery,
+};
+use async_graphql::parser::types::{
+    DocumentOperations, ExecutableDocument, Field, OperationDefinition, OperationType, Selection,
+    SelectionSet,
+};
+use async_graphql::{Name, Response, ServerResult, Value, Variables};
+use sale::domain::audit_event::AuditEvent;
+use sale::domain::time;
+use sale::infra::aws::ddb;
+use sale::AppResult;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+// ミューテーションの実行を記録する。新しいミューテーションも個別の対応なしに記録される
+pub struct Audit;
+impl ExtensionFactory for Audit {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(AuditExtension::default())
+    }
+}
+
+#[derive(Default)]
+struct AuditExtension {
+    parsed: Mutex<Option<(ExecutableDocument, Variables)>>,
+}
+
+#[async_trait::async_trait]
+impl Extension for AuditExtension {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let doc = next.run(ctx, query, variables).await?;
+        *self.parsed.lock().unwrap() = Some((doc.clone(), variables.clone()));
+        Ok(doc)
+    }
+
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        let started = Instant::now();
+        let res = next.run(ctx, operation_name).await;
+
+        let parsed = self.parsed.lock().unwrap().take();
+        let Some((doc, variables)) = parsed else {
+            return res;
+        };
+        let Some(operation) = operation_of(&doc, operation_name) else {
+            return res;
+        };
+        if operation.ty != OperationType::Mutation {
+            return res;
+        }
+
+        let mut fields: Vec<&Field> = vec![];
+        collect_fields(&doc, &operation.selection_set.node, &mut fields);
+        let arguments = arguments_of(&fields, &resolved_variables(operation, &variables));
+        let (actor, api_key_id) = match ctx.data_opt::<AppResult<Authorized>>() {
+            Some(Ok(v)) => (v.name.clone(), v.api_key_id.clone()),
+            _ => ("unauthorized".to_string(), None),
+        };
+        let event = AuditEvent::new(
+            actor,
+            api_key_id,
+            operation_name.map(|v| v.to_string()),
+            fields.iter().map(|v| v.name.node.to_string()).collect(),
+            arguments,
+            res.errors.iter().map(|v| v.message.clone()).collect(),
+            started.elapsed().as_millis() as u64,
+            time::now(),
+        );
+
+        // 記録に失敗しても操作の結果は返す
+        match ctx.data_opt::<ddb::types::audit_event::Repository>() {
+            Some(repo) => {
+                let id = event.id.clone();
+                if let Err(err) = repo.put(event).await {
+                    eprintln!("監査ログの保存に失敗しました: {}, {}", id.as_str(), err);
+                }
+            }
+            None => eprintln!("監査ログの保存先がありません"),
+        }
+        res
+    }
+}
+
+// トップレベルのフィールドを集める。フラグメントの中のフィールドも展開する
+fn collect_fields<'a>(
+    doc: &'a ExecutableDocument,
+    selection_set: &'a SelectionSet,
+    fields: &mut Vec<&'a Field>,
+) {
+    for selection in &selection_set.items {
+        match &selection.node {
+            Selection::Field(field) => fields.push(&field.node),
+            Selection::FragmentSpread(spread) => {
+                if let Some(fragment) = doc.fragments.get(&spread.node.fragment_name.node) {
+                    collect_fields(doc, &fragment.node.selection_set.node, fields);
+                }
+            }
+            Selection::InlineFragment(fragment) => {
+                collect_fields(doc, &fragment.node.selection_set.node, fields);
+            }
+        }
+    }
+}
+
+// 渡されなかった変数は定義の既定値にする
+fn resolved_variables(
+    operation: &OperationDefinition,
+    variables: &Variables,
+) -> BTreeMap<Name, Value> {
+    let mut resolved: BTreeMap<Name, Value> = operation
+        .variable_definitions
+        .iter()
+        .filter_map(|v| {
+            let default = v.node.default_value()?.clone();
+            Some((v.node.name.node.clone(), default))
+        })
+        .collect();
+    resolved.extend(variables.iter().map(|(k, v)| (k.clone(), v.clone())));
+    resolved
+}
+
+// トップレベルのフィールドごとに、インラインの値と変数を展開した引数をまとめる
+// 別名を付けた場合は別名をキーにする
+fn arguments_of(fields: &[&Field], variables: &BTreeMap<Name, Value>) -> serde_json::Value {
+    let arguments = fields
+        .iter()
+        .map(|field| {
+            let args = field
+                .arguments
+                .iter()
+                .map(|(name, value)| {
+                    let value = value
+                        .node
+                        .clone()
+                        .into_const_with(|name| {
+                            Ok::<_, Infallible>(
+                                variables.get(&name).cloned().unwrap_or(Value::Null),
+                            )
+                        })
+                        .unwrap_or(Value::Null);
+                    (name.node.to_string(), value.into_json().unwrap_or_default())
+                })
+                .collect();
+            (
+                field.response_key().node.to_string(),
+                serde_json::Value::Object(args),
+            )
+        })
+        .collect();
+    serde_json::Value::Object(arguments)
+}
+
+// 実行された操作。名前が省略された場合は唯一の操作を実行する
+fn operation_of<'a>(
+    doc: &'a ExecutableDocument,
+    operation_name: Option<&str>,
+) -> Option<&'a OperationDefinition> {
+    match (&doc.operations, operation_name) {
+        (DocumentOperations::Single(op), _) => Some(&op.node),
+        (DocumentOperations::Multiple(ops), Some(name)) => ops
+            .iter()
+            .find(|(k, _)| k.as_str() == name)
+            .map(|(_, v)| &v.node),
+        (DocumentOperations::Multiple(ops), None) if ops.len() == 1 => {
+            ops.values().next().map(|v| &v.node)
+        }
+        _ => None,
+    }
+}
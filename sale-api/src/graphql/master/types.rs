@@ -0,0 +1,5 @@
+pub mod api_key;
+pub mod audit_event;
+pub mod crawl_failure;
+pub mod migration;
+pub mod product;
@@ -0,0 +1,65 @@
+use crate::graphql::shared::types::DateTime;
+use async_graphql::{Object, SimpleObject, ID};
+use derive_more::{From, Into};
+use sale::domain;
+
+#[derive(Debug, Clone, Into, From)]
+pub struct ApiKey(domain::api_key::ApiKey);
+#[Object]
+impl ApiKey {
+    async fn id(&self) -> ID {
+        ID(self.0.id.clone().into())
+    }
+
+    async fn name(&self) -> String {
+        self.0.name.clone()
+    }
+
+    async fn scopes(&self) -> Vec<ApiKeyScope> {
+        self.0.scopes.iter().map(|v| (*v).into()).collect()
+    }
+
+    async fn status(&self) -> ApiKeyStatus {
+        self.0.status.into()
+    }
+
+    async fn created_at(&self) -> DateTime {
+        self.0.created_at.into()
+    }
+
+    async fn updated_at(&self) -> DateTime {
+        self.0.updated_at.into()
+    }
+
+    async fn last_used_at(&self) -> Option<DateTime> {
+        self.0.last_used_at.map(|v| v.into())
+    }
+
+    async fn revoked_at(&self) -> Option<DateTime> {
+        self.0.revoked_at.map(|v| v.into())
+    }
+}
+
+// トークンは発行時にだけ返す
+#[derive(SimpleObject)]
+pub struct IssuedApiKey {
+    pub api_key: ApiKey,
+    pub token: String,
+}
+
+#[derive(async_graphql::Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "sale::domain::api_key::Scope")]
+pub enum ApiKeyScope {
+    Read,
+    CrawlTrigger,
+    Migrate,
+    TargetsWrite,
+    KeysWrite,
+}
+
+#[derive(async_graphql::Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "sale::domain::api_key::Status")]
+pub enum ApiKeyStatus {
+    Active,
+    Revoked,
+}
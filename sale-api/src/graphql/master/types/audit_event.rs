@@ -0,0 +1,57 @@
+use crate::graphql::shared::types::DateTime;
+use async_graphql::{Object, ID};
+use derive_more::{From, Into};
+use sale::domain;
+
+#[derive(Debug, Clone, Into, From)]
+pub struct AuditEvent(domain::audit_event::AuditEvent);
+#[Object]
+impl AuditEvent {
+    async fn id(&self) -> ID {
+        ID(self.0.id.clone().into())
+    }
+
+    async fn actor(&self) -> String {
+        self.0.actor.clone()
+    }
+
+    async fn api_key_id(&self) -> Option<ID> {
+        self.0.api_key_id.clone().map(|v| ID(v.into()))
+    }
+
+    async fn operation_name(&self) -> Option<String> {
+        self.0.operation_name.clone()
+    }
+
+    async fn fields(&self) -> Vec<String> {
+        self.0.fields.clone()
+    }
+
+    // フィールドごとの、変数を展開して秘密の値を伏せた引数(JSON)
+    async fn arguments(&self) -> String {
+        self.0.arguments.clone()
+    }
+
+    async fn result(&self) -> AuditResult {
+        self.0.result.into()
+    }
+
+    async fn errors(&self) -> Vec<String> {
+        self.0.errors.clone()
+    }
+
+    async fn duration_ms(&self) -> u64 {
+        self.0.duration_ms
+    }
+
+    async fn created_at(&self) -> DateTime {
+        self.0.created_at.into()
+    }
+}
+
+#[derive(async_graphql::Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "sale::domain::audit_event::AuditResult")]
+pub enum AuditResult {
+    Succeeded,
+    Failed,
+}
@@ -0,0 +1,64 @@
+use crate::graphql::shared::types::DateTime;
+use async_graphql::{Object, ID};
+use derive_more::{From, Into};
+use sale::domain;
+
+#[derive(Debug, Clone, Into, From)]
+pub struct CrawlFailure(domain::crawl_failure::CrawlFailure);
+#[Object]
+impl CrawlFailure {
+    async fn id(&self) -> ID {
+        ID(self.0.id.clone().into())
+    }
+
+    async fn target(&self) -> CrawlFailureTarget {
+        self.0.target.into()
+    }
+
+    async fn payload(&self) -> String {
+        self.0.payload.clone()
+    }
+
+    async fn product_id(&self) -> Option<ID> {
+        self.0.product_id.clone().map(|v| ID(v.into()))
+    }
+
+    async fn error(&self) -> String {
+        self.0.error.clone()
+    }
+
+    async fn attempts(&self) -> u32 {
+        self.0.attempts
+    }
+
+    async fn status(&self) -> CrawlFailureStatus {
+        self.0.status.into()
+    }
+
+    async fn created_at(&self) -> DateTime {
+        self.0.created_at.into()
+    }
+
+    async fn updated_at(&self) -> DateTime {
+        self.0.updated_at.into()
+    }
+
+    async fn replayed_at(&self) -> Option<DateTime> {
+        self.0.replayed_at.map(|v| v.into())
+    }
+}
+
+#[derive(async_graphql::Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "sale::domain::crawl_failure::Target")]
+pub enum CrawlFailureTarget {
+    Message,
+    Product,
+}
+
+#[derive(async_graphql::Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "sale::domain::crawl_failure::Status")]
+pub enum CrawlFailureStatus {
+    Pending,
+    Replayed,
+    Resolved,
+}
@@ -0,0 +1,79 @@
+use crate::graphql::shared::types::DateTime;
+use async_graphql::{Object, SimpleObject, ID};
+use derive_more::{From, Into};
+use sale::domain;
+
+#[derive(Debug, Clone, Into, From)]
+pub struct Migration(domain::migration::Migration);
+#[Object]
+impl Migration {
+    async fn id(&self) -> ID {
+        ID(self.0.id.clone().into())
+    }
+
+    async fn name(&self) -> String {
+        self.0.name.clone()
+    }
+
+    async fn version(&self) -> u32 {
+        self.0.version
+    }
+
+    async fn dry_run(&self) -> bool {
+        self.0.dry_run
+    }
+
+    async fn status(&self) -> MigrationStatus {
+        self.0.status.into()
+    }
+
+    async fn cursor(&self) -> Option<String> {
+        self.0.cursor.clone()
+    }
+
+    async fn chunks(&self) -> u64 {
+        self.0.chunks
+    }
+
+    async fn processed(&self) -> u64 {
+        self.0.processed
+    }
+
+    async fn touched(&self) -> u64 {
+        self.0.touched
+    }
+
+    async fn error(&self) -> Option<String> {
+        self.0.error.clone()
+    }
+
+    async fn started_at(&self) -> DateTime {
+        self.0.started_at.into()
+    }
+
+    async fn updated_at(&self) -> DateTime {
+        self.0.updated_at.into()
+    }
+
+    async fn completed_at(&self) -> Option<DateTime> {
+        self.0.completed_at.map(|v| v.into())
+    }
+}
+
+#[derive(async_graphql::Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "sale::domain::migration::Status")]
+pub enum MigrationStatus {
+    Running,
+    Paused,
+    Completed,
+    Failed,
+}
+
+#[derive(SimpleObject)]
+pub struct MigrationDefinition {
+    pub name: String,
+    pub version: u32,
+    pub description: String,
+    pub last_run: Option<Migration>,
+    pub last_dry_run: Option<Migration>,
+}
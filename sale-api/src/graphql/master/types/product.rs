@@ -0,0 +1,61 @@
+use crate::graphql::shared::types::DateTime;
+use async_graphql::{Object, ID};
+use derive_more::{From, Into};
+use sale::domain;
+
+// クロールの状態。公開APIには出さず、運用の確認に使う
+#[derive(Debug, Clone, Into, From)]
+pub struct Product(domain::product::Product);
+#[Object]
+impl Product {
+    async fn id(&self) -> ID {
+        ID(self.0.id.clone().into())
+    }
+
+    async fn status(&self) -> ProductStatus {
+        self.0.status.into()
+    }
+
+    async fn detail_url(&self) -> String {
+        self.0.detail_url.to_string()
+    }
+
+    async fn charset(&self) -> Option<String> {
+        self.0.charset.clone()
+    }
+
+    async fn last_checked_at(&self) -> Option<DateTime> {
+        self.0.last_checked_at.map(|v| v.into())
+    }
+
+    async fn next_check_at(&self) -> Option<DateTime> {
+        self.0.next_check_at.map(|v| v.into())
+    }
+
+    async fn unchanged_count(&self) -> u32 {
+        self.0.unchanged_count
+    }
+
+    async fn failure_count(&self) -> u32 {
+        self.0.failure_count
+    }
+
+    async fn expires_at(&self) -> Option<DateTime> {
+        self.0.expires_at.map(|v| v.into())
+    }
+
+    async fn updated_at(&self) -> DateTime {
+        self.0.updated_at.into()
+    }
+}
+
+#[derive(async_graphql::Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "sale::domain::product::Status")]
+pub enum ProductStatus {
+    Prepare,
+    Active,
+    Expired,
+    SoldOut,
+    Removed,
+    Failed,
+}
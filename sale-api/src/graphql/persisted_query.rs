@@ -0,0 +1,124 @@
+use async_graphql::extensions::apollo_persisted_queries::{
+    ApolloPersistedQueries, CacheStorage, LruCacheStorage,
+};
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest,
+};
+use async_graphql::parser::parse_query;
+use async_graphql::parser::types::ExecutableDocument;
+use async_graphql::{
+    ErrorExtensionValues, ObjectType, Request, SchemaBuilder, ServerError, ServerResult,
+    SubscriptionType,
+};
+use sale::env::Environments;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// クライアントから登録されたクエリを覚えておく件数
+const CACHE_CAPACITY: usize = 1000;
+
+// 事前に登録したクエリと、クライアントから登録されたクエリを持つ
+// 登録済みのクエリだけを受け付ける場合はクライアントからの登録を無視する
+#[derive(Clone)]
+struct Storage {
+    manifest: Arc<HashMap<String, ExecutableDocument>>,
+    cache: Option<LruCacheStorage>,
+}
+impl Storage {
+    fn new(manifest: HashMap<String, ExecutableDocument>, registrable: bool) -> Self {
+        Self {
+            manifest: Arc::new(manifest),
+            cache: registrable.then(|| LruCacheStorage::new(CACHE_CAPACITY)),
+        }
+    }
+}
+#[async_trait::async_trait]
+impl CacheStorage for Storage {
+    async fn get(&self, key: String) -> Option<ExecutableDocument> {
+        if let Some(doc) = self.manifest.get(&key) {
+            return Some(doc.clone());
+        }
+        match &self.cache {
+            Some(cache) => cache.get(key).await,
+            None => None,
+        }
+    }
+
+    async fn set(&self, key: String, query: ExecutableDocument) {
+        if let Some(cache) = &self.cache {
+            cache.set(key, query).await;
+        }
+    }
+}
+
+// ハッシュとクエリの対応を書いたJSONを読み込む
+fn load_manifest(path: &str) -> HashMap<String, ExecutableDocument> {
+    let text = std::fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("failed to read persisted queries {}: {}", path, err));
+    let queries: HashMap<String, String> = serde_json::from_str(&text)
+        .unwrap_or_else(|err| panic!("failed to parse persisted queries {}: {}", path, err));
+    queries
+        .into_iter()
+        .map(|(hash, query)| {
+            let doc = parse_query(&query)
+                .unwrap_or_else(|err| panic!("failed to parse persisted query {}: {}", hash, err));
+            (hash, doc)
+        })
+        .collect()
+}
+
+// クエリの本文を含むリクエストを拒否する
+struct PersistedQueriesOnly;
+impl ExtensionFactory for PersistedQueriesOnly {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(PersistedQueriesOnlyExtension)
+    }
+}
+
+struct PersistedQueriesOnlyExtension;
+#[async_trait::async_trait]
+impl Extension for PersistedQueriesOnlyExtension {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        if !request.query.is_empty() {
+            let mut err = ServerError::new("登録済みのクエリのみ実行できます", None);
+            let mut ext = ErrorExtensionValues::default();
+            ext.set("code", "PERSISTED_QUERY_REQUIRED");
+            err.extensions = Some(ext);
+            return Err(err);
+        }
+        next.run(ctx, request).await
+    }
+}
+
+// Automatic Persisted Queries を有効にする
+// 登録済みのクエリだけを受け付ける場合は、事前に登録したクエリのハッシュだけを実行する
+pub fn apply<Q, M, S>(
+    builder: SchemaBuilder<Q, M, S>,
+    envs: &Environments,
+) -> SchemaBuilder<Q, M, S>
+where
+    Q: ObjectType + 'static,
+    M: ObjectType + 'static,
+    S: SubscriptionType + 'static,
+{
+    let manifest = match &envs.graphql_persisted_queries_path {
+        Some(path) => load_manifest(path),
+        None if envs.graphql_persisted_queries_only => {
+            panic!("env GRAPHQL_PERSISTED_QUERIES_PATH missing")
+        }
+        None => HashMap::new(),
+    };
+
+    let only = envs.graphql_persisted_queries_only;
+    let builder = if only {
+        builder.extension(PersistedQueriesOnly)
+    } else {
+        builder
+    };
+    builder.extension(ApolloPersistedQueries::new(Storage::new(manifest, !only)))
+}
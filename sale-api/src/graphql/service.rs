@@ -1,22 +1,27 @@
-use crate::graphql::data_loader::{DataLoader, ProductLoader};
+use crate::graphql::data_loader::{
+    loader_of, PopularityLoader, ProductGroupLoader, ProductLoader, ShopLoader,
+};
 use crate::graphql::service::mutation::MutationRoot;
 use crate::graphql::service::query::QueryRoot;
+use crate::graphql::service::subscription::SubscriptionRoot;
+use crate::graphql::{limit, persisted_query};
+use crate::rate_limit;
 use actix_web::http::header::{HeaderMap, HeaderValue};
 use actix_web::HttpRequest;
-use async_graphql::EmptySubscription;
-use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse};
+use actix_web::{web, HttpResponse};
+use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse, GraphQLSubscription};
 use sale::errors::AppError;
 use sale::errors::Kind::Unauthorized;
 use sale::{di, domain, AppResult};
-use std::sync::Arc;
 
 mod mutation;
 mod query;
+mod subscription;
 mod types;
 
 type AuthorizedUserId = domain::user::Id;
 
-pub type Schema = async_graphql::Schema<QueryRoot, MutationRoot, EmptySubscription>;
+pub type Schema = async_graphql::Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
 
 #[derive(Clone)]
 pub struct HttpHandler {
@@ -28,14 +33,28 @@ impl HttpHandler {
     pub async fn new() -> Self {
         let envs = di::ENVIRONMENTS.clone();
 
-        let schema = Schema::build(
+        let builder = Schema::build(
             QueryRoot::default(),
             MutationRoot::default(),
-            EmptySubscription,
-        )
-        .data(di::DB_PRODUCT_REPOSITORY.get().await.clone())
-        .data(DataLoader(Arc::new(di::DB_PRODUCT_REPOSITORY.get().await.clone())) as ProductLoader)
-        .finish();
+            SubscriptionRoot::default(),
+        );
+        let builder = limit::apply(builder, &envs);
+        // 公開されているスキーマのため、登録済みのクエリだけを受け付けられるようにする
+        let builder = persisted_query::apply(builder, &envs);
+        let schema = builder
+            .data(di::DB_PRODUCT_REPOSITORY.get().await.clone())
+            .data(di::DB_CATEGORY_REPOSITORY.get().await.clone())
+            .data(loader_of(di::DB_PRODUCT_REPOSITORY.get().await.clone()) as ProductLoader)
+            .data(
+                loader_of(di::DB_PRODUCT_GROUP_REPOSITORY.get().await.clone())
+                    as ProductGroupLoader,
+            )
+            .data(loader_of(di::DB_SHOP_REPOSITORY.get().await.clone()) as ShopLoader)
+            .data(di::DB_PRODUCT_STAT_REPOSITORY.get().await.clone())
+            .data(loader_of(di::DB_PRODUCT_STAT_REPOSITORY.get().await.clone()) as PopularityLoader)
+            .data(di::AFFILIATE_LINKS.clone())
+            .data(di::EVENT_BUS.get().await.clone())
+            .finish();
 
         HttpHandler {
             schema,
@@ -46,15 +65,21 @@ impl HttpHandler {
     pub async fn handle(&self, http_req: HttpRequest, gql_req: GraphQLRequest) -> GraphQLResponse {
         let mut gql_req = gql_req.into_inner();
 
-        let headers: HeaderMap = HeaderMap::from_iter(http_req.headers().clone().into_iter());
+        let headers: HeaderMap = HeaderMap::from_iter(http_req.headers().clone());
         gql_req = gql_req.data(match headers.get("authorization") {
             None => Err(Unauthorized.into()),
-            Some(hv) => self.verify_token(hv).await,
+            Some(hv) => {
+                let res = self.verify_token(hv).await;
+                if res.is_err() {
+                    rate_limit::record_auth_failure(&http_req);
+                }
+                res
+            }
         });
 
         if !self.is_prod {
             if let Some(hv) = headers.get("x-debug-user-id") {
-                if let Some(v) = hv.to_str().ok() {
+                if let Ok(v) = hv.to_str() {
                     gql_req = gql_req.data(Ok::<AuthorizedUserId, AppError>(v.to_string().into()));
                 }
             }
@@ -63,6 +88,15 @@ impl HttpHandler {
         self.schema.execute(gql_req).await.into()
     }
 
+    // graphql-ws での購読。WebSocketを維持できないLambdaでは使わない
+    pub fn handle_subscription(
+        &self,
+        http_req: HttpRequest,
+        payload: web::Payload,
+    ) -> actix_web::Result<HttpResponse> {
+        GraphQLSubscription::new(self.schema.clone()).start(&http_req, payload)
+    }
+
     async fn verify_token(&self, _hv: &HeaderValue) -> AppResult<AuthorizedUserId> {
         Ok(domain::user::Id::generate())
     }
@@ -1,15 +1,22 @@
 use crate::graphql::connection::connection_from;
-use crate::graphql::data_loader::ProductLoader;
+use crate::graphql::data_loader::{ProductGroupLoader, ProductLoader, ShopLoader};
 use crate::graphql::errors;
 use crate::graphql::errors::not_found_error;
-use crate::graphql::service::types::product::Product;
+use crate::graphql::limit;
+use crate::graphql::service::types::category::{tree_from, Category};
+use crate::graphql::service::types::product::{PopularityWindow, Product, ProductSort};
+use crate::graphql::service::types::product_group::ProductGroup;
+use crate::graphql::service::types::shop::Shop;
 use async_graphql::connection::Connection;
-use async_graphql::dataloader::Loader;
 use async_graphql::{Context, MergedObject, Object, ID};
 use sale::domain;
+use sale::domain::time;
+use sale::errors::Kind::BadRequest;
 use sale::infra::aws::ddb;
 use sale::infra::aws::ddb::cursor::Cursor;
 
+const TOP_DEALS_CANDIDATE_FACTOR: usize = 5;
+
 #[derive(MergedObject, Default)]
 pub struct QueryRoot(DefaultQuery);
 
@@ -17,26 +24,154 @@ pub struct QueryRoot(DefaultQuery);
 pub struct DefaultQuery;
 #[Object]
 impl DefaultQuery {
+    #[graphql(complexity = "limit::complexity_of(limit, child_complexity)")]
     async fn products(
         &self,
         ctx: &Context<'_>,
+        category: Option<ID>,
+        #[graphql(default_with = "ProductSort::Newest")] sort: ProductSort,
         cursor: Option<String>,
         limit: Option<i32>,
     ) -> Result<Connection<String, Product>, errors::Error> {
         let product_repo = ctx.data::<ddb::types::product::Repository>()?;
-        let cursor = cursor.map(|v| Cursor::from(v));
-        let products = product_repo
-            .find_by_status(domain::product::Status::Active, cursor, limit)
-            .await?;
-        Ok(connection_from(products, |v| Product::from(v)))
+        let cursor = cursor.map(Cursor::from);
+        let limit = Some(limit::limit_of(limit));
+        let status = domain::product::Status::Active;
+        let products = match (category, sort) {
+            (Some(_), ProductSort::BestDeal) => {
+                return Err(BadRequest
+                    .with("カテゴリの絞り込みとお得度順は同時に指定できません")
+                    .into())
+            }
+            (None, ProductSort::BestDeal) => {
+                product_repo
+                    .find_by_status_deal_score(status, cursor, limit)
+                    .await?
+            }
+            (Some(id), ProductSort::Newest) => {
+                let category_repo = ctx.data::<ddb::types::category::Repository>()?;
+                let category = category_repo.get(&domain::category::Id::new(id.0)).await?;
+                product_repo
+                    .find_by_status_category(status, &category, cursor, limit)
+                    .await?
+            }
+            (None, ProductSort::Newest) => {
+                product_repo.find_by_status(status, cursor, limit).await?
+            }
+        };
+        Ok(connection_from(products, Product::from))
+    }
+
+    // ルートから順にカテゴリの木を返す
+    async fn categories(&self, ctx: &Context<'_>) -> Result<Vec<Category>, errors::Error> {
+        let category_repo = ctx.data::<ddb::types::category::Repository>()?;
+        let categories = category_repo.find_all().await?;
+        Ok(tree_from(categories))
     }
 
     async fn product(&self, ctx: &Context<'_>, id: ID) -> Result<Product, errors::Error> {
         let product_loader = ctx.data::<ProductLoader>()?;
         let id = domain::product::Id::new(id.0);
-        let res = product_loader.load(&[id.clone()]).await?;
-        res.get(&id)
+        let res = product_loader.load_one(id).await?;
+        res.map(Product::from).ok_or(not_found_error())
+    }
+
+    // 今日確認できた販売中の商品をお得度の高い順に返す
+    #[graphql(complexity = "limit::complexity_of(limit, child_complexity)")]
+    async fn top_deals(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i32>,
+    ) -> Result<Vec<Product>, errors::Error> {
+        let product_repo = ctx.data::<ddb::types::product::Repository>()?;
+        let limit = limit::limit_of(limit) as usize;
+        let today = time::start_of_day(time::now());
+
+        // 今日更新されていない商品を除いても足りるよう、多めに読み込む
+        let products = product_repo
+            .find_by_status_deal_score(
+                domain::product::Status::Active,
+                None,
+                Some((limit * TOP_DEALS_CANDIDATE_FACTOR) as i32),
+            )
+            .await?;
+        Ok(products
+            .into_iter()
+            .map(|v| v.entity)
+            .filter(|v| {
+                v.updated_at >= today || v.last_checked_at.map(|v| v >= today).unwrap_or(false)
+            })
+            .take(limit)
+            .map(Product::from)
+            .collect())
+    }
+
+    // 期間内によく見られた販売中の商品を人気順に返す
+    #[graphql(complexity = "limit::complexity_of(limit, child_complexity)")]
+    async fn popular_products(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "PopularityWindow::Week")] window: PopularityWindow,
+        limit: Option<i32>,
+    ) -> Result<Vec<Product>, errors::Error> {
+        let stat_repo = ctx.data::<ddb::types::product_stat::Repository>()?;
+        let product_loader = ctx.data::<ProductLoader>()?;
+        let limit = limit::limit_of(limit) as usize;
+
+        let ranked = stat_repo.find_popular(window.into(), time::now()).await?;
+        // 販売終了した商品を除いても足りるよう、多めに読み込む
+        let ids: Vec<_> = ranked
+            .into_iter()
+            .take(limit * 2)
+            .map(|(id, _)| id)
+            .collect();
+        let res = product_loader.load_many(ids.iter().cloned()).await?;
+        Ok(ids
+            .iter()
+            .filter_map(|id| res.get(id))
+            .filter(|v| v.status == domain::product::Status::Active)
+            .take(limit)
             .map(|v| Product::from(v.clone()))
-            .ok_or(not_found_error())
+            .collect())
+    }
+
+    async fn product_group(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> Result<ProductGroup, errors::Error> {
+        let group_loader = ctx.data::<ProductGroupLoader>()?;
+        let id = domain::product_group::Id::new(id.0);
+        let res = group_loader.load_one(id).await?;
+        res.map(ProductGroup::from).ok_or(not_found_error())
+    }
+
+    async fn shop(&self, ctx: &Context<'_>, id: ID) -> Result<Shop, errors::Error> {
+        let shop_loader = ctx.data::<ShopLoader>()?;
+        let id = domain::shop::Id::new(id.0);
+        let res = shop_loader.load_one(id).await?;
+        res.map(Shop::from).ok_or(not_found_error())
+    }
+
+    // 店舗の販売中の商品を新しい順に返す
+    #[graphql(complexity = "limit::complexity_of(limit, child_complexity)")]
+    async fn shop_products(
+        &self,
+        ctx: &Context<'_>,
+        shop_id: ID,
+        cursor: Option<String>,
+        limit: Option<i32>,
+    ) -> Result<Connection<String, Product>, errors::Error> {
+        let product_repo = ctx.data::<ddb::types::product::Repository>()?;
+        let cursor = cursor.map(Cursor::from);
+        let products = product_repo
+            .find_by_shop_status(
+                &domain::shop::Id::new(shop_id.0),
+                domain::product::Status::Active,
+                cursor,
+                Some(limit::limit_of(limit)),
+            )
+            .await?;
+        Ok(connection_from(products, Product::from))
     }
 }
@@ -0,0 +1,146 @@
+use crate::graphql::data_loader::ProductLoader;
+use crate::graphql::errors;
+use crate::graphql::service::types::product::Product;
+use async_graphql::futures_util::stream::{self, Stream, StreamExt};
+use async_graphql::{Context, InputObject, MergedSubscription, SimpleObject, Subscription, ID};
+use sale::bus::EventBus;
+use sale::domain::product_event::ProductEvent;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+#[derive(MergedSubscription, Default)]
+pub struct SubscriptionRoot(DefaultSubscription);
+
+#[derive(InputObject, Default)]
+pub struct DealFilter {
+    // お得度の下限
+    min_deal_score: Option<f64>,
+    max_price: Option<u64>,
+    shop_id: Option<ID>,
+    // 子孫のカテゴリの商品も含む
+    category_id: Option<ID>,
+}
+impl DealFilter {
+    fn matches(&self, event: &ProductEvent) -> bool {
+        let ProductEvent::NewDeal {
+            deal_score,
+            price,
+            shop_id,
+            category_ids,
+            ..
+        } = event
+        else {
+            return false;
+        };
+        self.min_deal_score
+            .map(|v| *deal_score >= v)
+            .unwrap_or(true)
+            && self
+                .max_price
+                .map(|v| price.map(|price| price <= v).unwrap_or(false))
+                .unwrap_or(true)
+            && self
+                .shop_id
+                .as_ref()
+                .map(|v| shop_id.as_deref() == Some(v.as_str()))
+                .unwrap_or(true)
+            && self
+                .category_id
+                .as_ref()
+                .map(|v| category_ids.iter().any(|id| id == v.as_str()))
+                .unwrap_or(true)
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct PriceDrop {
+    product: Product,
+    previous_price: u64,
+    price: u64,
+}
+
+// 受信が遅れて読み飛ばした出来事は諦め、次の出来事から流す
+fn events_of(receiver: broadcast::Receiver<ProductEvent>) -> impl Stream<Item = ProductEvent> {
+    stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => return Some((event, receiver)),
+                Err(RecvError::Lagged(n)) => {
+                    eprintln!("購読者の受信が遅れたため{}件の出来事を読み飛ばしました", n);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+// 出来事の商品を読み込む。削除された商品の出来事は流さない
+async fn load_product(loader: &ProductLoader, event: &ProductEvent) -> Option<Product> {
+    let id = event.product_id();
+    match loader.load_one(id.clone()).await {
+        Ok(res) => res.map(Product::from),
+        Err(err) => {
+            eprintln!("商品の取得に失敗しました: 商品ID: {}, {}", id.as_str(), err);
+            None
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DefaultSubscription;
+#[Subscription]
+impl DefaultSubscription {
+    // 新しくお得度が付いた販売中の商品
+    async fn new_deals(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<DealFilter>,
+    ) -> Result<impl Stream<Item = Product>, errors::Error> {
+        let bus = ctx.data::<Arc<dyn EventBus>>()?;
+        let loader = ctx.data::<ProductLoader>()?.clone();
+        let filter = Arc::new(filter.unwrap_or_default());
+        Ok(events_of(bus.subscribe())
+            .filter(move |event| {
+                let matched = filter.matches(event);
+                async move { matched }
+            })
+            .filter_map(move |event| {
+                let loader = loader.clone();
+                async move { load_product(&loader, &event).await }
+            }))
+    }
+
+    // 指定した商品の値下がり
+    async fn price_dropped(
+        &self,
+        ctx: &Context<'_>,
+        product_id: ID,
+    ) -> Result<impl Stream<Item = PriceDrop>, errors::Error> {
+        let bus = ctx.data::<Arc<dyn EventBus>>()?;
+        let loader = ctx.data::<ProductLoader>()?.clone();
+        let product_id = product_id.0;
+        Ok(events_of(bus.subscribe()).filter_map(move |event| {
+            let loader = loader.clone();
+            let product_id = product_id.clone();
+            async move {
+                let ProductEvent::PriceDropped {
+                    product_id: id,
+                    previous_price,
+                    price,
+                } = &event
+                else {
+                    return None;
+                };
+                if *id != product_id {
+                    return None;
+                }
+                Some(PriceDrop {
+                    product: load_product(&loader, &event).await?,
+                    previous_price: *previous_price,
+                    price: *price,
+                })
+            }
+        }))
+    }
+}
@@ -1 +1,4 @@
+pub mod category;
 pub mod product;
+pub mod product_group;
+pub mod shop;
@@ -0,0 +1,64 @@
+use async_graphql::{Object, ID};
+use sale::domain;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct Category {
+    category: domain::category::Category,
+    children: Vec<Category>,
+}
+#[Object]
+impl Category {
+    async fn id(&self) -> ID {
+        ID(self.category.id.clone().into())
+    }
+
+    async fn name(&self) -> String {
+        self.category.name.clone()
+    }
+
+    async fn parent_id(&self) -> Option<ID> {
+        self.category.parent_id.clone().map(|v| ID(v.into()))
+    }
+
+    async fn genre_id(&self) -> Option<String> {
+        self.category.genre_id.clone()
+    }
+
+    // 子孫のカテゴリを含む販売中の商品数
+    async fn product_count(&self) -> u32 {
+        self.category.product_count
+    }
+
+    async fn children(&self) -> Vec<Category> {
+        self.children.clone()
+    }
+}
+
+// 親子関係からルートのカテゴリの一覧を作る
+pub fn tree_from(categories: Vec<domain::category::Category>) -> Vec<Category> {
+    let mut children: HashMap<Option<domain::category::Id>, Vec<domain::category::Category>> =
+        HashMap::new();
+    for category in categories {
+        children
+            .entry(category.parent_id.clone())
+            .or_default()
+            .push(category);
+    }
+    build(&mut children, None)
+}
+
+fn build(
+    children: &mut HashMap<Option<domain::category::Id>, Vec<domain::category::Category>>,
+    parent_id: Option<domain::category::Id>,
+) -> Vec<Category> {
+    let mut categories = children.remove(&parent_id).unwrap_or_default();
+    categories.sort_by_key(|v| std::cmp::Reverse(v.product_count));
+    categories
+        .into_iter()
+        .map(|category| {
+            let children = build(children, Some(category.id.clone()));
+            Category { category, children }
+        })
+        .collect()
+}
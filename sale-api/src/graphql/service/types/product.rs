@@ -1,6 +1,13 @@
+use crate::graphql::data_loader::{
+    PopularityLoader, ProductGroupLoader, ProductLoader, ShopLoader,
+};
+use crate::graphql::errors;
+use crate::graphql::service::types::product_group::{offers_of, ProductGroup};
+use crate::graphql::service::types::shop::Shop;
 use crate::graphql::shared::types::DateTime;
-use async_graphql::{Object, ID};
+use async_graphql::{Context, Object, ID};
 use derive_more::{From, Into};
+use sale::affiliate::AffiliateLinks;
 use sale::domain;
 
 #[derive(Debug, Clone, Into, From)]
@@ -12,17 +19,40 @@ impl Product {
     }
 
     async fn source(&self) -> Source {
-        self.0.source.clone().into()
+        self.0.source.into()
+    }
+
+    async fn shop_id(&self) -> Option<ID> {
+        self.0.shop_id.clone().map(|v| ID(v.into()))
+    }
+
+    async fn item_id(&self) -> Option<String> {
+        self.0.item_id.clone()
+    }
+
+    async fn shop(&self, ctx: &Context<'_>) -> Result<Option<Shop>, errors::Error> {
+        let Some(shop_id) = self.0.shop_id.clone() else {
+            return Ok(None);
+        };
+        let shop_loader = ctx.data::<ShopLoader>()?;
+        let res = shop_loader.load_one(shop_id).await?;
+        Ok(res.map(Shop::from))
     }
 
     async fn status(&self) -> Status {
-        self.0.status.clone().into()
+        self.0.status.into()
     }
 
     async fn detail_url(&self) -> String {
         self.0.detail_url.to_string()
     }
 
+    // アフィリエイトIDが未設定の販売元ではnullになる
+    async fn affiliate_url(&self, ctx: &Context<'_>) -> Result<Option<String>, errors::Error> {
+        let links = ctx.data::<AffiliateLinks>()?;
+        Ok(links.url_of(&self.0).map(|v| v.to_string()))
+    }
+
     async fn title(&self) -> Option<String> {
         self.0.title.clone()
     }
@@ -35,6 +65,14 @@ impl Product {
             .collect()
     }
 
+    // 複製済みの画像はストレージのURL、未複製の画像は元のURLを返す
+    async fn images(
+        &self,
+        #[graphql(default_with = "ImageSize::Original")] size: ImageSize,
+    ) -> Vec<String> {
+        self.0.images_of(size.into())
+    }
+
     async fn retail_price(&self) -> Option<String> {
         self.0.retail_price.clone()
     }
@@ -51,16 +89,94 @@ impl Product {
         self.0.breadcrumb.clone()
     }
 
+    async fn genre_id(&self) -> Option<String> {
+        self.0.genre_id.clone()
+    }
+
+    // ルートから末端の順
+    async fn category_ids(&self) -> Vec<ID> {
+        self.0
+            .category_ids
+            .iter()
+            .map(|v| ID(v.clone().into()))
+            .collect()
+    }
+
     async fn points(&self) -> Option<String> {
         self.0.points.clone()
     }
 
+    async fn brand(&self) -> Option<String> {
+        self.0.brand.clone()
+    }
+
+    async fn currency(&self) -> Option<String> {
+        self.0.currency.clone()
+    }
+
+    async fn rating(&self) -> Option<f64> {
+        self.0.rating
+    }
+
+    async fn review_count(&self) -> Option<u32> {
+        self.0.review_count
+    }
+
+    // 他店舗の同じ商品のまとまり
+    async fn group(&self, ctx: &Context<'_>) -> Result<Option<ProductGroup>, errors::Error> {
+        let Some(group_id) = self.0.group_id.clone() else {
+            return Ok(None);
+        };
+        let group_loader = ctx.data::<ProductGroupLoader>()?;
+        let res = group_loader.load_one(group_id).await?;
+        Ok(res.map(ProductGroup::from))
+    }
+
+    // 同じ商品の他店舗での販売。安い順
+    async fn other_offers(&self, ctx: &Context<'_>) -> Result<Vec<Product>, errors::Error> {
+        let Some(group_id) = self.0.group_id.clone() else {
+            return Ok(vec![]);
+        };
+        let group_loader = ctx.data::<ProductGroupLoader>()?;
+        let product_loader = ctx.data::<ProductLoader>()?;
+        let Some(group) = group_loader.load_one(group_id).await? else {
+            return Ok(vec![]);
+        };
+        let offers = offers_of(product_loader, &group).await?;
+        Ok(offers
+            .into_iter()
+            .filter(|v| v.id != self.0.id)
+            .map(Product::from)
+            .collect())
+    }
+
+    // 値引き率やポイント倍率などから求めたお得度(0〜100)
+    async fn deal_score(&self) -> Option<f64> {
+        self.0.deal_score.map(|v| v.0)
+    }
+
+    async fn lowest_price(&self) -> Option<u64> {
+        self.0.lowest_price
+    }
+
+    // 期間内の閲覧・クリック・お気に入りの重み付きの合計
+    async fn popularity(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "PopularityWindow::Week")] window: PopularityWindow,
+    ) -> Result<u64, errors::Error> {
+        let popularity_loader = ctx.data::<PopularityLoader>()?;
+        let key = (self.0.id.clone(), window.into());
+        let res = popularity_loader.load_one(key).await?;
+        Ok(res.map(|v| v.score).unwrap_or_default())
+    }
+
     async fn created_at(&self) -> DateTime {
-        self.0.created_at.clone().into()
+        self.0.created_at.into()
     }
 
     async fn updated_at(&self) -> DateTime {
-        self.0.updated_at.clone().into()
+        self.0.updated_at.into()
     }
 }
 
@@ -75,4 +191,35 @@ pub enum Source {
 pub enum Status {
     Prepare,
     Active,
+    Expired,
+    SoldOut,
+    Removed,
+    Failed,
+}
+
+#[derive(async_graphql::Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "sale::domain::product::ImageSize")]
+pub enum ImageSize {
+    Original,
+    Large,
+    Medium,
+    Small,
+}
+
+#[derive(async_graphql::Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "sale::domain::product_stat::Window")]
+pub enum PopularityWindow {
+    Day,
+    Week,
+    Month,
+}
+
+// 人気順は含めない。人気は日別の件数の上位から求めるため全商品を順に辿るカーソルを作れず、
+// 上位だけを返す popularProducts で扱う
+#[derive(async_graphql::Enum, Copy, Clone, Eq, PartialEq)]
+pub enum ProductSort {
+    // 新着順
+    Newest,
+    // お得度の高い順
+    BestDeal,
 }
@@ -0,0 +1,56 @@
+use crate::graphql::data_loader::ProductLoader;
+use crate::graphql::errors;
+use crate::graphql::service::types::product::Product;
+use crate::graphql::shared::types::DateTime;
+use async_graphql::{Context, Object, ID};
+use derive_more::{From, Into};
+use sale::domain;
+use sale::errors::AppError;
+
+#[derive(Debug, Clone, Into, From)]
+pub struct ProductGroup(domain::product_group::ProductGroup);
+#[Object]
+impl ProductGroup {
+    async fn id(&self) -> ID {
+        ID(self.0.id.clone().into())
+    }
+
+    // 販売中の商品を安い順に返す
+    async fn offers(&self, ctx: &Context<'_>) -> Result<Vec<Product>, errors::Error> {
+        let product_loader = ctx.data::<ProductLoader>()?;
+        let offers = offers_of(product_loader, &self.0).await?;
+        Ok(offers.into_iter().map(Product::from).collect())
+    }
+
+    async fn cheapest_offer(&self, ctx: &Context<'_>) -> Result<Option<Product>, errors::Error> {
+        let product_loader = ctx.data::<ProductLoader>()?;
+        let offers = offers_of(product_loader, &self.0).await?;
+        Ok(offers.into_iter().next().map(Product::from))
+    }
+
+    async fn created_at(&self) -> DateTime {
+        self.0.created_at.into()
+    }
+
+    async fn updated_at(&self) -> DateTime {
+        self.0.updated_at.into()
+    }
+}
+
+// 値段が取れない商品は最後にする
+pub async fn offers_of(
+    product_loader: &ProductLoader,
+    group: &domain::product_group::ProductGroup,
+) -> Result<Vec<domain::product::Product>, AppError> {
+    let res = product_loader
+        .load_many(group.product_ids.iter().cloned())
+        .await?;
+    let mut offers: Vec<domain::product::Product> = group
+        .product_ids
+        .iter()
+        .filter_map(|id| res.get(id).cloned())
+        .filter(|v| v.status == domain::product::Status::Active)
+        .collect();
+    offers.sort_by_key(|v| (v.price_value().is_none(), v.price_value()));
+    Ok(offers)
+}
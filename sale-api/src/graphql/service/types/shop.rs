@@ -0,0 +1,38 @@
+use crate::graphql::service::types::product::Source;
+use crate::graphql::shared::types::DateTime;
+use async_graphql::{Object, ID};
+use derive_more::{From, Into};
+use sale::domain;
+
+#[derive(Debug, Clone, Into, From)]
+pub struct Shop(domain::shop::Shop);
+#[Object]
+impl Shop {
+    async fn id(&self) -> ID {
+        ID(self.0.id.clone().into())
+    }
+
+    async fn source(&self) -> Source {
+        self.0.source.into()
+    }
+
+    async fn code(&self) -> String {
+        self.0.code.clone()
+    }
+
+    async fn name(&self) -> Option<String> {
+        self.0.name.clone()
+    }
+
+    async fn url(&self) -> Option<String> {
+        self.0.url.as_ref().map(|v| v.to_string())
+    }
+
+    async fn created_at(&self) -> DateTime {
+        self.0.created_at.into()
+    }
+
+    async fn updated_at(&self) -> DateTime {
+        self.0.updated_at.into()
+    }
+}
@@ -1,11 +1,15 @@
 use actix_cors::Cors;
+use actix_web::middleware::from_fn;
 use actix_web::web::Data;
 use actix_web::{guard, web, App, HttpRequest, HttpResponse, HttpServer};
 use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
 use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse};
 use sale::di;
 
+mod events;
 mod graphql;
+mod rate_limit;
+mod redirect;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -20,6 +24,8 @@ async fn main() -> std::io::Result<()> {
     let service_http_handler = graphql::service::HttpHandler::new().await;
     let master_http_handler = graphql::master::HttpHandler::new().await;
     let is_prod = envs.is_prod();
+    let affiliate_redirect_enabled = envs.affiliate_redirect_enabled;
+    let with_lambda = envs.with_lambda;
 
     let app_factory = move || {
         let mut app = App::new()
@@ -36,13 +42,38 @@ async fn main() -> std::io::Result<()> {
             .service(
                 web::resource("/service/graphql")
                     .guard(guard::Post())
+                    .wrap(from_fn(rate_limit::limit))
                     .to(service_graphql_route),
             )
             .service(
                 web::resource("/master/graphql")
                     .guard(guard::Post())
+                    .wrap(from_fn(rate_limit::limit))
                     .to(master_graphql_route),
+            )
+            .service(
+                web::resource("/service/events")
+                    .guard(guard::Post())
+                    .wrap(from_fn(rate_limit::limit))
+                    .to(events::handle),
+            );
+        if !with_lambda {
+            app = app.service(
+                web::resource("/service/graphql")
+                    .guard(guard::Get())
+                    .guard(guard::Header("upgrade", "websocket"))
+                    .wrap(from_fn(rate_limit::limit))
+                    .to(service_subscription_route),
+            );
+        }
+        if affiliate_redirect_enabled {
+            app = app.service(
+                web::resource("/go/{product_id}")
+                    .guard(guard::Get())
+                    .wrap(from_fn(rate_limit::limit))
+                    .to(redirect::handle),
             );
+        }
         if !is_prod {
             app = app.service(
                 web::resource("/service/playground")
@@ -62,7 +93,7 @@ async fn main() -> std::io::Result<()> {
     if envs.with_lambda {
         lambda_web::run_actix_on_lambda(app_factory)
             .await
-            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+            .map_err(std::io::Error::other)
     } else {
         println!("listen as http server on port {}", envs.port);
         HttpServer::new(app_factory)
@@ -80,6 +111,14 @@ async fn service_graphql_route(
     handler.handle(http_req, gql_req).await
 }
 
+async fn service_subscription_route(
+    handler: Data<graphql::service::HttpHandler>,
+    http_req: HttpRequest,
+    payload: web::Payload,
+) -> actix_web::Result<HttpResponse> {
+    handler.handle_subscription(http_req, payload)
+}
+
 async fn master_graphql_route(
     handler: Data<graphql::master::HttpHandler>,
     http_req: HttpRequest,
@@ -0,0 +1,130 @@
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header;
+use actix_web::middleware::Next;
+use actix_web::{HttpMessage, HttpRequest, HttpResponse};
+use async_graphql::{ErrorExtensionValues, Response, ServerError};
+use sale::di;
+use sale::rate_limit::RateLimiter;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+// 認証に失敗したリクエストの印。ハンドラーが付け、ミドルウェアが失敗の回数として数える
+#[derive(Clone)]
+struct AuthFailure;
+
+pub fn record_auth_failure(http_req: &HttpRequest) {
+    http_req.extensions_mut().insert(AuthFailure);
+}
+
+// IPごと、認証情報ごとに呼び出し回数を制限する
+// 認証の失敗が続いたIPからは、失敗の回数が回復するまでリクエストを受け付けない
+pub async fn limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let envs = di::ENVIRONMENTS.clone();
+    let limiter = di::API_RATE_LIMITER.get().await;
+
+    let ip = client_ip(req.request());
+    let auth_failure_key = format!("api:auth:{}", ip);
+    let auth_failure_wait = check(
+        limiter,
+        &auth_failure_key,
+        envs.api_auth_failures_per_second,
+        envs.api_auth_failure_burst,
+    )
+    .await;
+    if let Some(wait) = auth_failure_wait {
+        return Ok(rate_limited(req, wait));
+    }
+
+    let ip_wait = try_acquire(
+        limiter,
+        &format!("api:ip:{}", ip),
+        envs.api_ip_requests_per_second,
+        envs.api_ip_burst,
+    )
+    .await;
+    if let Some(wait) = ip_wait {
+        return Ok(rate_limited(req, wait));
+    }
+
+    // 認証情報はそのまま保存しないようハッシュにする
+    if let Some(credential) = req.headers().get(header::AUTHORIZATION) {
+        let user_key = format!("api:user:{:x}", Sha256::digest(credential.as_bytes()));
+        let user_wait = try_acquire(
+            limiter,
+            &user_key,
+            envs.api_user_requests_per_second,
+            envs.api_user_burst,
+        )
+        .await;
+        if let Some(wait) = user_wait {
+            return Ok(rate_limited(req, wait));
+        }
+    }
+
+    let res = next.call(req).await?;
+    if res.request().extensions().contains::<AuthFailure>() {
+        try_acquire(
+            limiter,
+            &auth_failure_key,
+            envs.api_auth_failures_per_second,
+            envs.api_auth_failure_burst,
+        )
+        .await;
+    }
+    Ok(res.map_into_left_body())
+}
+
+// 呼び出し元のIP。回数の制限やイベントの重複除去に使う
+// X-Forwarded-For などのヘッダーは呼び出し元が自由に付けられるため使わず、接続元を使う
+// Lambdaでは lambda_web が API Gateway のリクエストコンテキストの送信元IP(sourceIp)を接続元にする
+pub fn client_ip(req: &HttpRequest) -> String {
+    req.peer_addr()
+        .map(|v| v.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+// 制限の確認に失敗した場合はリクエストを止めない
+pub async fn try_acquire(
+    limiter: &RateLimiter,
+    key: &str,
+    rate: f64,
+    burst: f64,
+) -> Option<Duration> {
+    limiter
+        .try_acquire(key, rate, burst)
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("呼び出し回数の確認に失敗しました: {}, {}", key, err);
+            None
+        })
+}
+
+async fn check(limiter: &RateLimiter, key: &str, rate: f64, burst: f64) -> Option<Duration> {
+    limiter.check(key, rate, burst).await.unwrap_or_else(|err| {
+        eprintln!("呼び出し回数の確認に失敗しました: {}, {}", key, err);
+        None
+    })
+}
+
+// GraphQLのエラーの形で返す
+fn rate_limited<B>(req: ServiceRequest, wait: Duration) -> ServiceResponse<EitherBody<B>> {
+    let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+
+    let mut err = ServerError::new(
+        "リクエストが多すぎます。しばらくしてから再度お試しください",
+        None,
+    );
+    let mut ext = ErrorExtensionValues::default();
+    ext.set("code", "RATE_LIMITED");
+    ext.set("retryAfter", retry_after);
+    err.extensions = Some(ext);
+
+    let res = HttpResponse::TooManyRequests()
+        .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+        .json(Response::from_errors(vec![err]));
+    req.into_response(res).map_into_right_body()
+}
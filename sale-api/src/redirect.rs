@@ -0,0 +1,45 @@
+use crate::events;
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use sale::domain::product_stat::EventKind;
+use sale::errors::Kind::NotFound;
+use sale::{di, domain};
+
+// 外部の商品ページへの遷移。クリックを記録してからアフィリエイトリンクにリダイレクトする
+pub async fn handle(http_req: HttpRequest, path: web::Path<String>) -> HttpResponse {
+    let id = domain::product::Id::new(path.into_inner());
+    let repo = di::DB_PRODUCT_REPOSITORY.get().await;
+    let product = match repo.get(&id).await {
+        Ok(v) => v,
+        Err(err) if err.kind == NotFound => return HttpResponse::NotFound().finish(),
+        Err(err) => {
+            eprintln!("商品の取得に失敗しました: {}", err);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let location = di::AFFILIATE_LINKS.outbound_url_of(&product);
+    let header_of = |name: header::HeaderName| {
+        http_req
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    println!(
+        "クリック: product_id={} source={} affiliate={} referer={} user_agent={}",
+        product.id.as_str(),
+        product.source,
+        location != product.detail_url,
+        header_of(header::REFERER),
+        header_of(header::USER_AGENT),
+    );
+
+    events::record(&http_req, &product.id, EventKind::Click).await;
+
+    HttpResponse::Found()
+        .insert_header((header::LOCATION, location.to_string()))
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .finish()
+}
@@ -0,0 +1,101 @@
+use crate::domain::product::Product;
+use std::sync::Arc;
+
+// 販売元ごとのアフィリエイトリンクの作り方
+pub trait LinkBuilder: Send + Sync {
+    // 対象外のURLはNoneを返す
+    fn build(&self, url: &url::Url) -> Option<url::Url>;
+}
+
+fn host_matches(url: &url::Url, domain: &str) -> bool {
+    url.host_str()
+        .is_some_and(|v| v == domain || v.ends_with(&format!(".{}", domain)))
+}
+
+// 楽天アフィリエイト
+// https://affiliate.rakuten.co.jp/
+#[derive(Debug, Clone)]
+pub struct RakutenAffiliate {
+    affiliate_id: String,
+}
+impl RakutenAffiliate {
+    const LINK_HOST: &'static str = "hb.afl.rakuten.co.jp";
+
+    pub fn new(affiliate_id: String) -> Self {
+        Self { affiliate_id }
+    }
+}
+impl LinkBuilder for RakutenAffiliate {
+    fn build(&self, url: &url::Url) -> Option<url::Url> {
+        if !host_matches(url, "rakuten.co.jp") || url.host_str() == Some(Self::LINK_HOST) {
+            return None;
+        }
+        let mut link = url::Url::parse(&format!(
+            "https://{}/hgc/{}/",
+            Self::LINK_HOST,
+            self.affiliate_id
+        ))
+        .ok()?;
+        // PC用とスマートフォン用の遷移先
+        link.query_pairs_mut()
+            .append_pair("pc", url.as_str())
+            .append_pair("m", url.as_str());
+        Some(link)
+    }
+}
+
+// Amazonアソシエイト。商品URLにトラッキングIDを付ける
+#[derive(Debug, Clone)]
+pub struct AmazonAssociates {
+    tag: String,
+}
+impl AmazonAssociates {
+    const DOMAINS: [&'static str; 2] = ["amazon.co.jp", "amazon.com"];
+
+    pub fn new(tag: String) -> Self {
+        Self { tag }
+    }
+}
+impl LinkBuilder for AmazonAssociates {
+    fn build(&self, url: &url::Url) -> Option<url::Url> {
+        if !Self::DOMAINS.iter().any(|v| host_matches(url, v)) {
+            return None;
+        }
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(k, _)| k != "tag")
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+        let mut link = url.clone();
+        link.query_pairs_mut()
+            .clear()
+            .extend_pairs(pairs)
+            .append_pair("tag", &self.tag);
+        Some(link)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct AffiliateLinks {
+    builders: Vec<Arc<dyn LinkBuilder>>,
+}
+impl AffiliateLinks {
+    pub fn new(builders: Vec<Arc<dyn LinkBuilder>>) -> Self {
+        Self { builders }
+    }
+
+    // APIなど販売元が発行したリンクがあればそれを優先する
+    pub fn url_of(&self, product: &Product) -> Option<url::Url> {
+        product.affiliate_url.clone().or_else(|| {
+            self.builders
+                .iter()
+                .find_map(|v| v.build(&product.detail_url))
+        })
+    }
+
+    // リンクを作れない場合は商品ページに遷移する
+    pub fn outbound_url_of(&self, product: &Product) -> url::Url {
+        self.url_of(product)
+            .unwrap_or_else(|| product.detail_url.clone())
+    }
+}
@@ -0,0 +1,131 @@
+use crate::domain::product::Product;
+use crate::domain::product_event::{EventRecord, ProductEvent};
+use crate::domain::time;
+use crate::infra::aws::ddb;
+use crate::AppResult;
+use async_trait::async_trait;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::sleep;
+
+// 購読者ごとに溜めておける出来事の数。遅れた購読者は古い出来事を読み飛ばす
+const CHANNEL_CAPACITY: usize = 1024;
+// ポーリングで前回の最新の出来事からこの時間だけ遡って読み直す
+const OVERLAP: chrono::Duration = chrono::Duration::seconds(5);
+
+// 商品の保存から購読者へ出来事を届ける。複数のインスタンスで共有する場合はDynamoDBを使う
+#[async_trait]
+pub trait EventBus: Send + Sync {
+    async fn publish(&self, event: ProductEvent) -> AppResult<()>;
+    fn subscribe(&self) -> broadcast::Receiver<ProductEvent>;
+}
+
+// 同じプロセス内だけで届ける。ローカル実行用
+pub struct InMemoryEventBus {
+    sender: broadcast::Sender<ProductEvent>,
+}
+impl Default for InMemoryEventBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender }
+    }
+}
+#[async_trait]
+impl EventBus for InMemoryEventBus {
+    async fn publish(&self, event: ProductEvent) -> AppResult<()> {
+        // 購読者がいない場合のエラーは無視する
+        let _ = self.sender.send(event);
+        Ok(())
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<ProductEvent> {
+        self.sender.subscribe()
+    }
+}
+
+// 出来事をテーブルに追記し、購読するインスタンスがそれぞれポーリングして読み出す
+// クローラーのLambdaからAPIサーバーへ届ける場合もこちらを使う
+pub struct DdbEventBus {
+    repo: ddb::types::product_event::Repository,
+    poll_interval: Duration,
+    sender: broadcast::Sender<ProductEvent>,
+    polling: AtomicBool,
+}
+impl DdbEventBus {
+    pub fn new(repo: ddb::types::product_event::Repository, poll_interval: Duration) -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            repo,
+            poll_interval,
+            sender,
+            polling: AtomicBool::new(false),
+        }
+    }
+
+    // 購読を始めた時点より後の出来事だけを流す
+    // 書き込みの遅れや時計のずれで古い seq の出来事が後から見えることがあるため、
+    // 毎回 OVERLAP だけ遡って読み直し、流した seq は読み直す範囲の分だけ覚えておいて重複を除く
+    fn start_polling(&self) {
+        let repo = self.repo.clone();
+        let sender = self.sender.clone();
+        let poll_interval = self.poll_interval;
+        tokio::spawn(async move {
+            let started = EventRecord::seq_of(time::now(), "");
+            let mut last_at = time::now();
+            let mut sent: BTreeSet<String> = BTreeSet::new();
+            loop {
+                sleep(poll_interval).await;
+                let from = EventRecord::seq_of(last_at - OVERLAP, "");
+                let records = match repo.find_after(&from, None).await {
+                    Ok(v) => v,
+                    Err(err) => {
+                        eprintln!("出来事の読み出しに失敗しました: {}", err);
+                        continue;
+                    }
+                };
+                for record in records {
+                    if record.seq < started || !sent.insert(record.seq.clone()) {
+                        continue;
+                    }
+                    last_at = last_at.max(record.created_at);
+                    let _ = sender.send(record.event);
+                }
+                sent = sent.split_off(&from);
+            }
+        });
+    }
+}
+#[async_trait]
+impl EventBus for DdbEventBus {
+    async fn publish(&self, event: ProductEvent) -> AppResult<()> {
+        self.repo.append(EventRecord::new(event, time::now())).await
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<ProductEvent> {
+        let receiver = self.sender.subscribe();
+        if !self.polling.swap(true, Ordering::SeqCst) {
+            self.start_polling();
+        }
+        receiver
+    }
+}
+
+// 保存前後の商品から出来事を取り出して流す。失敗しても保存は取り消さない
+pub async fn publish_changes(
+    bus: &Arc<dyn EventBus>,
+    current: Option<&Product>,
+    updated: &Product,
+) {
+    for event in ProductEvent::detect(current, updated) {
+        if let Err(err) = bus.publish(event).await {
+            eprintln!(
+                "出来事の送信に失敗しました: 商品ID: {}, {}",
+                updated.id.as_str(),
+                err
+            );
+        }
+    }
+}
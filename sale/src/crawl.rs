@@ -0,0 +1,10 @@
+pub mod charset;
+pub mod failure;
+pub mod fetcher;
+pub mod image_mirror;
+pub mod politeness;
+pub mod rakuten_api;
+pub mod robots;
+
+// robots.txtのUser-agentとの照合にも使う
+pub const USER_AGENT: &str = "sale-crawler/0.1";
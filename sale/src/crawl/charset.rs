@@ -0,0 +1,92 @@
+use encoding_rs::Encoding;
+
+// metaタグを探す範囲。HTML標準では1024バイトだが、楽天の店舗ページは前置きが長いため広めに取る
+const META_SCAN_BYTES: usize = 4096;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, strum_macros::Display)]
+pub enum CharsetSource {
+    Bom,
+    Header,
+    Meta,
+    Detected,
+}
+
+#[derive(Debug, Clone)]
+pub struct Decoded {
+    pub text: String,
+    pub encoding: &'static Encoding,
+    pub source: CharsetSource,
+}
+
+// BOM、Content-Typeヘッダー、metaタグ、バイト列からの推定の順に文字コードを決めてデコードする
+pub fn decode(content_type: Option<&str>, body: &[u8], tld: Option<&str>) -> Decoded {
+    let (encoding, source) = resolve(content_type, body, tld);
+    let (text, _) = encoding.decode_with_bom_removal(body);
+    Decoded {
+        text: text.into_owned(),
+        encoding,
+        source,
+    }
+}
+
+fn resolve(
+    content_type: Option<&str>,
+    body: &[u8],
+    tld: Option<&str>,
+) -> (&'static Encoding, CharsetSource) {
+    if let Some((encoding, _)) = Encoding::for_bom(body) {
+        return (encoding, CharsetSource::Bom);
+    }
+    if let Some(encoding) = content_type.and_then(charset_param).and_then(for_label) {
+        return (encoding, CharsetSource::Header);
+    }
+    if let Some(encoding) = from_meta(&body[..body.len().min(META_SCAN_BYTES)]) {
+        return (encoding, CharsetSource::Meta);
+    }
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(body, true);
+    (
+        detector.guess(tld.map(|v| v.as_bytes()), true),
+        CharsetSource::Detected,
+    )
+}
+
+fn for_label(label: &str) -> Option<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+}
+
+// `text/html; charset="Shift_JIS"` のようなパラメータからcharsetを取り出す
+fn charset_param(v: &str) -> Option<&str> {
+    let lower = v.to_ascii_lowercase();
+    let start = lower.find("charset=")? + "charset=".len();
+    let rest = v[start..].trim_start();
+    let rest = rest.trim_start_matches(['"', '\'']);
+    let end = rest
+        .find(|c: char| c == '"' || c == '\'' || c == ';' || c == '>' || c.is_whitespace())
+        .unwrap_or(rest.len());
+    Some(&rest[..end]).filter(|v| !v.is_empty())
+}
+
+// <meta charset="..."> と <meta http-equiv="Content-Type" content="...; charset=..."> の両方に対応する
+fn from_meta(head: &[u8]) -> Option<&'static Encoding> {
+    // 判定前なのでASCII部分だけを見る
+    let head: String = head
+        .iter()
+        .map(|&b| if b.is_ascii() { b as char } else { ' ' })
+        .collect();
+    let lower = head.to_ascii_lowercase();
+    let mut pos = 0;
+    while let Some(idx) = lower[pos..].find("<meta") {
+        let start = pos + idx;
+        let end = lower[start..]
+            .find('>')
+            .map_or(lower.len(), |v| start + v + 1);
+        if let Some(encoding) = charset_param(&head[start..end]).and_then(for_label) {
+            // HTML標準に従い、metaでUTF-16が指定された場合はUTF-8として扱う
+            return Some(encoding.output_encoding());
+        }
+        pos = end;
+    }
+    None
+}
@@ -0,0 +1,109 @@
+use crate::domain::crawl_failure::{CrawlFailure, Id, Status, Target};
+use crate::domain::{product, time};
+use crate::errors::Kind::{BadRequest, Internal};
+use crate::errors::{AppError, NotFoundToNone};
+use crate::infra::aws::ddb::types::crawl_failure::Repository;
+use crate::infra::aws::lambda::types::crawler_rakuten::{
+    CrawlProductRequest, Request, RequestBody,
+};
+use crate::infra::aws::sns;
+use crate::AppResult;
+
+// 失敗したメッセージを記録する。再実行されたメッセージは元の失敗に試行回数を積み上げる
+// delivery_id は配信ごとのID(SNSのMessageIdなど)で、同じ配信の再試行は1回として数える
+pub async fn record_message(
+    repo: &Repository,
+    req: &Request,
+    delivery_id: Option<String>,
+    err: &AppError,
+) -> AppResult<()> {
+    let body = serde_json::to_string(&req.body).map_err(Internal.from_srcf())?;
+    let id = match req.failure_id.clone() {
+        Some(v) => Id::new(v),
+        None => CrawlFailure::id_of_message(&body),
+    };
+    let payload = serde_json::to_string(&Request {
+        body: req.body.clone(),
+        failure_id: None,
+    })
+    .map_err(Internal.from_srcf())?;
+    record(repo, id, Target::Message, payload, None, delivery_id, err).await
+}
+
+// 商品の詳細クロールの失敗を記録する。1商品だけを再クロールするリクエストをペイロードにする
+pub async fn record_product(
+    repo: &Repository,
+    product_id: &product::Id,
+    err: &AppError,
+) -> AppResult<()> {
+    let payload = serde_json::to_string(&Request {
+        body: RequestBody::CrawlProduct(CrawlProductRequest {
+            product_id: product_id.as_str().to_string(),
+        }),
+        failure_id: None,
+    })
+    .map_err(Internal.from_srcf())?;
+    record(
+        repo,
+        CrawlFailure::id_of_product(product_id),
+        Target::Product,
+        payload,
+        Some(product_id.clone()),
+        None,
+        err,
+    )
+    .await
+}
+
+async fn record(
+    repo: &Repository,
+    id: Id,
+    target: Target,
+    payload: String,
+    product_id: Option<product::Id>,
+    delivery_id: Option<String>,
+    err: &AppError,
+) -> AppResult<()> {
+    let now = time::now();
+    let error = err.to_string();
+    let failure = match repo.get(&id).await.not_found_to_none()? {
+        Some(current) => current.recur(error, delivery_id, now),
+        None => CrawlFailure::new(id, target, payload, product_id, error, delivery_id, now),
+    };
+    repo.put(failure).await
+}
+
+// 成功したら未解決の失敗を解決済みにする
+pub async fn resolve(repo: &Repository, id: &Id) -> AppResult<()> {
+    if let Some(current) = repo.get(id).await.not_found_to_none()? {
+        if current.status != Status::Resolved {
+            repo.put(current.resolve(time::now())).await?;
+        }
+    }
+    Ok(())
+}
+
+// ペイロードをクローラーのSNSトピックに再送する
+pub async fn replay(
+    repo: &Repository,
+    sns: &sns::Adapter,
+    sns_arn: String,
+    failure: CrawlFailure,
+) -> AppResult<CrawlFailure> {
+    if failure.status == Status::Resolved {
+        return Err(BadRequest.with(format!("解決済みです: {}", failure.id.as_str())));
+    }
+    let req: Request = serde_json::from_str(&failure.payload).map_err(Internal.from_srcf())?;
+    sns.publish(
+        Request {
+            failure_id: Some(failure.id.as_str().to_string()),
+            ..req
+        },
+        sns_arn,
+    )
+    .await?;
+
+    let failure = failure.replay(time::now());
+    repo.put(failure.clone()).await?;
+    Ok(failure)
+}
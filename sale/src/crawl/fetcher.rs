@@ -0,0 +1,255 @@
+use crate::crawl::charset::{decode, Decoded};
+use crate::crawl::politeness::Politeness;
+use crate::crawl::USER_AGENT;
+use crate::errors::AppError;
+use crate::errors::Kind::{Forbidden, Internal, NotFound, Unavailable};
+use crate::AppResult;
+use rand::Rng;
+use reqwest::header::{
+    HeaderMap, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, LOCATION,
+};
+use reqwest::{Client, Response, StatusCode};
+use std::time::Duration;
+use tokio::time::sleep;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_ATTEMPTS: u32 = 3;
+const BASE_DELAY_MS: u64 = 500;
+const MAX_DELAY_MS: u64 = 10_000;
+const MAX_REDIRECTS: usize = 10;
+
+// 条件付きGETに使う検証子
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+impl Validators {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let get = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+        Self {
+            etag: get(ETAG),
+            last_modified: get(LAST_MODIFIED),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FetchOptions {
+    // falseの場合はリダイレクトをOutcome::Redirectとして返す
+    pub follow_redirects: bool,
+    // 指定された場合は条件付きGETを行う
+    pub validators: Option<Validators>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Page {
+    // リダイレクト後のURL
+    pub url: url::Url,
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+    pub validators: Validators,
+}
+impl Page {
+    // 文字コードを判定してデコードする。推定にはドメインのTLDをヒントとして使う
+    pub fn decode(&self) -> Decoded {
+        let content_type = self.headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok());
+        let tld = self.url.host_str().and_then(|v| v.rsplit('.').next());
+        let decoded = decode(content_type, &self.body, tld);
+        println!(
+            "文字コード: {} ({}), URL: {}",
+            decoded.encoding.name(),
+            decoded.source,
+            self.url
+        );
+        decoded
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    Fetched(Page),
+    // 条件付きGETで変更がなかった
+    NotModified,
+    // 404/410
+    NotFound(StatusCode),
+    Redirect {
+        status: StatusCode,
+        location: Option<url::Url>,
+    },
+    // robots.txtで禁止されている場合はNone
+    Blocked(Option<StatusCode>),
+    // 再試行しても回復しなかった5xx
+    ServerError(StatusCode),
+    // その他の4xx
+    ClientError(StatusCode),
+}
+impl Outcome {
+    // 取得できた場合以外はエラーにする
+    // 429/503は相手側の制限なのでUnavailableとし、呼び出し元で中断できるようにする
+    pub fn into_page(self, url: &url::Url) -> AppResult<Page> {
+        let err = |kind: crate::errors::Kind, msg: &str| -> AppError {
+            kind.with(format!("{}: {}", msg, url))
+        };
+        match self {
+            Outcome::Fetched(page) => Ok(page),
+            Outcome::NotModified => Err(Internal.with(format!("変更がありません: {}", url))),
+            Outcome::NotFound(status) => Err(err(NotFound, status.as_str())),
+            Outcome::Redirect { status, .. } => Err(err(Internal, status.as_str())),
+            Outcome::Blocked(None) => Err(err(Forbidden, "robots.txtで禁止されています")),
+            Outcome::Blocked(Some(
+                status @ (StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE),
+            )) => Err(err(Unavailable, status.as_str())),
+            Outcome::Blocked(Some(status)) => Err(err(Forbidden, status.as_str())),
+            Outcome::ServerError(status) => Err(err(Internal, status.as_str())),
+            Outcome::ClientError(status) => Err(err(Internal, status.as_str())),
+        }
+    }
+}
+
+// クローラー共通のHTTPクライアント。コネクションを使い回すためプロセスで1つ持つ
+#[derive(Clone)]
+pub struct Fetcher {
+    client: Client,
+    politeness: Politeness,
+}
+impl Fetcher {
+    pub fn new(politeness: Politeness) -> AppResult<Self> {
+        // リダイレクトは1ホップずつrobots.txtとレート制限を確認するため自前で辿る
+        let client = Client::builder()
+            .user_agent(USER_AGENT)
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(Internal.from_srcf())?;
+        Ok(Self { client, politeness })
+    }
+
+    pub async fn fetch(&self, url: &url::Url, opts: FetchOptions) -> AppResult<Outcome> {
+        let mut url = url.clone();
+        for _ in 0..=MAX_REDIRECTS {
+            let response = match self.send(&url, &opts).await? {
+                Ok(v) => v,
+                Err(outcome) => return Ok(outcome),
+            };
+            let status = response.status();
+
+            if status.is_redirection() && status != StatusCode::NOT_MODIFIED {
+                let location = response
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| url.join(v).ok());
+                match location {
+                    Some(location) if opts.follow_redirects => {
+                        url = location;
+                        continue;
+                    }
+                    location => return Ok(Outcome::Redirect { status, location }),
+                }
+            }
+
+            return Ok(match status {
+                StatusCode::NOT_MODIFIED => Outcome::NotModified,
+                StatusCode::NOT_FOUND | StatusCode::GONE => Outcome::NotFound(status),
+                StatusCode::UNAUTHORIZED
+                | StatusCode::FORBIDDEN
+                | StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS => Outcome::Blocked(Some(status)),
+                v if v.is_client_error() => Outcome::ClientError(status),
+                v if v.is_server_error() => Outcome::ServerError(status),
+                _ => {
+                    let headers = response.headers().clone();
+                    let body = response.bytes().await.map_err(Internal.from_srcf())?;
+                    Outcome::Fetched(Page {
+                        url,
+                        status,
+                        validators: Validators::from_headers(&headers),
+                        headers,
+                        body: body.to_vec(),
+                    })
+                }
+            });
+        }
+
+        Err(Internal.with(format!("リダイレクトが多すぎます: {}", url)))
+    }
+
+    // 通信エラーと5xxはバックオフしながら再試行する
+    // 429/503はホストへのリクエストを止めた上でBlockedを返す
+    async fn send(
+        &self,
+        url: &url::Url,
+        opts: &FetchOptions,
+    ) -> AppResult<Result<Response, Outcome>> {
+        let mut attempt = 0;
+        loop {
+            if let Err(err) = self.politeness.before_request(url).await {
+                return match err.kind {
+                    Forbidden => Ok(Err(Outcome::Blocked(None))),
+                    _ => Err(err),
+                };
+            }
+
+            let mut req = self.client.get(url.as_str());
+            if let Some(validators) = &opts.validators {
+                if let Some(etag) = &validators.etag {
+                    req = req.header(IF_NONE_MATCH, etag);
+                }
+                if let Some(last_modified) = &validators.last_modified {
+                    req = req.header(IF_MODIFIED_SINCE, last_modified);
+                }
+            }
+
+            let result = req.send().await;
+            let retryable = match &result {
+                Ok(response) => {
+                    let status = response.status();
+                    if self
+                        .politeness
+                        .after_response(url, status, response.headers())
+                        .await?
+                        .is_some()
+                    {
+                        return Ok(Err(Outcome::Blocked(Some(status))));
+                    }
+                    status.is_server_error()
+                }
+                Err(err) => err.is_timeout() || err.is_connect(),
+            };
+            if !retryable || attempt + 1 >= MAX_ATTEMPTS {
+                return result.map(Ok).map_err(Internal.from_srcf());
+            }
+
+            let delay = backoff(attempt);
+            eprintln!(
+                "リクエストに失敗したため{:?}後に再試行します (attempt {}/{}): {}",
+                delay,
+                attempt + 1,
+                MAX_ATTEMPTS,
+                url
+            );
+            sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+// Full Jitter
+fn backoff(attempt: u32) -> Duration {
+    let ceil = BASE_DELAY_MS
+        .saturating_mul(1 << attempt.min(16))
+        .min(MAX_DELAY_MS);
+    Duration::from_millis(rand::thread_rng().gen_range(0..=ceil))
+}
@@ -0,0 +1,168 @@
+use crate::crawl::fetcher::{FetchOptions, Fetcher};
+use crate::domain::product::{ImageSize, MirroredImage, Product};
+use crate::errors::Kind::{BadRequest, Internal, NotFound, Unavailable};
+use crate::storage::ObjectStorage;
+use crate::AppResult;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use strum::IntoEnumIterator;
+
+const CONTENT_TYPE: &str = "image/webp";
+
+// 商品画像を取得してWebPに変換し、サイズごとに public/products/<id>/ 以下へ保存する
+// 複製済みの元画像は再取得しない
+pub async fn mirror(
+    fetcher: &Fetcher,
+    storage: &dyn ObjectStorage,
+    product: &Product,
+) -> AppResult<Vec<MirroredImage>> {
+    let mut images = vec![];
+    for url in product.image_urls.iter() {
+        if let Some(mirrored) = product
+            .mirrored_images
+            .iter()
+            .find(|v| v.source_url == url.as_str())
+        {
+            images.push(mirrored.clone());
+            continue;
+        }
+
+        match mirror_one(fetcher, storage, product, url).await {
+            Ok(image) => images.push(image),
+            // 相手側の制限は後で再実行できるようエラーにする
+            Err(err) if err.kind == Unavailable => return Err(err),
+            // 壊れた画像があっても他の画像は複製する
+            Err(err) => eprintln!(
+                "画像を複製できません: {:?}, 商品ID: {}, url: {}",
+                err,
+                product.id.as_str(),
+                url
+            ),
+        }
+    }
+    Ok(images)
+}
+
+async fn mirror_one(
+    fetcher: &Fetcher,
+    storage: &dyn ObjectStorage,
+    product: &Product,
+    url: &url::Url,
+) -> AppResult<MirroredImage> {
+    let opts = FetchOptions {
+        follow_redirects: true,
+        validators: None,
+    };
+    let page = fetcher.fetch(url, opts).await?.into_page(url)?;
+    // デコードと縮小はCPUを使うため非同期ランタイムのスレッドを塞がない
+    let (encoded, perceptual_hash) = tokio::task::spawn_blocking(move || encode_all(&page.body))
+        .await
+        .map_err(Internal.from_srcf())??;
+
+    let mut urls = BTreeMap::new();
+    for (size, body) in encoded {
+        let key = key_of(product, url, size);
+        storage.put(&key, body, CONTENT_TYPE).await?;
+        urls.insert(size, storage.public_url(&key));
+    }
+    Ok(MirroredImage {
+        source_url: url.to_string(),
+        urls,
+        perceptual_hash: Some(perceptual_hash),
+    })
+}
+
+// 知覚ハッシュが無い複製済み画像について、保存先にある元のサイズの画像からハッシュを計算する
+// 保存先に無い、またはデコードできない場合はハッシュ無しのままにする
+pub async fn backfill_hashes(
+    storage: &dyn ObjectStorage,
+    product: &Product,
+) -> AppResult<Vec<MirroredImage>> {
+    let mut images = vec![];
+    for mut image in product.mirrored_images.clone() {
+        let key = image
+            .urls
+            .get(&ImageSize::Original)
+            .and_then(|v| storage.key_of(v));
+        if let (None, Some(key)) = (image.perceptual_hash.as_ref(), key) {
+            match hash_stored(storage, &key).await {
+                Ok(hash) => image.perceptual_hash = Some(hash),
+                Err(err) if matches!(err.kind, BadRequest | NotFound) => eprintln!(
+                    "画像のハッシュを計算できません: {:?}, 商品ID: {}, key: {}",
+                    err,
+                    product.id.as_str(),
+                    key
+                ),
+                Err(err) => return Err(err),
+            }
+        }
+        images.push(image);
+    }
+    Ok(images)
+}
+
+async fn hash_stored(storage: &dyn ObjectStorage, key: &str) -> AppResult<String> {
+    let body = storage.get(key).await?;
+    tokio::task::spawn_blocking(move || {
+        let img = image::load_from_memory(&body).map_err(BadRequest.from_srcf())?;
+        Ok(format!("{:016x}", dhash(&img)))
+    })
+    .await
+    .map_err(Internal.from_srcf())?
+}
+
+// 元画像ごとにURLのハッシュでキーを決め、並び順が変わっても同じキーになるようにする
+fn key_of(product: &Product, url: &url::Url, size: ImageSize) -> String {
+    let hash = format!("{:x}", Sha256::digest(url.as_str().as_bytes()));
+    format!(
+        "public/products/{}/{}-{}.webp",
+        product.id.as_str(),
+        &hash[..16],
+        size.to_string().to_lowercase()
+    )
+}
+
+type Encoded = (Vec<(ImageSize, Vec<u8>)>, String);
+
+fn encode_all(body: &[u8]) -> AppResult<Encoded> {
+    let img = image::load_from_memory(body).map_err(BadRequest.from_srcf())?;
+    let encoded = ImageSize::iter()
+        .map(|size| encode(&img, size).map(|v| (size, v)))
+        .collect::<AppResult<Vec<_>>>()?;
+    Ok((encoded, format!("{:016x}", dhash(&img))))
+}
+
+// 9x8のグレースケールに縮小し、隣り合う画素の明暗を64bitにする
+fn dhash(img: &DynamicImage) -> u64 {
+    let gray = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = gray.get_pixel(x, y)[0];
+            let right = gray.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(left < right);
+        }
+    }
+    hash
+}
+
+// 長辺が上限を超える場合のみ縦横比を保って縮小する
+fn encode(img: &DynamicImage, size: ImageSize) -> AppResult<Vec<u8>> {
+    let max = size.max_dimension();
+    let resized = if img.width() > max || img.height() > max {
+        img.thumbnail(max, max)
+    } else {
+        img.clone()
+    };
+    // WebPエンコーダはRGB(A)8bitのみ対応している
+    let resized = DynamicImage::ImageRgba8(resized.to_rgba8());
+
+    let mut buf = Cursor::new(vec![]);
+    resized
+        .write_to(&mut buf, ImageFormat::WebP)
+        .map_err(Internal.from_srcf())?;
+    Ok(buf.into_inner())
+}
@@ -0,0 +1,117 @@
+use crate::crawl::robots::RobotsCache;
+use crate::crawl::USER_AGENT;
+use crate::domain::time;
+use crate::errors::Kind::{BadRequest, Forbidden};
+use crate::rate_limit::RateLimiter;
+use crate::AppResult;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::time::Duration;
+
+// Retry-Afterが無い、または読めない場合の待ち時間
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(60);
+// 極端に長いRetry-Afterでクローラーが止まり続けないようにする
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub requests_per_second: f64,
+    pub burst: f64,
+    // ホストごとの上書き
+    pub host_requests_per_second: HashMap<String, f64>,
+}
+
+// クロール先への配慮。robots.txtの確認とホストごとのリクエスト間隔の制御を行う
+#[derive(Clone)]
+pub struct Politeness {
+    limiter: RateLimiter,
+    robots: RobotsCache,
+    config: Config,
+}
+impl Politeness {
+    pub fn new(limiter: RateLimiter, robots: RobotsCache, config: Config) -> Self {
+        Self {
+            limiter,
+            robots,
+            config,
+        }
+    }
+
+    // robots.txtで許可されていることを確認し、ホストのトークンが取得できるまで待つ
+    // 止められている間など長く待つ必要がある場合はUnavailableを返す
+    pub async fn before_request(&self, url: &url::Url) -> AppResult<()> {
+        let host = host_of(url)?;
+        let robots = self.robots.get(url).await?;
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        if !robots.is_allowed(USER_AGENT, &path) {
+            return Err(Forbidden.with(format!("robots.txtで禁止されています: {}", url)));
+        }
+
+        self.limiter
+            .acquire(&bucket_key(host), self.rate_of(host), self.config.burst)
+            .await
+    }
+
+    // 429/503の場合はRetry-Afterまでホストへのリクエストを止め、停止する時間を返す
+    pub async fn after_response(
+        &self,
+        url: &url::Url,
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) -> AppResult<Option<Duration>> {
+        if status != StatusCode::TOO_MANY_REQUESTS && status != StatusCode::SERVICE_UNAVAILABLE {
+            return Ok(None);
+        }
+
+        let host = host_of(url)?;
+        let wait = headers
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_retry_after)
+            .unwrap_or(DEFAULT_RETRY_AFTER)
+            .min(MAX_RETRY_AFTER);
+        let until = time::now() + chrono::Duration::from_std(wait).unwrap_or_default();
+        self.limiter.block(&bucket_key(host), until).await?;
+        eprintln!(
+            "{}のため{}秒間リクエストを停止します: {}",
+            status,
+            wait.as_secs(),
+            url
+        );
+
+        Ok(Some(wait))
+    }
+
+    fn rate_of(&self, host: &str) -> f64 {
+        self.config
+            .host_requests_per_second
+            .get(host)
+            .copied()
+            .unwrap_or(self.config.requests_per_second)
+    }
+}
+
+fn host_of(url: &url::Url) -> AppResult<&str> {
+    url.host_str()
+        .ok_or(BadRequest.with(format!("ホストがありません: {}", url)))
+}
+
+fn bucket_key(host: &str) -> String {
+    format!("crawl:{}", host)
+}
+
+// 秒数またはHTTP-date
+fn parse_retry_after(v: &str) -> Option<Duration> {
+    let v = v.trim();
+    if let Ok(secs) = v.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(v).ok()?;
+    (at.with_timezone(&chrono::Local) - time::now())
+        .to_std()
+        .ok()
+}
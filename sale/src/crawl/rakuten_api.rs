@@ -0,0 +1,240 @@
+use crate::crawl::USER_AGENT;
+use crate::domain::product::{Id, Product, Source};
+use crate::domain::shop::Shop;
+use crate::domain::time::LocalDateTime;
+use crate::errors::Kind::{BadRequest, Internal, NotFound, Unauthorized, Unavailable};
+use crate::rate_limit::RateLimiter;
+use crate::AppResult;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::time::Duration;
+
+// 楽天市場商品検索API
+// https://webservice.rakuten.co.jp/documentation/ichiba-item-search
+pub const DEFAULT_BASE_URL: &str =
+    "https://app.rakuten.co.jp/services/api/IchibaItem/Search/20220601";
+// アプリIDごとに1秒1リクエストまで
+const REQUESTS_PER_SECOND: f64 = 1.0;
+const RATE_LIMIT_KEY: &str = "rakuten-api";
+// APIで取得できるのは100ページまで
+pub const MAX_PAGE: u32 = 100;
+pub const MAX_HITS: u32 = 30;
+
+#[derive(Debug, Clone)]
+pub struct SearchParams {
+    pub genre_id: String,
+    pub page: u32,
+    pub hits: u32,
+}
+
+// formatVersion=2 のレスポンス
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResponse {
+    pub count: u64,
+    pub page: u32,
+    pub page_count: u32,
+    pub hits: u32,
+    #[serde(rename = "Items")]
+    pub items: Vec<Item>,
+}
+impl SearchResponse {
+    pub fn next_page(&self) -> Option<u32> {
+        Some(self.page + 1).filter(|v| *v <= self.page_count.min(MAX_PAGE))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Item {
+    pub item_name: String,
+    // <店舗コード>:<商品番号>
+    pub item_code: String,
+    pub item_price: u64,
+    pub item_url: url::Url,
+    #[serde(default)]
+    pub affiliate_url: Option<String>,
+    #[serde(default)]
+    pub medium_image_urls: Vec<String>,
+    // 1: 販売可能, 0: 販売不可
+    pub availability: u8,
+    pub review_count: u32,
+    pub review_average: f64,
+    pub point_rate: u32,
+    pub shop_name: String,
+    pub shop_code: String,
+    pub shop_url: url::Url,
+    pub genre_id: String,
+}
+impl Item {
+    // HTMLの一覧と同じく、詳細URLから店舗コードと商品番号を取り出す
+    // 詳細URLが楽天市場の商品ページでない場合は itemCode を使う
+    fn codes(&self) -> (String, String) {
+        Product::codes_of(&self.item_url).unwrap_or_else(|| {
+            let item_code = self
+                .item_code
+                .split_once(':')
+                .map(|(_, v)| v)
+                .unwrap_or(&self.item_code);
+            (self.shop_code.clone(), item_code.to_string())
+        })
+    }
+
+    pub fn item_id(&self) -> String {
+        self.codes().1
+    }
+
+    pub fn product_id(&self) -> Id {
+        let (shop_code, item_code) = self.codes();
+        Product::id_of(Source::Rakuten, &shop_code, &item_code)
+    }
+
+    pub fn shop(&self, now: LocalDateTime) -> Shop {
+        Shop {
+            name: Some(self.shop_name.clone()),
+            ..Shop::new(
+                Source::Rakuten,
+                self.shop_code.clone(),
+                Some(self.shop_url.clone()),
+                now,
+            )
+        }
+    }
+
+    // 画像URLの ?_ex=128x128 を外すと元のサイズになる
+    fn image_urls(&self) -> Vec<url::Url> {
+        self.medium_image_urls
+            .iter()
+            .filter_map(|v| url::Url::parse(v).ok())
+            .map(|mut v| {
+                v.set_query(None);
+                v
+            })
+            .collect()
+    }
+
+    // 登録済みの商品がある場合は、パンくずなど詳細クロールで取る項目を引き継ぐ
+    pub fn into_product(self, current: Option<Product>, now: LocalDateTime) -> Product {
+        let item_id = self.item_id();
+        let current = current.unwrap_or_else(|| {
+            Product::new(
+                self.product_id(),
+                Source::Rakuten,
+                self.item_url.clone(),
+                now,
+            )
+        });
+        let breadcrumb = current.breadcrumb.clone();
+        let retail_price = current.retail_price.clone();
+        let retail_off = current.retail_off.clone();
+        let brand = current.brand.clone();
+        let product = current
+            .with_shop(Shop::id_of(Source::Rakuten, &self.shop_code), item_id)
+            .with_genre(Some(self.genre_id.clone()))
+            .with_affiliate_url(
+                self.affiliate_url
+                    .as_deref()
+                    .filter(|v| !v.is_empty())
+                    .and_then(|v| url::Url::parse(v).ok()),
+            )
+            .update(
+                Some(self.item_name.clone()),
+                self.image_urls(),
+                retail_price,
+                Some(self.item_price.to_string()),
+                retail_off,
+                breadcrumb,
+                Some(format!("ポイント{}倍", self.point_rate)),
+                now,
+            )
+            .describe(
+                brand,
+                Some("JPY".to_string()),
+                Some(self.review_average).filter(|_| self.review_count > 0),
+                Some(self.review_count),
+            );
+        match self.availability {
+            0 => product.sold_out(now),
+            _ => product.activate(now),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct RakutenApi {
+    client: Client,
+    base_url: url::Url,
+    application_id: String,
+    affiliate_id: Option<String>,
+    limiter: RateLimiter,
+}
+impl RakutenApi {
+    pub fn new(
+        base_url: url::Url,
+        application_id: String,
+        affiliate_id: Option<String>,
+        limiter: RateLimiter,
+    ) -> AppResult<Self> {
+        let client = Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(Internal.from_srcf())?;
+        Ok(Self {
+            client,
+            base_url,
+            application_id,
+            affiliate_id,
+            limiter,
+        })
+    }
+
+    pub async fn search(&self, params: &SearchParams) -> AppResult<SearchResponse> {
+        let mut url = self.base_url.clone();
+        url.query_pairs_mut()
+            .append_pair("applicationId", &self.application_id)
+            .append_pair("format", "json")
+            .append_pair("formatVersion", "2")
+            .append_pair("genreId", &params.genre_id)
+            .append_pair("page", &params.page.to_string())
+            .append_pair("hits", &params.hits.min(MAX_HITS).to_string());
+        if let Some(affiliate_id) = self.affiliate_id.as_ref() {
+            url.query_pairs_mut()
+                .append_pair("affiliateId", affiliate_id);
+        }
+
+        self.limiter
+            .acquire(RATE_LIMIT_KEY, REQUESTS_PER_SECOND, 1.0)
+            .await?;
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(Unavailable.from_srcf())?;
+        let status = response.status();
+        let body = response.bytes().await.map_err(Unavailable.from_srcf())?;
+        if status.is_success() {
+            return serde_json::from_slice(&body).map_err(Internal.from_srcf());
+        }
+
+        let msg = serde_json::from_slice::<ErrorResponse>(&body)
+            .map(|v| format!("{}: {}", v.error, v.error_description.unwrap_or_default()))
+            .unwrap_or_else(|_| status.to_string());
+        Err(match status {
+            StatusCode::BAD_REQUEST => BadRequest.with(msg),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Unauthorized.with(msg),
+            StatusCode::NOT_FOUND => NotFound.with(msg),
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+                Unavailable.with(msg)
+            }
+            _ => Internal.with(msg),
+        })
+    }
+}
@@ -0,0 +1,212 @@
+use crate::errors::Kind::Internal;
+use crate::AppResult;
+use reqwest::{Client, StatusCode};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+// https://www.rfc-editor.org/rfc/rfc9309
+const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+// 取得できなかった場合は短い間隔で取り直す
+const FAILURE_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Group {
+    agents: Vec<String>,
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Robots {
+    groups: Vec<Group>,
+    disallow_all: bool,
+}
+impl Robots {
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    pub fn disallow_all() -> Self {
+        Self {
+            groups: vec![],
+            disallow_all: true,
+        }
+    }
+
+    pub fn parse(body: &str) -> Self {
+        let mut groups: Vec<Group> = vec![];
+        let mut current = Group::default();
+        // User-agent行が連続する間は同じグループに属する
+        let mut in_agents = false;
+
+        for line in body.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if !in_agents && !current.agents.is_empty() {
+                        groups.push(std::mem::take(&mut current));
+                    }
+                    current.agents.push(value.to_ascii_lowercase());
+                    in_agents = true;
+                }
+                key @ ("allow" | "disallow") => {
+                    in_agents = false;
+                    // 空のDisallowは全て許可を意味する
+                    if current.agents.is_empty() || value.is_empty() {
+                        continue;
+                    }
+                    current.rules.push(Rule {
+                        allow: key == "allow",
+                        pattern: value.to_string(),
+                    });
+                }
+                _ => {
+                    in_agents = false;
+                }
+            }
+        }
+        if !current.agents.is_empty() {
+            groups.push(current);
+        }
+
+        Self {
+            groups,
+            disallow_all: false,
+        }
+    }
+
+    // 最も長く一致したルールを採用し、同じ長さならAllowを優先する
+    pub fn is_allowed(&self, user_agent: &str, path: &str) -> bool {
+        if self.disallow_all {
+            return false;
+        }
+        let Some(group) = self.find_group(user_agent) else {
+            return true;
+        };
+        group
+            .rules
+            .iter()
+            .filter(|rule| matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .map(|rule| rule.allow)
+            .unwrap_or(true)
+    }
+
+    fn find_group(&self, user_agent: &str) -> Option<&Group> {
+        let product = user_agent
+            .split('/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        self.groups
+            .iter()
+            .filter(|group| {
+                group
+                    .agents
+                    .iter()
+                    .any(|agent| agent != "*" && product.contains(agent.as_str()))
+            })
+            .max_by_key(|group| group.agents.iter().map(|v| v.len()).max())
+            .or_else(|| {
+                self.groups
+                    .iter()
+                    .find(|group| group.agents.iter().any(|agent| agent == "*"))
+            })
+    }
+}
+
+// `*`は任意の文字列、末尾の`$`はパスの終端に一致する
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(v) => (v, true),
+        None => (pattern, false),
+    };
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let Some(rest) = path.strip_prefix(parts[0]) else {
+        return false;
+    };
+    let mut pos = path.len() - rest.len();
+    for (i, part) in parts.iter().enumerate().skip(1) {
+        if anchored && i == parts.len() - 1 {
+            return path.len() >= pos + part.len() && path.ends_with(part);
+        }
+        match path[pos..].find(part) {
+            Some(idx) => pos += idx + part.len(),
+            None => return false,
+        }
+    }
+    !anchored || pos == path.len()
+}
+
+// robots.txtと有効期限
+type CacheEntry = (Arc<Robots>, Instant);
+
+// ホストごとにrobots.txtをキャッシュする
+#[derive(Clone)]
+pub struct RobotsCache {
+    client: Client,
+    cache: Arc<Mutex<HashMap<String, CacheEntry>>>,
+}
+impl RobotsCache {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub async fn get(&self, url: &url::Url) -> AppResult<Arc<Robots>> {
+        let origin = url.origin().ascii_serialization();
+        if let Some((robots, expires_at)) = self.cache.lock().await.get(&origin) {
+            if *expires_at > Instant::now() {
+                return Ok(robots.clone());
+            }
+        }
+
+        let robots = Arc::new(self.fetch(&origin).await?);
+        let ttl = if robots.disallow_all {
+            FAILURE_CACHE_TTL
+        } else {
+            CACHE_TTL
+        };
+        self.cache
+            .lock()
+            .await
+            .insert(origin, (robots.clone(), Instant::now() + ttl));
+        Ok(robots)
+    }
+
+    async fn fetch(&self, origin: &str) -> AppResult<Robots> {
+        let url = format!("{}/robots.txt", origin);
+        println!("robots.txt取得: {}", url);
+        let response = match self.client.get(&url).send().await {
+            Ok(v) => v,
+            // 到達できない場合はクロールしない
+            Err(err) => {
+                eprintln!("robots.txtの取得に失敗しました: {:?}", err);
+                return Ok(Robots::disallow_all());
+            }
+        };
+        let status = response.status();
+        if status.is_success() {
+            let body = response.text().await.map_err(Internal.from_srcf())?;
+            return Ok(Robots::parse(&body));
+        }
+        // 4xxは制限なし、5xxや429は一時的に全て不許可として扱う
+        if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS {
+            return Ok(Robots::allow_all());
+        }
+        Ok(Robots::disallow_all())
+    }
+}
@@ -0,0 +1,49 @@
+use crate::domain::product::{DealWeights, Product};
+use crate::domain::product_stat::Window;
+use crate::domain::time::LocalDateTime;
+use crate::infra::aws::ddb::prelude::BatchGet;
+use crate::infra::aws::ddb::types::product_stat;
+use crate::AppResult;
+
+// お得度の人気に使う集計期間
+const POPULARITY_WINDOW: Window = Window::Week;
+
+// 保存する前の商品にお得度を付ける
+#[derive(Clone)]
+pub struct DealScorer {
+    stat_repo: product_stat::Repository,
+    weights: DealWeights,
+}
+impl DealScorer {
+    pub fn new(stat_repo: product_stat::Repository, weights: DealWeights) -> Self {
+        Self { stat_repo, weights }
+    }
+
+    // 人気は商品ごとの日別の件数をまとめて読み込んで求める
+    pub async fn score(
+        &self,
+        products: Vec<Product>,
+        now: LocalDateTime,
+    ) -> AppResult<Vec<Product>> {
+        let keys: Vec<_> = products
+            .iter()
+            .map(|v| (v.id.clone(), POPULARITY_WINDOW))
+            .collect();
+        let popularity = self.stat_repo.batch_get(&keys).await?;
+        Ok(products
+            .into_iter()
+            .map(|v| {
+                let score = popularity
+                    .get(&(v.id.clone(), POPULARITY_WINDOW))
+                    .map(|v| v.score)
+                    .unwrap_or_default();
+                v.with_deal_score(&self.weights, score, now)
+            })
+            .collect())
+    }
+
+    pub async fn score_one(&self, product: Product, now: LocalDateTime) -> AppResult<Product> {
+        let mut products = self.score(vec![product], now).await?;
+        Ok(products.remove(0))
+    }
+}
@@ -1,13 +1,27 @@
-use crate::infra::aws::{ddb, lambda, sns, ssm};
+use crate::affiliate::{AffiliateLinks, AmazonAssociates, LinkBuilder, RakutenAffiliate};
+use crate::bus::{DdbEventBus, EventBus, InMemoryEventBus};
+use crate::crawl::fetcher::Fetcher;
+use crate::crawl::politeness::{Config, Politeness};
+use crate::crawl::rakuten_api;
+use crate::crawl::rakuten_api::RakutenApi;
+use crate::crawl::robots::RobotsCache;
+use crate::deal::DealScorer;
+use crate::env::{EventBusBackend, RateLimitBackend};
+use crate::infra::aws::{ddb, lambda, s3, sns, ssm};
+use crate::rate_limit::{BucketStore, DdbBucketStore, InMemoryBucketStore, RateLimiter};
+use crate::storage::{LocalStorage, ObjectStorage, S3Storage};
 use crate::sync::LazyAsync;
 use crate::{env, lazy_async};
 use aws_config::BehaviorVersion;
 use once_cell::sync::Lazy;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 static SSM_PARAMETER_NAME: Lazy<String> = Lazy::new(|| {
     std::env::var("SSM_DOTENV_PARAMETER_NAME").expect("SSM_DOTENV_PARAMETER_NAME should set")
 });
-pub static ENVIRONMENTS: Lazy<env::Environments> = Lazy::new(|| env::Environments::new());
+pub static ENVIRONMENTS: Lazy<env::Environments> = Lazy::new(env::Environments::new);
 
 static AWS_CONFIG: LazyAsync<aws_types::SdkConfig> =
     lazy_async!(aws_config::defaults(BehaviorVersion::latest()).load());
@@ -19,6 +33,8 @@ static LAMBDA_CLIENT: LazyAsync<aws_sdk_lambda::Client> = lazy_async!(async {
     let ses_config = aws_config::defaults(BehaviorVersion::latest()).load().await;
     aws_sdk_lambda::Client::new(&ses_config)
 });
+static S3_CLIENT: LazyAsync<aws_sdk_s3::Client> =
+    lazy_async!(async { aws_sdk_s3::Client::new(AWS_CONFIG.get().await) });
 static SNS_CLIENT: LazyAsync<aws_sdk_sns::Client> = lazy_async!(async {
     let ses_config = aws_config::defaults(BehaviorVersion::latest()).load().await;
     aws_sdk_sns::Client::new(&ses_config)
@@ -29,6 +45,12 @@ pub static SSM_ADAPTER: LazyAsync<ssm::Adapter> = lazy_async!(async {
 });
 pub static LAMBDA_ADAPTER: LazyAsync<lambda::Adapter> =
     lazy_async!(async { lambda::Adapter::new(LAMBDA_CLIENT.get().await.clone(),) });
+pub static S3_ADAPTER: LazyAsync<s3::Adapter> = lazy_async!(async {
+    s3::Adapter::new(
+        S3_CLIENT.get().await.clone(),
+        format!("{}-sale-userdata", ENVIRONMENTS.clone().env),
+    )
+});
 pub static SNS_ADAPTER: LazyAsync<sns::Adapter> =
     lazy_async!(async { sns::Adapter::new(SNS_CLIENT.get().await.clone(),) });
 
@@ -42,3 +64,139 @@ static DDB_TABLE_NAME_PROVIDER: Lazy<ddb::TableNameProvider> =
     Lazy::new(|| ddb::TableNameProvider::new(format!("{}-sale-", ENVIRONMENTS.clone().env)));
 pub static DB_PRODUCT_REPOSITORY: LazyAsync<ddb::types::product::Repository> =
     lazy_async!(ddb_repo());
+pub static DB_PRODUCT_GROUP_REPOSITORY: LazyAsync<ddb::types::product_group::Repository> =
+    lazy_async!(ddb_repo());
+pub static DB_CATEGORY_REPOSITORY: LazyAsync<ddb::types::category::Repository> =
+    lazy_async!(ddb_repo());
+pub static DB_PRODUCT_STAT_REPOSITORY: LazyAsync<ddb::types::product_stat::Repository> =
+    lazy_async!(ddb_repo());
+pub static DB_PRODUCT_EVENT_REPOSITORY: LazyAsync<ddb::types::product_event::Repository> =
+    lazy_async!(ddb_repo());
+pub static DB_SHOP_REPOSITORY: LazyAsync<ddb::types::shop::Repository> = lazy_async!(ddb_repo());
+pub static DB_API_KEY_REPOSITORY: LazyAsync<ddb::types::api_key::Repository> =
+    lazy_async!(ddb_repo());
+pub static DB_AUDIT_EVENT_REPOSITORY: LazyAsync<ddb::types::audit_event::Repository> =
+    lazy_async!(ddb_repo());
+pub static DB_MIGRATION_REPOSITORY: LazyAsync<ddb::types::migration::Repository> =
+    lazy_async!(ddb_repo());
+pub static DB_CRAWL_FAILURE_REPOSITORY: LazyAsync<ddb::types::crawl_failure::Repository> =
+    lazy_async!(ddb_repo());
+pub static DB_RATE_LIMIT_REPOSITORY: LazyAsync<ddb::types::rate_limit::Repository> =
+    lazy_async!(ddb_repo());
+
+// Lambdaでは同時実行間で共有するためDynamoDBにバケットを保存する
+static CRAWL_RATE_LIMITER: LazyAsync<RateLimiter> = lazy_async!(async {
+    let store: Arc<dyn BucketStore> = if ENVIRONMENTS.with_lambda {
+        Arc::new(DdbBucketStore::new(
+            DB_RATE_LIMIT_REPOSITORY.get().await.clone(),
+        ))
+    } else {
+        Arc::new(InMemoryBucketStore::default())
+    };
+    RateLimiter::new(store)
+});
+// APIの呼び出し回数の制限。Lambdaでは同時実行間で共有するためDynamoDBにバケットを保存する
+pub static API_RATE_LIMITER: LazyAsync<RateLimiter> = lazy_async!(async {
+    let store: Arc<dyn BucketStore> = match ENVIRONMENTS.api_rate_limit_backend {
+        RateLimitBackend::Memory => Arc::new(InMemoryBucketStore::default()),
+        RateLimitBackend::Dynamodb => Arc::new(DdbBucketStore::new(
+            DB_RATE_LIMIT_REPOSITORY.get().await.clone(),
+        )),
+    };
+    RateLimiter::new(store)
+});
+pub static CRAWL_POLITENESS: LazyAsync<Politeness> = lazy_async!(async {
+    let client = reqwest::Client::builder()
+        .user_agent(crate::crawl::USER_AGENT)
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("failed to build http client");
+    Politeness::new(
+        CRAWL_RATE_LIMITER.get().await.clone(),
+        RobotsCache::new(client),
+        Config {
+            requests_per_second: ENVIRONMENTS.crawler_requests_per_second,
+            burst: ENVIRONMENTS.crawler_burst,
+            host_requests_per_second: ENVIRONMENTS.crawler_host_requests_per_second.clone(),
+        },
+    )
+});
+pub static CRAWL_FETCHER: LazyAsync<Fetcher> = lazy_async!(async {
+    Fetcher::new(CRAWL_POLITENESS.get().await.clone()).expect("failed to build fetcher")
+});
+pub static RAKUTEN_API: LazyAsync<RakutenApi> = lazy_async!(async {
+    let envs = ENVIRONMENTS.clone();
+    RakutenApi::new(
+        envs.rakuten_api_base_url.unwrap_or_else(|| {
+            url::Url::parse(rakuten_api::DEFAULT_BASE_URL).expect("failed to parse api url")
+        }),
+        envs.rakuten_application_id
+            .expect("RAKUTEN_APPLICATION_ID should set"),
+        envs.rakuten_affiliate_id,
+        CRAWL_RATE_LIMITER.get().await.clone(),
+    )
+    .expect("failed to build rakuten api client")
+});
+
+pub static DEAL_SCORER: LazyAsync<DealScorer> = lazy_async!(async {
+    DealScorer::new(
+        DB_PRODUCT_STAT_REPOSITORY.get().await.clone(),
+        ENVIRONMENTS.deal_score_weights.clone(),
+    )
+});
+
+pub static EVENT_BUS: LazyAsync<Arc<dyn EventBus>> = lazy_async!(async {
+    match ENVIRONMENTS.event_bus_backend {
+        EventBusBackend::Memory => Arc::new(InMemoryEventBus::default()) as Arc<dyn EventBus>,
+        EventBusBackend::Dynamodb => Arc::new(DdbEventBus::new(
+            DB_PRODUCT_EVENT_REPOSITORY.get().await.clone(),
+            Duration::from_millis(ENVIRONMENTS.event_bus_poll_interval_ms),
+        )),
+    }
+});
+
+// IDが設定されている販売元だけリンクを作る
+pub static AFFILIATE_LINKS: Lazy<AffiliateLinks> = Lazy::new(|| {
+    let envs = ENVIRONMENTS.clone();
+    let mut builders: Vec<Arc<dyn LinkBuilder>> = vec![];
+    if let Some(id) = envs.rakuten_affiliate_id {
+        builders.push(Arc::new(RakutenAffiliate::new(id)));
+    }
+    if let Some(tag) = envs.amazon_associate_tag {
+        builders.push(Arc::new(AmazonAssociates::new(tag)));
+    }
+    AffiliateLinks::new(builders)
+});
+
+// 公開URLは未指定ならバケットのURLを使う
+pub static IMAGE_STORAGE: LazyAsync<Arc<dyn ObjectStorage>> = lazy_async!(async {
+    let envs = ENVIRONMENTS.clone();
+    if let Some(dir) = envs.image_local_dir {
+        let root =
+            std::path::absolute(PathBuf::from(dir)).expect("failed to resolve IMAGE_LOCAL_DIR");
+        let base_url = envs.image_public_base_url.unwrap_or_else(|| {
+            url::Url::from_directory_path(&root).expect("failed to build IMAGE_LOCAL_DIR url")
+        });
+        return Arc::new(LocalStorage::new(root, base_url)) as Arc<dyn ObjectStorage>;
+    }
+
+    let adapter = S3_ADAPTER.get().await.clone();
+    let base_url = match envs.image_public_base_url {
+        Some(v) => v,
+        None => {
+            let region = AWS_CONFIG
+                .get()
+                .await
+                .region()
+                .map(|v| v.to_string())
+                .unwrap_or("ap-northeast-1".to_string());
+            url::Url::parse(&format!(
+                "https://{}.s3.{}.amazonaws.com",
+                adapter.bucket_name(),
+                region
+            ))
+            .expect("failed to build s3 url")
+        }
+    };
+    Arc::new(S3Storage::new(adapter, base_url))
+});
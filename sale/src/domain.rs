@@ -2,7 +2,17 @@ use rand::random;
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
 
+pub mod api_key;
+pub mod audit_event;
+pub mod category;
+pub mod crawl_failure;
+pub mod migration;
 pub mod product;
+pub mod product_event;
+pub mod product_group;
+pub mod product_stat;
+pub mod rate_limit;
+pub mod shop;
 pub mod time;
 pub mod user;
 
@@ -32,9 +42,9 @@ impl<E> From<String> for Id<E> {
         Self::new(id)
     }
 }
-impl<E> Into<String> for Id<E> {
-    fn into(self) -> String {
-        self.id
+impl<E> From<Id<E>> for String {
+    fn from(id: Id<E>) -> Self {
+        id.id
     }
 }
 impl<E> Hash for Id<E> {
@@ -0,0 +1,145 @@
+use crate::domain;
+use crate::domain::time::LocalDateTime;
+use chrono::Duration;
+use sha2::{Digest, Sha256};
+use strum::IntoEnumIterator;
+
+// トークンの接頭辞。ログなどに紛れ込んだ場合に見つけやすくする
+const TOKEN_PREFIX: &str = "sk";
+// 最終利用日時を更新する間隔。リクエストごとに書き込まないよう間引く
+const TOUCH_INTERVAL_SECONDS: i64 = 60;
+
+// 管理APIのAPIキー。トークンは発行時にだけ返し、ハッシュだけを保存する
+// トークンは sk_{ID}_{シークレット} の形で、IDでキーを引いてからシークレットを照合する
+pub type Id = domain::Id<ApiKey>;
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub id: Id,
+    pub name: String,
+    pub secret_hash: String,
+    pub scopes: Vec<Scope>,
+    pub status: Status,
+    pub created_at: LocalDateTime,
+    pub updated_at: LocalDateTime,
+    pub last_used_at: Option<LocalDateTime>,
+    pub revoked_at: Option<LocalDateTime>,
+}
+impl ApiKey {
+    // 新しいキーとトークンを返す
+    pub fn issue(name: String, scopes: Vec<Scope>, now: LocalDateTime) -> (Self, String) {
+        let id = Id::generate();
+        let secret = domain::generate_id_str();
+        let token = format!("{}_{}_{}", TOKEN_PREFIX, id.as_str(), secret);
+        let key = Self {
+            id,
+            name,
+            secret_hash: hash_of(&secret),
+            scopes: normalize(scopes),
+            status: Status::Active,
+            created_at: now,
+            updated_at: now,
+            last_used_at: None,
+            revoked_at: None,
+        };
+        (key, token)
+    }
+
+    // トークンからIDとシークレットを取り出す
+    pub fn parse_token(token: &str) -> Option<(Id, &str)> {
+        let (id, secret) = token
+            .strip_prefix(TOKEN_PREFIX)?
+            .strip_prefix('_')?
+            .split_once('_')?;
+        if id.is_empty() || secret.is_empty() {
+            return None;
+        }
+        Some((Id::new(id), secret))
+    }
+
+    // 失効したキーは照合しない
+    pub fn verify(&self, secret: &str) -> bool {
+        self.status == Status::Active
+            && constant_time_eq(hash_of(secret).as_bytes(), self.secret_hash.as_bytes())
+    }
+
+    pub fn should_touch(&self, now: LocalDateTime) -> bool {
+        self.last_used_at
+            .map(|v| now - v >= Duration::seconds(TOUCH_INTERVAL_SECONDS))
+            .unwrap_or(true)
+    }
+
+    pub fn with_scopes(self, scopes: Vec<Scope>, now: LocalDateTime) -> Self {
+        Self {
+            scopes: normalize(scopes),
+            updated_at: now,
+            ..self
+        }
+    }
+
+    pub fn revoke(self, now: LocalDateTime) -> Self {
+        Self {
+            status: Status::Revoked,
+            updated_at: now,
+            revoked_at: Some(now),
+            ..self
+        }
+    }
+}
+
+fn hash_of(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+fn normalize(mut scopes: Vec<Scope>) -> Vec<Scope> {
+    scopes.sort_by_key(|v| v.to_string());
+    scopes.dedup();
+    scopes
+}
+
+// 一致するまでの時間から値を推測されないよう、全てのバイトを比べる
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Hash,
+    strum_macros::EnumString,
+    strum_macros::Display,
+    strum_macros::EnumIter,
+)]
+pub enum Scope {
+    // 参照系のクエリ
+    #[strum(serialize = "read")]
+    Read,
+    // クロールの再実行
+    #[strum(serialize = "crawl:trigger")]
+    CrawlTrigger,
+    // マイグレーションの実行
+    #[strum(serialize = "migrate")]
+    Migrate,
+    // クロール対象の編集
+    #[strum(serialize = "targets:write")]
+    TargetsWrite,
+    // APIキーの発行・失効
+    #[strum(serialize = "keys:write")]
+    KeysWrite,
+}
+impl Scope {
+    pub fn all() -> Vec<Self> {
+        Self::iter().collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+pub enum Status {
+    Active,
+    Revoked,
+}
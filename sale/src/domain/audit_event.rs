@@ -0,0 +1,82 @@
+use crate::domain;
+use crate::domain::time::LocalDateTime;
+use serde_json::Value;
+
+// 値を伏せる引数名。大文字小文字を区別せず部分一致で判定する
+const SECRET_NAMES: [&str; 5] = ["token", "secret", "password", "authorization", "credential"];
+const REDACTED: &str = "[REDACTED]";
+
+// 管理APIで実行された更新操作の記録
+pub type Id = domain::Id<AuditEvent>;
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub id: Id,
+    // APIキーの名前。マスタートークンの場合は master
+    pub actor: String,
+    pub api_key_id: Option<domain::api_key::Id>,
+    pub operation_name: Option<String>,
+    // 実行されたミューテーションのフィールド名
+    pub fields: Vec<String>,
+    // フィールドごとの、変数を展開して秘密の値を伏せた引数(JSON)
+    pub arguments: String,
+    pub result: AuditResult,
+    pub errors: Vec<String>,
+    pub duration_ms: u64,
+    pub created_at: LocalDateTime,
+}
+impl AuditEvent {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        actor: String,
+        api_key_id: Option<domain::api_key::Id>,
+        operation_name: Option<String>,
+        fields: Vec<String>,
+        arguments: Value,
+        errors: Vec<String>,
+        duration_ms: u64,
+        now: LocalDateTime,
+    ) -> Self {
+        Self {
+            id: Id::generate(),
+            actor,
+            api_key_id,
+            operation_name,
+            fields,
+            arguments: redact(arguments).to_string(),
+            result: if errors.is_empty() {
+                AuditResult::Succeeded
+            } else {
+                AuditResult::Failed
+            },
+            errors,
+            duration_ms,
+            created_at: now,
+        }
+    }
+}
+
+// 秘密の値らしい名前の項目を入れ子の中まで伏せる
+pub fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| {
+                    let lower = k.to_lowercase();
+                    if SECRET_NAMES.iter().any(|name| lower.contains(name)) {
+                        (k, Value::String(REDACTED.to_string()))
+                    } else {
+                        (k, redact(v))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(redact).collect()),
+        v => v,
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+pub enum AuditResult {
+    Succeeded,
+    Failed,
+}
@@ -0,0 +1,171 @@
+use crate::domain;
+use crate::domain::product::{Product, Status};
+use crate::domain::time::LocalDateTime;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+// パンくずから作るカテゴリの木。販売元ごとの呼び方の違いは正規化してまとめる
+pub type Id = domain::Id<Category>;
+#[derive(Debug, Clone, PartialEq)]
+pub struct Category {
+    pub id: Id,
+    pub name: String,
+    pub parent_id: Option<Id>,
+    // ルートから自身までのカテゴリID
+    pub path: Vec<Id>,
+    // 楽天市場のジャンルID。一覧URLの /mall/-/551177/ から取る
+    pub genre_id: Option<String>,
+    // 子孫のカテゴリを含む販売中の商品数
+    pub product_count: u32,
+    pub created_at: LocalDateTime,
+    pub updated_at: LocalDateTime,
+}
+impl Category {
+    // 同じ名前の並びからは同じIDになるようにする
+    pub fn id_of(names: &[String]) -> Id {
+        let hash = format!("{:x}", Sha256::digest(names.join("\u{1f}").as_bytes()));
+        Id::new(hash[..16].to_string())
+    }
+
+    // 商品の絞り込みで前方一致に使うキー
+    pub fn path_key_of(path: &[Id]) -> String {
+        path.iter().map(|v| format!("{}/", v.as_str())).collect()
+    }
+
+    pub fn path_key(&self) -> String {
+        Self::path_key_of(&self.path)
+    }
+
+    // 内容が同じなら作成・更新日時を引き継ぐ
+    pub fn merge(self, current: Option<&Category>) -> Option<Self> {
+        match current {
+            Some(current)
+                if Self {
+                    created_at: current.created_at,
+                    updated_at: current.updated_at,
+                    ..self.clone()
+                } == *current =>
+            {
+                None
+            }
+            Some(current) => Some(Self {
+                created_at: current.created_at,
+                ..self
+            }),
+            None => Some(self),
+        }
+    }
+}
+
+// 販売元のサイト名やトップページなど、カテゴリではないパンくず
+const ROOT_NAMES: [&str; 9] = [
+    "top",
+    "トップ",
+    "home",
+    "ホーム",
+    "楽天市場",
+    "楽天ファッション",
+    "rakuten fashion",
+    "楽天ブランドアベニュー",
+    "rakuten brand avenue",
+];
+// ブランドアベニューの呼び方を楽天市場のジャンル名に揃える
+const ALIASES: [(&str, &str); 4] = [
+    ("レディース", "レディースファッション"),
+    ("メンズ", "メンズファッション"),
+    ("キッズ", "キッズ・ベビー・マタニティ"),
+    ("シューズ", "靴"),
+];
+
+// 全角英数と空白を半角にし、サイト名を除いて別名を揃える
+pub fn normalize_breadcrumb(breadcrumb: &[String]) -> Vec<String> {
+    breadcrumb
+        .iter()
+        .map(|v| {
+            v.chars()
+                .map(|c| match c {
+                    '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+                    '\u{3000}' => ' ',
+                    _ => c,
+                })
+                .collect::<String>()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|v| !v.is_empty() && !ROOT_NAMES.contains(&v.to_lowercase().as_str()))
+        .map(|v| {
+            ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == v)
+                .map(|(_, name)| name.to_string())
+                .unwrap_or(v)
+        })
+        .collect()
+}
+
+pub struct Tree {
+    pub categories: Vec<Category>,
+    // 商品ごとのルートから末端までのカテゴリID
+    pub product_paths: HashMap<domain::product::Id, Vec<Id>>,
+}
+
+// 全商品のパンくずからカテゴリの木を作る
+// ジャンルIDは、そのジャンルの一覧から見つかった商品に共通する最も深いカテゴリに割り当てる
+pub fn build(products: &[Product], now: LocalDateTime) -> Tree {
+    let mut categories: HashMap<Id, Category> = HashMap::new();
+    let mut product_paths = HashMap::new();
+    let mut genre_paths: HashMap<String, Vec<Id>> = HashMap::new();
+
+    for product in products {
+        let names = normalize_breadcrumb(&product.breadcrumb);
+        if names.is_empty() {
+            continue;
+        }
+
+        let mut path: Vec<Id> = vec![];
+        for (i, name) in names.iter().enumerate() {
+            let id = Category::id_of(&names[..=i]);
+            let parent_id = path.last().cloned();
+            path.push(id.clone());
+            let category = categories.entry(id.clone()).or_insert_with(|| Category {
+                id,
+                name: name.clone(),
+                parent_id,
+                path: path.clone(),
+                genre_id: None,
+                product_count: 0,
+                created_at: now,
+                updated_at: now,
+            });
+            if product.status == Status::Active {
+                category.product_count += 1;
+            }
+        }
+
+        if let Some(genre_id) = product.genre_id.clone() {
+            let common = match genre_paths.remove(&genre_id) {
+                Some(current) => current
+                    .into_iter()
+                    .zip(path.iter())
+                    .take_while(|(a, b)| a == *b)
+                    .map(|(a, _)| a)
+                    .collect(),
+                None => path.clone(),
+            };
+            genre_paths.insert(genre_id, common);
+        }
+        product_paths.insert(product.id.clone(), path);
+    }
+
+    for (genre_id, path) in genre_paths {
+        if let Some(category) = path.last().and_then(|v| categories.get_mut(v)) {
+            category.genre_id = Some(genre_id);
+        }
+    }
+
+    Tree {
+        categories: categories.into_values().collect(),
+        product_paths,
+    }
+}
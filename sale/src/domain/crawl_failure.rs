@@ -0,0 +1,105 @@
+use crate::domain;
+use crate::domain::time::LocalDateTime;
+use sha2::{Digest, Sha256};
+
+// クローラーで失敗したメッセージ・商品。再実行(リプレイ)できるようにペイロードを保持する
+pub type Id = domain::Id<CrawlFailure>;
+#[derive(Debug, Clone)]
+pub struct CrawlFailure {
+    pub id: Id,
+    pub target: Target,
+    // クローラーのリクエスト(JSON)
+    pub payload: String,
+    pub product_id: Option<domain::product::Id>,
+    pub error: String,
+    pub attempts: u32,
+    pub status: Status,
+    pub created_at: LocalDateTime,
+    pub updated_at: LocalDateTime,
+    pub replayed_at: Option<LocalDateTime>,
+    // 最後に失敗した配信のID(SNSのMessageIdなど)。Lambdaの再試行で同じ配信を二重に数えないようにする
+    pub last_delivery_id: Option<String>,
+}
+impl CrawlFailure {
+    // 同じメッセージの失敗は1件にまとめて試行回数を数える
+    pub fn id_of_message(body: &str) -> Id {
+        let hash = format!("{:x}", Sha256::digest(body.as_bytes()));
+        Id::new(format!("message:{}", &hash[..32]))
+    }
+
+    pub fn id_of_product(product_id: &domain::product::Id) -> Id {
+        Id::new(format!("product:{}", product_id.as_str()))
+    }
+
+    pub fn new(
+        id: Id,
+        target: Target,
+        payload: String,
+        product_id: Option<domain::product::Id>,
+        error: String,
+        delivery_id: Option<String>,
+        now: LocalDateTime,
+    ) -> Self {
+        Self {
+            id,
+            target,
+            payload,
+            product_id,
+            error,
+            attempts: 1,
+            status: Status::Pending,
+            created_at: now,
+            updated_at: now,
+            replayed_at: None,
+            last_delivery_id: delivery_id,
+        }
+    }
+
+    // 再び失敗した。同じ配信の再試行であれば試行回数は増やさない
+    pub fn recur(self, error: String, delivery_id: Option<String>, now: LocalDateTime) -> Self {
+        let retried = delivery_id.is_some() && delivery_id == self.last_delivery_id;
+        Self {
+            error,
+            attempts: if retried {
+                self.attempts
+            } else {
+                self.attempts + 1
+            },
+            status: Status::Pending,
+            updated_at: now,
+            last_delivery_id: delivery_id,
+            ..self
+        }
+    }
+
+    pub fn replay(self, now: LocalDateTime) -> Self {
+        Self {
+            status: Status::Replayed,
+            replayed_at: Some(now),
+            updated_at: now,
+            ..self
+        }
+    }
+
+    // 再実行などで成功した
+    pub fn resolve(self, now: LocalDateTime) -> Self {
+        Self {
+            status: Status::Resolved,
+            updated_at: now,
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+pub enum Target {
+    Message,
+    Product,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+pub enum Status {
+    Pending,
+    Replayed,
+    Resolved,
+}
@@ -0,0 +1,125 @@
+use crate::domain;
+use crate::domain::time::LocalDateTime;
+use chrono::Duration;
+
+// 実行中のまま更新が止まった記録は、Lambdaのタイムアウト(15分)を過ぎていれば途中で落ちたとみなす
+const STALE_AFTER_MINUTES: i64 = 20;
+
+pub type Id = domain::Id<Migration>;
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub id: Id,
+    pub name: String,
+    pub version: u32,
+    pub dry_run: bool,
+    pub status: Status,
+    pub cursor: Option<String>,
+    pub chunks: u64,
+    pub processed: u64,
+    pub touched: u64,
+    pub error: Option<String>,
+    pub started_at: LocalDateTime,
+    pub updated_at: LocalDateTime,
+    pub completed_at: Option<LocalDateTime>,
+    // 楽観ロック用。保存するたびに進み、同時に2つの実行が進まないようにする
+    pub revision: u64,
+}
+impl Migration {
+    // 本実行とドライランは別々に記録する
+    pub fn id_of(name: &str, version: u32, dry_run: bool) -> Id {
+        if dry_run {
+            Id::new(format!("{}@v{}:dry-run", name, version))
+        } else {
+            Id::new(format!("{}@v{}", name, version))
+        }
+    }
+
+    pub fn new(name: String, version: u32, dry_run: bool, now: LocalDateTime) -> Self {
+        Self {
+            id: Self::id_of(&name, version, dry_run),
+            name,
+            version,
+            dry_run,
+            status: Status::Running,
+            cursor: None,
+            chunks: 0,
+            processed: 0,
+            touched: 0,
+            error: None,
+            started_at: now,
+            updated_at: now,
+            completed_at: None,
+            revision: 0,
+        }
+    }
+
+    // 失敗・中断したところからカーソルを引き継いで再開する
+    pub fn resume(self, now: LocalDateTime) -> Self {
+        Self {
+            status: Status::Running,
+            error: None,
+            updated_at: now,
+            ..self
+        }
+    }
+
+    pub fn progress(
+        self,
+        next_cursor: Option<String>,
+        processed: u64,
+        touched: u64,
+        now: LocalDateTime,
+    ) -> Self {
+        let completed = next_cursor.is_none();
+        Self {
+            status: if completed {
+                Status::Completed
+            } else {
+                Status::Running
+            },
+            cursor: next_cursor,
+            chunks: self.chunks + 1,
+            processed: self.processed + processed,
+            touched: self.touched + touched,
+            updated_at: now,
+            completed_at: if completed { Some(now) } else { None },
+            ..self
+        }
+    }
+
+    // 時間切れでチャンクの区切りで止める。続きは次の実行が引き継ぐ
+    pub fn pause(self, now: LocalDateTime) -> Self {
+        Self {
+            status: Status::Paused,
+            updated_at: now,
+            ..self
+        }
+    }
+
+    pub fn fail(self, error: String, now: LocalDateTime) -> Self {
+        Self {
+            status: Status::Failed,
+            error: Some(error),
+            updated_at: now,
+            ..self
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.status == Status::Completed
+    }
+
+    // 他の実行が進めている最中かどうか
+    pub fn is_in_progress(&self, now: LocalDateTime) -> bool {
+        self.status == Status::Running
+            && now - self.updated_at < Duration::minutes(STALE_AFTER_MINUTES)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+pub enum Status {
+    Running,
+    Paused,
+    Completed,
+    Failed,
+}
@@ -1,13 +1,23 @@
 use crate::domain;
 use crate::domain::time::LocalDateTime;
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::str::FromStr;
 
 pub type Id = domain::Id<Product>;
 #[derive(Debug, Clone)]
 pub struct Product {
     pub id: Id,
     pub source: Source,
+    pub shop_id: Option<domain::shop::Id>,
+    // 販売元での商品番号(楽天市場の詳細URLの店舗コードの次のパス)
+    pub item_id: Option<String>,
     pub status: Status,
     pub detail_url: url::Url,
+    // 販売元が発行したアフィリエイトURL(楽天市場商品検索APIなど)
+    pub affiliate_url: Option<url::Url>,
     pub title: Option<String>,
     pub image_urls: Vec<url::Url>,
     pub retail_price: Option<String>,
@@ -15,6 +25,34 @@ pub struct Product {
     pub retail_off: Option<String>,
     pub breadcrumb: Vec<String>,
     pub points: Option<String>,
+    // これまでに確認した最も安い値段
+    pub lowest_price: Option<u64>,
+    // お得度。販売中で値段が分かる商品だけに付ける
+    pub deal_score: Option<DealScore>,
+    pub brand: Option<String>,
+    pub currency: Option<String>,
+    pub rating: Option<f64>,
+    pub review_count: Option<u32>,
+    pub failure_count: u32,
+    pub expires_at: Option<LocalDateTime>,
+    // 診断用。最後にクロールしたページの文字コード
+    pub charset: Option<String>,
+    // 変更検知用。抽出した項目のハッシュとHTTPの検証子
+    pub fingerprint: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub last_checked_at: Option<LocalDateTime>,
+    pub next_check_at: Option<LocalDateTime>,
+    // 内容が変わらなかった連続回数。再クロール間隔の決定に使う
+    pub unchanged_count: u32,
+    // 自前のストレージに複製した画像。image_urls の元画像ごとにサイズ別のURLを持つ
+    pub mirrored_images: Vec<MirroredImage>,
+    // 他店舗の同じ商品とのまとまり。まとまりが無い場合はNone
+    pub group_id: Option<domain::product_group::Id>,
+    // 一覧を取得した楽天市場のジャンルID
+    pub genre_id: Option<String>,
+    // パンくずから決めたカテゴリ。ルートから末端の順
+    pub category_ids: Vec<domain::category::Id>,
     pub created_at: LocalDateTime,
     pub updated_at: LocalDateTime,
 }
@@ -23,8 +61,11 @@ impl Product {
         Self {
             id,
             source,
+            shop_id: None,
+            item_id: None,
             status: Status::Prepare,
             detail_url,
+            affiliate_url: None,
             title: None,
             image_urls: vec![],
             retail_price: None,
@@ -32,11 +73,32 @@ impl Product {
             retail_off: None,
             breadcrumb: vec![],
             points: None,
+            lowest_price: None,
+            deal_score: None,
+            brand: None,
+            currency: None,
+            rating: None,
+            review_count: None,
+            failure_count: 0,
+            expires_at: None,
+            charset: None,
+            fingerprint: None,
+            etag: None,
+            last_modified: None,
+            last_checked_at: None,
+            next_check_at: None,
+            unchanged_count: 0,
+            mirrored_images: vec![],
+            group_id: None,
+            genre_id: None,
+            category_ids: vec![],
             created_at: now,
             updated_at: now,
         }
     }
 
+    // 抽出した内容だけを更新する。ステータスなどの状態はそれぞれの遷移で変える
+    #[allow(clippy::too_many_arguments)]
     pub fn update(
         self,
         title: Option<String>,
@@ -48,8 +110,14 @@ impl Product {
         points: Option<String>,
         now: LocalDateTime,
     ) -> Self {
+        let lowest_price = match (
+            actual_price.as_deref().and_then(parse_price),
+            self.lowest_price,
+        ) {
+            (Some(price), Some(lowest)) => Some(price.min(lowest)),
+            (price, lowest) => price.or(lowest),
+        };
         Self {
-            status: Status::Active,
             title,
             image_urls,
             retail_price,
@@ -57,11 +125,486 @@ impl Product {
             retail_off,
             breadcrumb,
             points,
+            lowest_price,
             updated_at: now,
             ..self
         }
     }
+
+    // 構造化データ(JSON-LD/microdata)からのみ取れる項目
+    pub fn describe(
+        self,
+        brand: Option<String>,
+        currency: Option<String>,
+        rating: Option<f64>,
+        review_count: Option<u32>,
+    ) -> Self {
+        Self {
+            brand,
+            currency,
+            rating,
+            review_count,
+            ..self
+        }
+    }
+
+    pub fn with_charset(self, charset: &str) -> Self {
+        Self {
+            charset: Some(charset.to_string()),
+            ..self
+        }
+    }
+
+    pub fn with_shop(self, shop_id: domain::shop::Id, item_id: String) -> Self {
+        Self {
+            shop_id: Some(shop_id),
+            item_id: Some(item_id),
+            ..self
+        }
+    }
+
+    // 商品IDは <販売元>-<店舗コード>:<商品番号>
+    // 店舗コードには - が含まれることがあるため、楽天市場の itemCode と同じく : で区切る
+    pub fn id_of(source: Source, shop_code: &str, item_code: &str) -> Id {
+        Id::new(format!("{}-{}:{}", source, shop_code, item_code))
+    }
+
+    // https://item.rakuten.co.jp/tab11/john013/ の (tab11, john013)
+    // APIモードとHTMLの一覧のどちらでも、詳細URLから同じ商品IDを作る
+    pub fn codes_of(detail_url: &url::Url) -> Option<(String, String)> {
+        let shop_code = domain::shop::Shop::code_of(detail_url)?;
+        let item_code = detail_url
+            .path_segments()?
+            .nth(1)
+            .filter(|v| !v.is_empty())?;
+        Some((shop_code, item_code.to_string()))
+    }
+
+    // <販売元>-<店舗コード>:<商品番号> 形式のIDから店舗コードと商品番号を取り出す
+    pub fn parse_id(&self) -> Option<(String, String)> {
+        let (source, codes) = self.id.as_str().split_once('-')?;
+        if source != self.source.to_string() {
+            return None;
+        }
+        let (shop_code, item_code) = codes.split_once(':')?;
+        Some((shop_code.to_string(), item_code.to_string()))
+    }
+
+    // IDを付け替える。付け替え前のIDの記録は呼び出し側で消す
+    pub fn rekey(self, id: Id) -> Self {
+        Self { id, ..self }
+    }
+
+    pub fn with_affiliate_url(self, affiliate_url: Option<url::Url>) -> Self {
+        Self {
+            affiliate_url: affiliate_url.or(self.affiliate_url),
+            ..self
+        }
+    }
+
+    pub fn with_genre(self, genre_id: Option<String>) -> Self {
+        Self {
+            genre_id: genre_id.or(self.genre_id),
+            ..self
+        }
+    }
+
+    pub fn with_validators(self, etag: Option<String>, last_modified: Option<String>) -> Self {
+        Self {
+            etag,
+            last_modified,
+            ..self
+        }
+    }
+
+    // クロールで抽出する項目のハッシュ。日時や失敗回数など内容以外の項目は含めない
+    pub fn content_hash(&self) -> String {
+        let content = serde_json::json!([
+            self.status.to_string(),
+            self.title,
+            self.image_urls
+                .iter()
+                .map(|v| v.as_str())
+                .collect::<Vec<_>>(),
+            self.retail_price,
+            self.actual_price,
+            self.retail_off,
+            self.breadcrumb,
+            self.points,
+            self.brand,
+            self.currency,
+            self.rating,
+            self.review_count,
+        ]);
+        format!("{:x}", Sha256::digest(content.to_string().as_bytes()))
+    }
+
+    // 準備中の商品と確認予定を過ぎた商品をクロールする
+    pub fn is_due(&self, now: LocalDateTime) -> bool {
+        self.status == Status::Prepare || self.next_check_at.map(|v| v <= now).unwrap_or(true)
+    }
+
+    // 再クロールの結果と比べ、抽出した内容が変わっていなければ確認日時だけを進める
+    // 取得できた時点で失敗の回数は戻す。304の場合は現在の商品がそのまま渡るため、Failedは販売中に戻す
+    pub fn check(self, crawled: Product, policy: &RecrawlPolicy, now: LocalDateTime) -> Checked {
+        let crawled = match crawled.status {
+            Status::Failed => crawled.activate(now),
+            _ => Self {
+                failure_count: 0,
+                ..crawled
+            },
+        };
+        let hash = crawled.content_hash();
+        if self.fingerprint.as_deref() == Some(hash.as_str()) {
+            let unchanged_count = self.unchanged_count + 1;
+            return Checked::Unchanged(Self {
+                status: crawled.status,
+                failure_count: 0,
+                expires_at: crawled.expires_at,
+                etag: crawled.etag,
+                last_modified: crawled.last_modified,
+                last_checked_at: Some(now),
+                next_check_at: Some(policy.next_check_at(unchanged_count, now)),
+                unchanged_count,
+                ..self
+            });
+        }
+
+        Checked::Changed(Self {
+            fingerprint: Some(hash),
+            last_checked_at: Some(now),
+            next_check_at: Some(policy.next_check_at(0, now)),
+            unchanged_count: 0,
+            ..crawled
+        })
+    }
+
+    // 元画像が変わった、またはまだ複製していない画像がある
+    pub fn needs_mirroring(&self) -> bool {
+        self.image_urls.len() != self.mirrored_images.len()
+            || self
+                .image_urls
+                .iter()
+                .zip(self.mirrored_images.iter())
+                .any(|(a, b)| a.as_str() != b.source_url)
+    }
+
+    // 指定サイズの画像URL。複製していない画像は元のURLを返す
+    pub fn images_of(&self, size: ImageSize) -> Vec<String> {
+        self.image_urls
+            .iter()
+            .map(|url| {
+                self.mirrored_images
+                    .iter()
+                    .find(|v| v.source_url == url.as_str())
+                    .and_then(|v| v.urls.get(&size))
+                    .cloned()
+                    .unwrap_or_else(|| url.to_string())
+            })
+            .collect()
+    }
+
+    // 先頭の画像の知覚ハッシュ。複製時に計算する
+    pub fn image_hash(&self) -> Option<String> {
+        let url = self.image_urls.first()?;
+        self.mirrored_images
+            .iter()
+            .find(|v| v.source_url == url.as_str())
+            .and_then(|v| v.perceptual_hash.clone())
+    }
+
+    pub fn price_value(&self) -> Option<u64> {
+        self.actual_price.as_deref().and_then(parse_price)
+    }
+
+    pub fn retail_price_value(&self) -> Option<u64> {
+        self.retail_price.as_deref().and_then(parse_price)
+    }
+
+    // "ポイント10倍" などから倍率を取り出す。1倍は値段の1%
+    pub fn points_rate(&self) -> Option<u32> {
+        let points = self.points.as_deref()?;
+        let (head, _) = points.split_once('倍')?;
+        let digits: String = head
+            .chars()
+            .rev()
+            .take_while(|v| v.is_ascii_digit())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect();
+        digits.parse().ok()
+    }
+
+    // 販売中でない商品や値段が分からない商品はお得度を外し、並び替えの対象にしない
+    pub fn with_deal_score(
+        self,
+        weights: &DealWeights,
+        popularity: u64,
+        now: LocalDateTime,
+    ) -> Self {
+        let deal_score = Some(self.status)
+            .filter(|v| *v == Status::Active)
+            .and_then(|_| DealScore::of(&self, weights, popularity, now));
+        Self { deal_score, ..self }
+    }
+
+    // 販売中であることを確認できた。失敗や販売終了からも戻す
+    pub fn activate(self, now: LocalDateTime) -> Self {
+        Self {
+            status: Status::Active,
+            failure_count: 0,
+            expires_at: None,
+            updated_at: now,
+            ..self
+        }
+    }
+
+    // セールの販売期間が終了した
+    pub fn expire(self, now: LocalDateTime) -> Self {
+        Self {
+            status: Status::Expired,
+            failure_count: 0,
+            expires_at: Some(now + Duration::days(RETENTION_DAYS)),
+            updated_at: now,
+            ..self
+        }
+    }
+
+    // 在庫切れは再入荷がありうるので削除対象にしない
+    pub fn sold_out(self, now: LocalDateTime) -> Self {
+        Self {
+            status: Status::SoldOut,
+            failure_count: 0,
+            expires_at: None,
+            updated_at: now,
+            ..self
+        }
+    }
+
+    // 商品ページ自体がなくなった
+    pub fn remove(self, now: LocalDateTime) -> Self {
+        Self {
+            status: Status::Removed,
+            failure_count: 0,
+            expires_at: Some(now + Duration::days(RETENTION_DAYS)),
+            updated_at: now,
+            ..self
+        }
+    }
+
+    // クロールに連続で失敗した回数が上限に達したらFailedにする
+    pub fn fail(self, now: LocalDateTime) -> Self {
+        let failure_count = self.failure_count + 1;
+        Self {
+            status: if failure_count >= MAX_FAILURE_COUNT {
+                Status::Failed
+            } else {
+                self.status
+            },
+            failure_count,
+            updated_at: now,
+            ..self
+        }
+    }
+}
+
+// 表示用の値段("1,980円" や "1980.0" など)から数値を取り出す
+fn parse_price(price: &str) -> Option<u64> {
+    let integer = price.split('.').next().unwrap_or_default();
+    let digits: String = integer.chars().filter(|v| v.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+// お得度の各要素の重み。合計が1でなくてもよい
+#[derive(Debug, Clone, PartialEq)]
+pub struct DealWeights {
+    pub discount: f64,
+    pub points: f64,
+    pub lowest_price: f64,
+    pub recency: f64,
+    pub popularity: f64,
 }
+impl Default for DealWeights {
+    fn default() -> Self {
+        Self {
+            discount: 0.4,
+            points: 0.1,
+            lowest_price: 0.2,
+            recency: 0.15,
+            popularity: 0.15,
+        }
+    }
+}
+impl FromStr for DealWeights {
+    type Err = String;
+
+    // 例: discount=0.5,points=0.1,lowestPrice=0.2,recency=0.1,popularity=0.1
+    // 指定しなかった要素は既定の重みを使う
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = Self::default();
+        for pair in s.split(',').filter(|v| !v.trim().is_empty()) {
+            let (name, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("invalid weight: {}", pair))?;
+            let value =
+                f64::from_str(value.trim()).map_err(|_| format!("invalid weight: {}", pair))?;
+            match name.trim() {
+                "discount" => weights.discount = value,
+                "points" => weights.points = value,
+                "lowestPrice" => weights.lowest_price = value,
+                "recency" => weights.recency = value,
+                "popularity" => weights.popularity = value,
+                name => return Err(format!("unknown weight: {}", name)),
+            }
+        }
+        Ok(weights)
+    }
+}
+
+// 値引き率、ポイント倍率、最安値との比較、新しさ、人気を0から1にして重み付きで平均し、100倍したもの
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct DealScore(pub f64);
+impl DealScore {
+    // ポイントはこの倍率で最大とする
+    const MAX_POINTS_RATE: f64 = 20.0;
+    // 新しさはこの日数ごとに半分になる
+    const RECENCY_HALF_LIFE_DAYS: f64 = 3.0;
+    // 人気はこのスコアで0.5になる
+    const POPULARITY_PIVOT: f64 = 50.0;
+    // 新しさは時間とともに少しずつ下がるため、再計算ではこれより小さい変化を無視する
+    const SIGNIFICANT_CHANGE: f64 = 0.5;
+
+    // 算出できる・できないが変わった場合は常に変化とみなす
+    pub fn changed_significantly(current: Option<Self>, next: Option<Self>) -> bool {
+        match (current, next) {
+            (Some(current), Some(next)) => (current.0 - next.0).abs() >= Self::SIGNIFICANT_CHANGE,
+            (current, next) => current.is_some() != next.is_some(),
+        }
+    }
+
+    pub fn of(
+        product: &Product,
+        weights: &DealWeights,
+        popularity: u64,
+        now: LocalDateTime,
+    ) -> Option<Self> {
+        let price = product.price_value().filter(|v| *v > 0)? as f64;
+
+        let discount = product
+            .retail_price_value()
+            .map(|retail| retail as f64)
+            .filter(|retail| *retail > price)
+            .map(|retail| (retail - price) / retail)
+            .unwrap_or_default();
+        let points = product
+            .points_rate()
+            .map(|v| (v as f64 / Self::MAX_POINTS_RATE).min(1.0))
+            .unwrap_or_default();
+        // 最安値と同じかそれより安ければ最大
+        let lowest_price = product
+            .lowest_price
+            .map(|lowest| (lowest as f64 / price).min(1.0))
+            .unwrap_or(1.0);
+        let age_days = (now - product.created_at).num_hours().max(0) as f64 / 24.0;
+        let recency = 0.5_f64.powf(age_days / Self::RECENCY_HALF_LIFE_DAYS);
+        let popularity = popularity as f64 / (popularity as f64 + Self::POPULARITY_PIVOT);
+
+        let total = weights.discount
+            + weights.points
+            + weights.lowest_price
+            + weights.recency
+            + weights.popularity;
+        if total <= 0.0 {
+            return None;
+        }
+        let score = (weights.discount * discount
+            + weights.points * points
+            + weights.lowest_price * lowest_price
+            + weights.recency * recency
+            + weights.popularity * popularity)
+            / total;
+        // 保存と比較のため小数第2位までにする
+        Some(Self((score * 10000.0).round() / 100.0))
+    }
+}
+
+pub enum Checked {
+    Changed(Product),
+    Unchanged(Product),
+}
+impl Checked {
+    // 内容は変わっていないが、失敗からの回復でステータスや失敗の回数が変わった
+    pub fn recovered(&self, current: &Product) -> bool {
+        match self {
+            Checked::Changed(_) => false,
+            Checked::Unchanged(v) => v.status != current.status || current.failure_count > 0,
+        }
+    }
+}
+
+// 再クロールの間隔。内容が変わらないたびに間隔を倍にし、変わったら最短に戻す
+#[derive(Debug, Clone)]
+pub struct RecrawlPolicy {
+    pub min_interval: Duration,
+    pub max_interval: Duration,
+}
+impl RecrawlPolicy {
+    pub fn next_check_at(&self, unchanged_count: u32, now: LocalDateTime) -> LocalDateTime {
+        let interval = self
+            .min_interval
+            .checked_mul(1 << unchanged_count.min(16))
+            .unwrap_or(self.max_interval)
+            .min(self.max_interval);
+        now + interval
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MirroredImage {
+    pub source_url: String,
+    pub urls: BTreeMap<ImageSize, String>,
+    // 同じ商品の検出に使うdHash(16進数)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perceptual_hash: Option<String>,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Serialize,
+    Deserialize,
+    strum_macros::EnumString,
+    strum_macros::EnumIter,
+    strum_macros::Display,
+)]
+pub enum ImageSize {
+    Original,
+    Large,
+    Medium,
+    Small,
+}
+impl ImageSize {
+    // 長辺の最大ピクセル数。Originalも極端に大きい画像は縮小する
+    pub fn max_dimension(&self) -> u32 {
+        match self {
+            ImageSize::Original => 1200,
+            ImageSize::Large => 800,
+            ImageSize::Medium => 400,
+            ImageSize::Small => 200,
+        }
+    }
+}
+
+// Expired, Removed の商品はこの日数が経過したらTTLで削除される
+const RETENTION_DAYS: i64 = 30;
+const MAX_FAILURE_COUNT: u32 = 3;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, strum_macros::EnumString, strum_macros::Display)]
 pub enum Source {
@@ -72,4 +615,8 @@ pub enum Source {
 pub enum Status {
     Prepare,
     Active,
+    Expired,
+    SoldOut,
+    Removed,
+    Failed,
 }
@@ -0,0 +1,101 @@
+use crate::domain;
+use crate::domain::product::{Product, Status};
+use crate::domain::time::LocalDateTime;
+use serde::{Deserialize, Serialize};
+
+// 商品の保存から購読者に届ける出来事。絞り込みに使う値は商品を読み込まずに済むよう持たせる
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ProductEvent {
+    // 販売中になりお得度が付いた
+    NewDeal {
+        product_id: String,
+        deal_score: f64,
+        price: Option<u64>,
+        shop_id: Option<String>,
+        category_ids: Vec<String>,
+    },
+    // 前回の保存時より値下がりした
+    PriceDropped {
+        product_id: String,
+        previous_price: u64,
+        price: u64,
+    },
+}
+impl ProductEvent {
+    pub fn product_id(&self) -> domain::product::Id {
+        match self {
+            ProductEvent::NewDeal { product_id, .. } => domain::product::Id::new(product_id),
+            ProductEvent::PriceDropped { product_id, .. } => domain::product::Id::new(product_id),
+        }
+    }
+
+    // 保存前後の商品を比べて出来事を取り出す
+    pub fn detect(current: Option<&Product>, updated: &Product) -> Vec<Self> {
+        let mut events = vec![];
+        if updated.status != Status::Active {
+            return events;
+        }
+
+        let was_deal = current
+            .map(|v| v.status == Status::Active && v.deal_score.is_some())
+            .unwrap_or(false);
+        if let (false, Some(deal_score)) = (was_deal, updated.deal_score) {
+            events.push(ProductEvent::NewDeal {
+                product_id: updated.id.as_str().to_string(),
+                deal_score: deal_score.0,
+                price: updated.price_value(),
+                shop_id: updated.shop_id.as_ref().map(|v| v.as_str().to_string()),
+                category_ids: updated
+                    .category_ids
+                    .iter()
+                    .map(|v| v.as_str().to_string())
+                    .collect(),
+            });
+        }
+
+        if let (Some(previous_price), Some(price)) =
+            (current.and_then(|v| v.price_value()), updated.price_value())
+        {
+            if price < previous_price {
+                events.push(ProductEvent::PriceDropped {
+                    product_id: updated.id.as_str().to_string(),
+                    previous_price,
+                    price,
+                });
+            }
+        }
+        events
+    }
+}
+
+// 複数のインスタンスで共有するために保存した出来事。seq の順に読み出す
+#[derive(Debug, Clone)]
+pub struct EventRecord {
+    pub seq: String,
+    pub event: ProductEvent,
+    pub created_at: LocalDateTime,
+}
+impl EventRecord {
+    // 同時刻の出来事が重ならないよう乱数を付ける
+    pub fn new(event: ProductEvent, now: LocalDateTime) -> Self {
+        Self {
+            seq: Self::seq_of(now, &domain::generate_id_str()),
+            event,
+            created_at: now,
+        }
+    }
+
+    // 文字列の順が時刻の順になるよう桁を揃える
+    pub fn seq_of(at: LocalDateTime, suffix: &str) -> String {
+        format!(
+            "{:020}-{}",
+            at.timestamp_nanos_opt().unwrap_or_default(),
+            suffix
+        )
+    }
+}
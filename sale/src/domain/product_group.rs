@@ -0,0 +1,187 @@
+use crate::domain;
+use crate::domain::product::Product;
+use crate::domain::time::LocalDateTime;
+use std::collections::{HashMap, HashSet};
+
+// 複数の店舗で販売されている同じ商品のまとまり
+pub type Id = domain::Id<ProductGroup>;
+#[derive(Debug, Clone)]
+pub struct ProductGroup {
+    pub id: Id,
+    pub product_ids: Vec<domain::product::Id>,
+    pub created_at: LocalDateTime,
+    pub updated_at: LocalDateTime,
+}
+impl ProductGroup {
+    // 最も小さい商品IDをグループのIDにし、再計算してもIDが変わりにくいようにする
+    pub fn new(mut product_ids: Vec<domain::product::Id>, now: LocalDateTime) -> Self {
+        product_ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        Self {
+            id: Id::new(product_ids.first().map(|v| v.as_str()).unwrap_or_default()),
+            product_ids,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    // 所属する商品が同じなら作成日時を引き継ぐ
+    pub fn merge(self, current: Option<&ProductGroup>) -> Option<Self> {
+        match current {
+            Some(current) if current.product_ids == self.product_ids => None,
+            Some(current) => Some(Self {
+                created_at: current.created_at,
+                ..self
+            }),
+            None => Some(self),
+        }
+    }
+}
+
+// タイトルの類似度だけで同じ商品とみなす閾値
+const TITLE_ONLY_SIMILARITY: f64 = 0.85;
+// 画像が似ている場合のタイトルの類似度の閾値
+const TITLE_WITH_IMAGE_SIMILARITY: f64 = 0.5;
+// 画像の知覚ハッシュ(64bit)のハミング距離の上限
+const MAX_IMAGE_DISTANCE: u32 = 7;
+// 多くの商品に現れるタイトルの断片は候補の絞り込みに使わない
+const MAX_POSTINGS: usize = 200;
+
+// 似ている商品をまとめる。2件以上のまとまりだけを返す
+pub fn cluster(products: &[Product]) -> Vec<Vec<domain::product::Id>> {
+    let titles: Vec<HashSet<String>> = products
+        .iter()
+        .map(|v| bigrams(&normalize_title(v.title.as_deref().unwrap_or_default())))
+        .collect();
+    let hashes: Vec<Option<u64>> = products
+        .iter()
+        .map(|v| {
+            v.image_hash()
+                .and_then(|v| u64::from_str_radix(&v, 16).ok())
+        })
+        .collect();
+
+    let mut sets = DisjointSet::new(products.len());
+    for (a, b) in candidates(&titles, &hashes) {
+        let similarity = dice(&titles[a], &titles[b]);
+        let image_matched = match (hashes[a], hashes[b]) {
+            (Some(x), Some(y)) => (x ^ y).count_ones() <= MAX_IMAGE_DISTANCE,
+            _ => false,
+        };
+        if similarity >= TITLE_ONLY_SIMILARITY
+            || (image_matched && similarity >= TITLE_WITH_IMAGE_SIMILARITY)
+        {
+            sets.union(a, b);
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<domain::product::Id>> = HashMap::new();
+    for (i, product) in products.iter().enumerate() {
+        groups
+            .entry(sets.find(i))
+            .or_default()
+            .push(product.id.clone());
+    }
+    groups.into_values().filter(|v| v.len() >= 2).collect()
+}
+
+// 全組み合わせを比べないよう、タイトルの断片か画像ハッシュの一部が一致する組だけを候補にする
+// 画像ハッシュは8bitずつ8つに分けるため、距離が7以下なら必ずどこかが一致する
+fn candidates(titles: &[HashSet<String>], hashes: &[Option<u64>]) -> HashSet<(usize, usize)> {
+    let mut postings: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, grams) in titles.iter().enumerate() {
+        for gram in grams {
+            postings.entry(gram.clone()).or_default().push(i);
+        }
+    }
+    for (i, hash) in hashes.iter().enumerate() {
+        if let Some(hash) = hash {
+            for band in 0..8 {
+                let key = format!("#{}:{:02x}", band, (hash >> (band * 8)) & 0xff);
+                postings.entry(key).or_default().push(i);
+            }
+        }
+    }
+
+    let mut pairs = HashSet::new();
+    for indexes in postings.values() {
+        if indexes.len() > MAX_POSTINGS {
+            continue;
+        }
+        for (n, a) in indexes.iter().enumerate() {
+            for b in indexes[n + 1..].iter() {
+                pairs.insert((*a.min(b), *a.max(b)));
+            }
+        }
+    }
+    pairs
+}
+
+// 全角英数を半角にし、【送料無料】などの括弧書きと記号を取り除く
+pub fn normalize_title(title: &str) -> String {
+    let mut normalized = String::new();
+    let mut depth = 0;
+    for c in title.chars() {
+        let c = match c {
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            '\u{3000}' => ' ',
+            _ => c,
+        };
+        match c {
+            '【' | '[' | '［' | '〔' => depth += 1,
+            '】' | ']' | '］' | '〕' => depth = (depth - 1).max(0),
+            _ if depth > 0 => {}
+            _ if c.is_alphanumeric() => normalized.extend(c.to_lowercase()),
+            _ => {
+                if !normalized.ends_with(' ') {
+                    normalized.push(' ')
+                }
+            }
+        }
+    }
+    normalized.trim().to_string()
+}
+
+fn bigrams(title: &str) -> HashSet<String> {
+    let chars: Vec<char> = title.chars().filter(|v| !v.is_whitespace()).collect();
+    chars.windows(2).map(|v| v.iter().collect()).collect()
+}
+
+// Dice係数
+fn dice(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    2.0 * a.intersection(b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+struct DisjointSet {
+    parents: Vec<usize>,
+}
+impl DisjointSet {
+    fn new(size: usize) -> Self {
+        Self {
+            parents: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        let mut i = i;
+        while self.parents[i] != root {
+            let next = self.parents[i];
+            self.parents[i] = root;
+            i = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parents[b] = a;
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use crate::domain::product;
+use crate::domain::time::{Date, LocalDateTime};
+use chrono::Duration;
+use std::collections::HashMap;
+
+// 商品ごとの日別の閲覧・クリック・お気に入りの件数。件数は加算だけで更新する
+#[derive(Debug, Clone)]
+pub struct ProductStat {
+    pub product_id: product::Id,
+    pub date: Date,
+    pub views: u64,
+    pub clicks: u64,
+    pub favorites: u64,
+    // 重み付きの合計。日別のランキングに使う
+    pub score: u64,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+pub enum EventKind {
+    View,
+    Click,
+    Favorite,
+}
+impl EventKind {
+    // DynamoDBの件数の属性名
+    pub fn counter_name(&self) -> &'static str {
+        match self {
+            EventKind::View => "views",
+            EventKind::Click => "clicks",
+            EventKind::Favorite => "favorites",
+        }
+    }
+
+    // 閲覧よりも外部サイトへの遷移やお気に入りを重く見る
+    pub fn weight(&self) -> u64 {
+        match self {
+            EventKind::View => 1,
+            EventKind::Click => 5,
+            EventKind::Favorite => 10,
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Hash, strum_macros::EnumString, strum_macros::Display,
+)]
+pub enum Window {
+    Day,
+    Week,
+    Month,
+}
+impl Window {
+    pub fn days(&self) -> i64 {
+        match self {
+            Window::Day => 1,
+            Window::Week => 7,
+            Window::Month => 30,
+        }
+    }
+
+    // 当日を含む集計対象の日付。新しい順
+    pub fn dates(&self, now: LocalDateTime) -> Vec<Date> {
+        let today = now.date_naive();
+        (0..self.days())
+            .map(|v| today - Duration::days(v))
+            .collect()
+    }
+
+    pub fn since(&self, now: LocalDateTime) -> Date {
+        now.date_naive() - Duration::days(self.days() - 1)
+    }
+}
+
+// 期間内の日別の件数を合計したもの
+#[derive(Debug, Clone, Default)]
+pub struct Popularity {
+    pub views: u64,
+    pub clicks: u64,
+    pub favorites: u64,
+    pub score: u64,
+}
+impl Popularity {
+    pub fn accumulate(mut self, stat: &ProductStat) -> Self {
+        self.views += stat.views;
+        self.clicks += stat.clicks;
+        self.favorites += stat.favorites;
+        self.score += stat.score;
+        self
+    }
+}
+
+// 日別の件数を商品ごとに合計し、スコアの高い順に並べる
+pub fn rank(stats: &[ProductStat]) -> Vec<(product::Id, Popularity)> {
+    let mut totals: HashMap<product::Id, Popularity> = HashMap::new();
+    for stat in stats {
+        let total = totals.remove(&stat.product_id).unwrap_or_default();
+        totals.insert(stat.product_id.clone(), total.accumulate(stat));
+    }
+    let mut ranked: Vec<_> = totals.into_iter().collect();
+    ranked.sort_by(|(a_id, a), (b_id, b)| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| a_id.as_str().cmp(b_id.as_str()))
+    });
+    ranked
+}
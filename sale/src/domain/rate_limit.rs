@@ -0,0 +1,60 @@
+use crate::domain;
+use crate::domain::time::LocalDateTime;
+use std::time::Duration;
+
+// トークンバケット
+// rate(個/秒)でトークンが補充され、burstを上限に貯まる。1リクエストにつき1トークン消費する
+pub type Id = domain::Id<Bucket>;
+#[derive(Debug, Clone)]
+pub struct Bucket {
+    pub id: Id,
+    pub tokens: f64,
+    pub refilled_at: LocalDateTime,
+    pub blocked_until: Option<LocalDateTime>,
+    pub version: u64,
+}
+impl Bucket {
+    pub fn new(id: Id, burst: f64, now: LocalDateTime) -> Self {
+        Self {
+            id,
+            tokens: burst,
+            refilled_at: now,
+            blocked_until: None,
+            version: 0,
+        }
+    }
+
+    // トークンを1つ取得する。取得できなければ次に取得できるまでの待ち時間を返す
+    pub fn take(self, rate: f64, burst: f64, now: LocalDateTime) -> Result<Self, Duration> {
+        if let Some(until) = self.blocked_until {
+            if until > now {
+                return Err((until - now).to_std().unwrap_or_default());
+            }
+        }
+
+        // burstが1未満だと取得できる時が来ないため、少なくとも1トークンは貯まるようにする
+        let burst = burst.max(1.0);
+        let elapsed = (now - self.refilled_at).num_milliseconds().max(0) as f64 / 1000.0;
+        let tokens = (self.tokens + elapsed * rate.max(0.0)).min(burst);
+        if tokens < 1.0 {
+            // rateが0以下の場合は補充されないので、待ち時間は上限とする
+            return Err(Duration::try_from_secs_f64((1.0 - tokens) / rate).unwrap_or(Duration::MAX));
+        }
+
+        Ok(Self {
+            tokens: tokens - 1.0,
+            refilled_at: now,
+            blocked_until: None,
+            ..self
+        })
+    }
+
+    // Retry-After などで指定された時刻まで取得を止める
+    pub fn block(self, until: LocalDateTime) -> Self {
+        Self {
+            tokens: 0.0,
+            blocked_until: Some(self.blocked_until.map_or(until, |v| v.max(until))),
+            ..self
+        }
+    }
+}
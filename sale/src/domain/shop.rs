@@ -0,0 +1,51 @@
+use crate::domain;
+use crate::domain::product::Source;
+use crate::domain::time::LocalDateTime;
+
+// 商品を販売している店舗
+pub type Id = domain::Id<Shop>;
+#[derive(Debug, Clone)]
+pub struct Shop {
+    pub id: Id,
+    pub source: Source,
+    // 販売元での店舗コード(楽天市場の詳細URLの先頭のパス、APIの shopCode)
+    pub code: String,
+    // 詳細ページの構造化データ(販売者)から取る
+    pub name: Option<String>,
+    pub url: Option<url::Url>,
+    pub created_at: LocalDateTime,
+    pub updated_at: LocalDateTime,
+}
+impl Shop {
+    pub fn id_of(source: Source, code: &str) -> Id {
+        Id::new(format!("{}-{}", source, code))
+    }
+
+    pub fn new(source: Source, code: String, url: Option<url::Url>, now: LocalDateTime) -> Self {
+        Self {
+            id: Self::id_of(source, &code),
+            source,
+            code,
+            name: None,
+            url,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    // https://item.rakuten.co.jp/tab11/john013/ の店舗は https://www.rakuten.co.jp/tab11/
+    pub fn url_of(detail_url: &url::Url) -> Option<url::Url> {
+        let code = Self::code_of(detail_url)?;
+        url::Url::parse(&format!("https://www.rakuten.co.jp/{}/", code)).ok()
+    }
+
+    // https://item.rakuten.co.jp/tab11/john013/ の店舗コードは tab11
+    // 楽天市場APIの shopCode と同じ値になる
+    pub fn code_of(detail_url: &url::Url) -> Option<String> {
+        if detail_url.host_str() != Some("item.rakuten.co.jp") {
+            return None;
+        }
+        let code = detail_url.path_segments()?.next()?;
+        Some(code.to_string()).filter(|v| !v.is_empty())
+    }
+}
@@ -7,6 +7,14 @@ pub fn now() -> LocalDateTime {
     Local::now()
 }
 
+// その日の0時
+pub fn start_of_day(v: LocalDateTime) -> LocalDateTime {
+    v.date_naive()
+        .and_hms_opt(0, 0, 0)
+        .and_then(|v| Local.from_local_datetime(&v).earliest())
+        .unwrap_or(v)
+}
+
 pub trait ParseFromRfc3339<T> {
     fn parse_from_rfc3339(s: &str) -> Result<T, String>;
 }
@@ -1,7 +1,61 @@
+use crate::domain::product::DealWeights;
+use std::collections::HashMap;
 use std::str::FromStr;
 
+// 楽天市場の一覧の取得方法
+#[derive(Debug, Clone, Copy, Eq, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum RakutenCrawlerMode {
+    // 検索結果のHTMLをスクレイピングする
+    Html,
+    // 楽天市場商品検索APIを使う
+    Api,
+}
+
+// 商品の出来事の届け方
+#[derive(Debug, Clone, Copy, Eq, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum EventBusBackend {
+    // 同じプロセス内だけで届ける
+    Memory,
+    // テーブルを介して複数のインスタンスに届ける
+    Dynamodb,
+}
+
+// APIの呼び出し回数を数えるバケットの保存先
+#[derive(Debug, Clone, Copy, Eq, PartialEq, strum_macros::EnumString, strum_macros::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum RateLimitBackend {
+    // プロセス内だけで数える
+    Memory,
+    // テーブルを介して複数のインスタンスで共有する
+    Dynamodb,
+}
+
 fn must_env(k: &str) -> String {
-    std::env::var(k).expect(format!("env {} missing", k).as_str())
+    std::env::var(k).unwrap_or_else(|_| panic!("env {} missing", k))
+}
+
+// トークンバケットの補充量(個/秒)。0以下だと補充されず待ち続けるため受け付けない
+fn rate_env(k: &str, default: f64) -> f64 {
+    let v = std::env::var(k)
+        .map(|v| f64::from_str(&v).unwrap_or_else(|_| panic!("failed to parse {}", k)))
+        .unwrap_or(default);
+    if !(v.is_finite() && v > 0.0) {
+        panic!("env {} must be positive: {}", k, v);
+    }
+    v
+}
+
+// トークンバケットの上限。1未満だと1リクエストも通らないため受け付けない
+fn burst_env(k: &str, default: f64) -> f64 {
+    let v = std::env::var(k)
+        .map(|v| f64::from_str(&v).unwrap_or_else(|_| panic!("failed to parse {}", k)))
+        .unwrap_or(default);
+    if !(v.is_finite() && v >= 1.0) {
+        panic!("env {} must be at least 1: {}", k, v);
+    }
+    v
 }
 
 #[derive(Debug, Clone)]
@@ -12,18 +66,154 @@ pub struct Environments {
     pub master_api_token: String,
     pub crawler_rakuten_lambda_arn: String,
     pub crawler_rakuten_sns_arn: String,
+    pub migration_sns_arn: Option<String>,
+    pub crawler_requests_per_second: f64,
+    pub crawler_burst: f64,
+    pub crawler_host_requests_per_second: HashMap<String, f64>,
+    pub crawler_detail_page_size: i32,
+    pub crawler_detail_concurrency: usize,
+    pub recrawl_min_interval_hours: i64,
+    pub recrawl_max_interval_hours: i64,
+    pub rakuten_crawler_mode: RakutenCrawlerMode,
+    pub rakuten_application_id: Option<String>,
+    pub rakuten_affiliate_id: Option<String>,
+    pub rakuten_api_base_url: Option<url::Url>,
+    pub amazon_associate_tag: Option<String>,
+    pub deal_score_weights: DealWeights,
+    pub event_bus_backend: EventBusBackend,
+    pub event_bus_poll_interval_ms: u64,
+    pub affiliate_redirect_enabled: bool,
+    pub api_rate_limit_backend: RateLimitBackend,
+    pub api_ip_requests_per_second: f64,
+    pub api_ip_burst: f64,
+    pub api_user_requests_per_second: f64,
+    pub api_user_burst: f64,
+    pub api_auth_failures_per_second: f64,
+    pub api_auth_failure_burst: f64,
+    pub graphql_max_depth: usize,
+    pub graphql_max_complexity: usize,
+    pub graphql_persisted_queries_only: bool,
+    pub graphql_persisted_queries_path: Option<String>,
+    pub image_local_dir: Option<String>,
+    pub image_public_base_url: Option<url::Url>,
 }
 impl Environments {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
+        let with_lambda = std::env::var("WITH_LAMBDA")
+            .map(|v| bool::from_str(&v).expect("failed to parse WITH_LAMBDA"))
+            .unwrap_or(true);
         Environments {
             env: must_env("ENV"),
             port: std::env::var("PORT").unwrap_or("4000".to_string()),
             master_api_token: must_env("MASTER_API_TOKEN"),
-            with_lambda: std::env::var("WITH_LAMBDA")
-                .map(|v| bool::from_str(&v).expect("failed to parse WITH_LAMBDA"))
-                .unwrap_or(true),
+            with_lambda,
             crawler_rakuten_lambda_arn: must_env("CRAWLER_RAKUTEN_LAMBDA_ARN"),
             crawler_rakuten_sns_arn: must_env("CRAWLER_RAKUTEN_SNS_ARN"),
+            migration_sns_arn: std::env::var("MIGRATION_SNS_ARN").ok(),
+            crawler_requests_per_second: rate_env("CRAWLER_REQUESTS_PER_SECOND", 1.0),
+            crawler_burst: burst_env("CRAWLER_BURST", 1.0),
+            // 例: item.rakuten.co.jp=0.5,search.rakuten.co.jp=2
+            crawler_host_requests_per_second: std::env::var("CRAWLER_HOST_REQUESTS_PER_SECOND")
+                .map(|v| {
+                    v.split(',')
+                        .filter(|v| !v.trim().is_empty())
+                        .map(|v| {
+                            let (host, rps) = v
+                                .split_once('=')
+                                .expect("failed to parse CRAWLER_HOST_REQUESTS_PER_SECOND");
+                            let rps = f64::from_str(rps.trim())
+                                .expect("failed to parse CRAWLER_HOST_REQUESTS_PER_SECOND");
+                            if !(rps.is_finite() && rps > 0.0) {
+                                panic!(
+                                    "env CRAWLER_HOST_REQUESTS_PER_SECOND must be positive: {}",
+                                    v
+                                );
+                            }
+                            (host.trim().to_string(), rps)
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            crawler_detail_page_size: std::env::var("CRAWLER_DETAIL_PAGE_SIZE")
+                .map(|v| i32::from_str(&v).expect("failed to parse CRAWLER_DETAIL_PAGE_SIZE"))
+                .unwrap_or(50),
+            crawler_detail_concurrency: std::env::var("CRAWLER_DETAIL_CONCURRENCY")
+                .map(|v| usize::from_str(&v).expect("failed to parse CRAWLER_DETAIL_CONCURRENCY"))
+                .unwrap_or(4),
+            recrawl_min_interval_hours: std::env::var("RECRAWL_MIN_INTERVAL_HOURS")
+                .map(|v| i64::from_str(&v).expect("failed to parse RECRAWL_MIN_INTERVAL_HOURS"))
+                .unwrap_or(6),
+            recrawl_max_interval_hours: std::env::var("RECRAWL_MAX_INTERVAL_HOURS")
+                .map(|v| i64::from_str(&v).expect("failed to parse RECRAWL_MAX_INTERVAL_HOURS"))
+                .unwrap_or(7 * 24),
+            rakuten_crawler_mode: std::env::var("RAKUTEN_CRAWLER_MODE")
+                .map(|v| {
+                    RakutenCrawlerMode::from_str(&v).expect("failed to parse RAKUTEN_CRAWLER_MODE")
+                })
+                .unwrap_or(RakutenCrawlerMode::Html),
+            rakuten_application_id: std::env::var("RAKUTEN_APPLICATION_ID").ok(),
+            rakuten_affiliate_id: std::env::var("RAKUTEN_AFFILIATE_ID").ok(),
+            // 開発時にモックサーバーへ向ける場合に指定する
+            rakuten_api_base_url: std::env::var("RAKUTEN_API_BASE_URL")
+                .map(|v| url::Url::parse(&v).expect("failed to parse RAKUTEN_API_BASE_URL"))
+                .ok(),
+            amazon_associate_tag: std::env::var("AMAZON_ASSOCIATE_TAG").ok(),
+            // 例: discount=0.5,points=0.1,lowestPrice=0.2,recency=0.1,popularity=0.1
+            deal_score_weights: std::env::var("DEAL_SCORE_WEIGHTS")
+                .map(|v| DealWeights::from_str(&v).expect("failed to parse DEAL_SCORE_WEIGHTS"))
+                .unwrap_or_default(),
+            // Lambdaではクローラーと購読側が別のプロセスになるため、既定でDynamoDBを使う
+            event_bus_backend: std::env::var("EVENT_BUS_BACKEND")
+                .map(|v| EventBusBackend::from_str(&v).expect("failed to parse EVENT_BUS_BACKEND"))
+                .unwrap_or(if with_lambda {
+                    EventBusBackend::Dynamodb
+                } else {
+                    EventBusBackend::Memory
+                }),
+            event_bus_poll_interval_ms: std::env::var("EVENT_BUS_POLL_INTERVAL_MS")
+                .map(|v| u64::from_str(&v).expect("failed to parse EVENT_BUS_POLL_INTERVAL_MS"))
+                .unwrap_or(2000),
+            // 有効にすると /go/{商品ID} でクリックを記録してから商品ページに遷移する
+            affiliate_redirect_enabled: std::env::var("AFFILIATE_REDIRECT_ENABLED")
+                .map(|v| bool::from_str(&v).expect("failed to parse AFFILIATE_REDIRECT_ENABLED"))
+                .unwrap_or(false),
+            // Lambdaでは同時実行間で共有するため、既定でDynamoDBを使う
+            api_rate_limit_backend: std::env::var("API_RATE_LIMIT_BACKEND")
+                .map(|v| {
+                    RateLimitBackend::from_str(&v).expect("failed to parse API_RATE_LIMIT_BACKEND")
+                })
+                .unwrap_or(if with_lambda {
+                    RateLimitBackend::Dynamodb
+                } else {
+                    RateLimitBackend::Memory
+                }),
+            api_ip_requests_per_second: rate_env("API_IP_REQUESTS_PER_SECOND", 5.0),
+            api_ip_burst: burst_env("API_IP_BURST", 30.0),
+            api_user_requests_per_second: rate_env("API_USER_REQUESTS_PER_SECOND", 10.0),
+            api_user_burst: burst_env("API_USER_BURST", 60.0),
+            // 認証の失敗は5回まで続けて許し、以降は100秒に1回だけ試せる
+            api_auth_failures_per_second: rate_env("API_AUTH_FAILURES_PER_SECOND", 0.01),
+            api_auth_failure_burst: burst_env("API_AUTH_FAILURE_BURST", 5.0),
+            graphql_max_depth: std::env::var("GRAPHQL_MAX_DEPTH")
+                .map(|v| usize::from_str(&v).expect("failed to parse GRAPHQL_MAX_DEPTH"))
+                .unwrap_or(10),
+            graphql_max_complexity: std::env::var("GRAPHQL_MAX_COMPLEXITY")
+                .map(|v| usize::from_str(&v).expect("failed to parse GRAPHQL_MAX_COMPLEXITY"))
+                .unwrap_or(1000),
+            // 有効にするとクエリの本文を受け付けず、登録済みのハッシュだけを実行する
+            graphql_persisted_queries_only: std::env::var("GRAPHQL_PERSISTED_QUERIES_ONLY")
+                .map(|v| {
+                    bool::from_str(&v).expect("failed to parse GRAPHQL_PERSISTED_QUERIES_ONLY")
+                })
+                .unwrap_or(false),
+            // ハッシュとクエリの対応を持つJSON。例: {"<sha256>": "query { ... }"}
+            graphql_persisted_queries_path: std::env::var("GRAPHQL_PERSISTED_QUERIES_PATH").ok(),
+            // 指定した場合はS3ではなくローカルのディレクトリに画像を保存する
+            image_local_dir: std::env::var("IMAGE_LOCAL_DIR").ok(),
+            image_public_base_url: std::env::var("IMAGE_PUBLIC_BASE_URL")
+                .map(|v| url::Url::parse(&v).expect("failed to parse IMAGE_PUBLIC_BASE_URL"))
+                .ok(),
         }
     }
 
@@ -7,6 +7,8 @@ pub enum Kind {
     Unauthorized,
     Forbidden,
     NotFound,
+    Conflict,
+    Unavailable,
     Internal,
 }
 impl Kind {
@@ -86,15 +88,3 @@ impl<T> NotFoundToNone<T> for Result<T, AppError> {
         }
     }
 }
-
-macro_rules! impl_from_err_to_app_internal_err {
-    ($T:ty) => {
-        impl From<$T> for crate::errors::AppError {
-            fn from(v: $T) -> Self {
-                crate::errors::Kind::Internal.from_src(v)
-            }
-        }
-    };
-}
-#[allow(unused)]
-pub(crate) use impl_from_err_to_app_internal_err;
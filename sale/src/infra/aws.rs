@@ -1,4 +1,5 @@
 pub mod ddb;
 pub mod lambda;
+pub mod s3;
 pub mod sns;
 pub mod ssm;
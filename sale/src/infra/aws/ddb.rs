@@ -1,18 +1,26 @@
+use crate::errors::Kind::{Internal, Unavailable};
 use crate::infra::aws::ddb::cursor::EntityWithCursor;
-use crate::infra::aws::ddb::types::{FromAttrValue, ToAttrValue};
+use crate::infra::aws::ddb::retry::{backoff, send_with_retry, MAX_ATTEMPTS};
+use crate::infra::aws::ddb::types::ToAttrValue;
+use crate::AppResult;
 use aws_sdk_dynamodb::operation::query::builders::QueryFluentBuilder;
+use aws_sdk_dynamodb::operation::scan::builders::ScanFluentBuilder;
 use aws_sdk_dynamodb::types::{
     AttributeValue, ComparisonOperator, Condition, KeysAndAttributes, Select,
 };
 use derive_more::Into;
 use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
 use std::iter;
 use std::marker::PhantomData;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
 
 pub mod cursor;
 mod errors;
 mod index;
 pub mod prelude;
+mod retry;
 pub mod types;
 
 pub trait HasTableName {
@@ -58,6 +66,46 @@ impl<E: HasTableName> TableRepository<E> {
     }
 }
 
+// エクスポート・インポート用に、テーブル全体をエンティティ単位で読み書きできる型
+pub trait Transferable: HasTableName + Sized + Send + 'static {
+    fn from_item(v: HashMap<String, AttributeValue>) -> Result<Self, String>;
+    fn into_item(self) -> Result<HashMap<String, AttributeValue>, String>;
+}
+impl<E> Transferable for E
+where
+    E: HasTableName + TryFrom<HashMap<String, AttributeValue>, Error = String> + Send + 'static,
+    HashMap<String, AttributeValue>: TryFrom<E>,
+    <HashMap<String, AttributeValue> as TryFrom<E>>::Error: Display,
+{
+    fn from_item(v: HashMap<String, AttributeValue>) -> Result<Self, String> {
+        E::try_from(v)
+    }
+
+    fn into_item(self) -> Result<HashMap<String, AttributeValue>, String> {
+        HashMap::try_from(self).map_err(|e| e.to_string())
+    }
+}
+impl<E: Transferable> TableRepository<E> {
+    pub fn scan_all(&self, total_segments: i32) -> mpsc::Receiver<AppResult<Vec<E>>> {
+        parallel_scan(
+            self.cli.scan().table_name(self.table_name()),
+            total_segments,
+            E::from_item,
+        )
+    }
+
+    // 索引用の属性はエンティティから組み立て直すため、エクスポート時に含まれていなくてもよい
+    pub async fn restore(&self, item: E) -> AppResult<()> {
+        let q = self
+            .cli
+            .put_item()
+            .table_name(self.table_name())
+            .set_item(Some(item.into_item().map_err(Internal.withf())?));
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+}
+
 // https://docs.aws.amazon.com/ja_jp/amazondynamodb/latest/developerguide/Query.Pagination.html
 // データ量によって分割した結果が返ってくるので、limitに達していないがhas_nextがtrueになることがある
 #[allow(unused)]
@@ -65,19 +113,20 @@ async fn query<T>(
     q: QueryFluentBuilder,
     limit: Option<i32>,
     conv: impl Fn(HashMap<String, AttributeValue>) -> Result<T, String>,
-) -> Result<Vec<T>, String> {
+) -> AppResult<Vec<T>> {
     let mut limit = limit;
     let mut q = q.set_limit(limit);
 
     let mut items: Vec<T> = vec![];
     while {
-        let query_res = q.clone().send().await.unwrap();
+        let query_res = send_with_retry(|| q.clone().send()).await?;
         let mut partial_items = query_res
             .items
-            .ok_or_else(|| "result items missing")?
+            .ok_or_else(|| Internal.with("result items missing"))?
             .into_iter()
-            .map(|v| conv(v))
-            .collect::<Result<Vec<_>, _>>()?;
+            .map(&conv)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Internal.withf())?;
 
         let has_next = query_res.last_evaluated_key.is_some();
         limit = limit.map(|limit| limit - partial_items.len() as i32);
@@ -94,13 +143,13 @@ async fn query<T>(
 // https://docs.aws.amazon.com/ja_jp/amazondynamodb/latest/developerguide/Query.Pagination.html
 // データ量によって分割した結果が返ってくるので、whileでhas_nextを見る必要がある
 #[allow(unused)]
-async fn count(q: QueryFluentBuilder) -> Result<usize, String> {
+async fn count(q: QueryFluentBuilder) -> AppResult<usize> {
     let mut q = q.select(Select::Count);
 
     let mut count: usize = 0;
     let mut has_next = true;
     while has_next {
-        let query_res = q.clone().send().await.unwrap();
+        let query_res = send_with_retry(|| q.clone().send()).await?;
         count += query_res.count as usize;
         has_next = query_res.last_evaluated_key.is_some();
         q = q.set_exclusive_start_key(query_res.last_evaluated_key);
@@ -114,32 +163,33 @@ async fn batch_get<T>(
     table_name: impl Into<String>,
     keys: &[HashMap<String, AttributeValue>],
     conv: impl Fn(HashMap<String, AttributeValue>) -> Result<T, String>,
-) -> Result<Vec<T>, String> {
+) -> AppResult<Vec<T>> {
     let table_name = table_name.into();
+    let get_one = |key: HashMap<String, AttributeValue>| {
+        let q = cli.get_item().table_name(&table_name).set_key(Some(key));
+        let conv = &conv;
+        async move {
+            send_with_retry(|| q.clone().send())
+                .await?
+                .item
+                .map(conv)
+                .transpose()
+                .map_err(Internal.withf())
+        }
+    };
 
     match keys.len() {
         0 => return Ok(vec![]),
-        1 => {
-            return cli
-                .get_item()
-                .table_name(table_name)
-                .set_key(Some(keys[0].clone()))
-                .send()
-                .await
-                .map_err(|e| e.to_string())?
-                .item
-                .map(&conv)
-                .into_iter()
-                .collect::<Result<Vec<_>, String>>()
-        }
+        1 => return Ok(get_one(keys[0].clone()).await?.into_iter().collect()),
         _ => (),
     }
 
     const CHUNK_SIZE: usize = 100;
     let mut res = Vec::<T>::with_capacity(keys.len());
-    let mut req_keys = VecDeque::from_iter(keys.into_iter().cloned());
+    let mut req_keys = keys.iter().cloned().collect::<VecDeque<_>>();
     let mut unprocessed_keys = Vec::<HashMap<String, AttributeValue>>::with_capacity(CHUNK_SIZE);
     let mut next_keys = Vec::<HashMap<String, AttributeValue>>::with_capacity(CHUNK_SIZE);
+    let mut unprocessed_attempt: u32 = 0;
 
     while {
         next_keys.truncate(0);
@@ -150,37 +200,22 @@ async fn batch_get<T>(
                 .take_while(Option::is_some)
                 .flatten(),
         );
-        next_keys.len() > 0
+        !next_keys.is_empty()
     } {
         match next_keys.len() {
-            1 => res.extend(
-                cli.get_item()
-                    .table_name(&table_name)
-                    .set_key(Some(next_keys.pop().unwrap()))
-                    .send()
-                    .await
-                    .map_err(|e| e.to_string())?
-                    .item
-                    .map(&conv)
-                    .transpose()?
-                    .into_iter(),
-            ),
+            1 => res.extend(get_one(next_keys.pop().unwrap()).await?),
             _ => {
-                let api_res = cli
-                    .batch_get_item()
-                    .set_request_items(Some(
-                        iter::once((
-                            table_name.clone(),
-                            KeysAndAttributes::builder()
-                                .set_keys(Some(next_keys.to_vec()))
-                                .build()
-                                .unwrap(),
-                        ))
-                        .collect(),
+                let q = cli.batch_get_item().set_request_items(Some(
+                    iter::once((
+                        table_name.clone(),
+                        KeysAndAttributes::builder()
+                            .set_keys(Some(next_keys.to_vec()))
+                            .build()
+                            .map_err(Internal.from_srcf())?,
                     ))
-                    .send()
-                    .await
-                    .map_err(|e| e.to_string())?;
+                    .collect(),
+                ));
+                let api_res = send_with_retry(|| q.clone().send()).await?;
 
                 if let Some(mut responses) = api_res.responses {
                     if let Some(items) = responses.remove(&table_name) {
@@ -188,7 +223,8 @@ async fn batch_get<T>(
                             items
                                 .into_iter()
                                 .map(&conv)
-                                .collect::<Result<Vec<_>, String>>()?
+                                .collect::<Result<Vec<_>, String>>()
+                                .map_err(Internal.withf())?
                                 .as_mut(),
                         )
                     }
@@ -199,6 +235,21 @@ async fn batch_get<T>(
                         unprocessed_keys.append(&mut keys);
                     }
                 }
+
+                // 未処理のキーが返ってきた場合はキャパシティ不足なので、間隔を空けてから再リクエストする
+                // 再試行の回数は send_with_retry と揃え、上限を超えたら呼び出し元に任せる
+                if unprocessed_keys.is_empty() {
+                    unprocessed_attempt = 0;
+                } else if unprocessed_attempt + 1 < MAX_ATTEMPTS {
+                    sleep(backoff(unprocessed_attempt)).await;
+                    unprocessed_attempt += 1;
+                } else {
+                    return Err(Unavailable.with(format!(
+                        "{} keys remain unprocessed after {} attempts",
+                        unprocessed_keys.len() + req_keys.len(),
+                        MAX_ATTEMPTS
+                    )));
+                }
             }
         };
     }
@@ -206,6 +257,53 @@ async fn batch_get<T>(
     Ok(res)
 }
 
+// https://docs.aws.amazon.com/ja_jp/amazondynamodb/latest/developerguide/Scan.html#Scan.ParallelScan
+// セグメントごとにタスクを分けて並列にScanし、取得したページから順に流す
+#[allow(unused)]
+fn parallel_scan<T: Send + 'static>(
+    q: ScanFluentBuilder,
+    total_segments: i32,
+    conv: fn(HashMap<String, AttributeValue>) -> Result<T, String>,
+) -> mpsc::Receiver<AppResult<Vec<T>>> {
+    let total_segments = total_segments.max(1);
+    let (tx, rx) = mpsc::channel(total_segments as usize * 2);
+
+    for segment in 0..total_segments {
+        let tx = tx.clone();
+        let mut q = q.clone().segment(segment).total_segments(total_segments);
+        tokio::spawn(async move {
+            loop {
+                let scan_res = match send_with_retry(|| q.clone().send()).await {
+                    Ok(v) => v,
+                    Err(err) => {
+                        let _ = tx.send(Err(err.into())).await;
+                        return;
+                    }
+                };
+                let items = scan_res
+                    .items
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(conv)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(Internal.withf());
+                let failed = items.is_err();
+
+                // 受信側が閉じられた場合も打ち切る
+                if tx.send(items).await.is_err() || failed {
+                    return;
+                }
+                match scan_res.last_evaluated_key {
+                    Some(key) => q = q.set_exclusive_start_key(Some(key)),
+                    None => return,
+                }
+            }
+        });
+    }
+
+    rx
+}
+
 #[allow(unused)]
 fn anchor_attr_value() -> AttributeValue {
     AttributeValue::S("#".into())
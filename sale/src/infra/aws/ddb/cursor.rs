@@ -82,11 +82,13 @@ impl<E> EntityWithCursor<E> {
         Self { entity, cursor }
     }
 }
+pub type EntityWithCursorConv<T> = Box<
+    dyn Fn(HashMap<String, AttributeValue>) -> Result<EntityWithCursor<T>, String> + Send + Sync,
+>;
 pub fn entity_with_cursor_conv_from<T: 'static>(
     evaluated_key_names: Vec<&'static str>,
     conv: fn(HashMap<String, AttributeValue>) -> Result<T, String>,
-) -> Box<dyn Fn(HashMap<String, AttributeValue>) -> Result<EntityWithCursor<T>, String> + Send + Sync>
-{
+) -> EntityWithCursorConv<T> {
     Box::new(move |attrs: HashMap<String, AttributeValue>| {
         let cursor = {
             let mut attrs = attrs.clone();
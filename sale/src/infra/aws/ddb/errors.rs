@@ -1,18 +1,97 @@
-use crate::errors::impl_from_err_to_app_internal_err;
+use crate::errors::AppError;
+use crate::errors::Kind::{Conflict, Internal, Unavailable};
 use aws_sdk_dynamodb::config::http::HttpResponse;
-use aws_sdk_dynamodb::error::SdkError;
+use aws_sdk_dynamodb::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
 use aws_sdk_dynamodb::operation::batch_get_item::BatchGetItemError;
 use aws_sdk_dynamodb::operation::batch_write_item::BatchWriteItemError;
 use aws_sdk_dynamodb::operation::delete_item::DeleteItemError;
 use aws_sdk_dynamodb::operation::get_item::GetItemError;
 use aws_sdk_dynamodb::operation::put_item::PutItemError;
 use aws_sdk_dynamodb::operation::query::QueryError;
+use aws_sdk_dynamodb::operation::scan::ScanError;
 use aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError;
+use aws_sdk_dynamodb::operation::update_item::UpdateItemError;
 
-impl_from_err_to_app_internal_err!(SdkError<GetItemError, HttpResponse>);
-impl_from_err_to_app_internal_err!(SdkError<PutItemError, HttpResponse>);
-impl_from_err_to_app_internal_err!(SdkError<DeleteItemError, HttpResponse>);
-impl_from_err_to_app_internal_err!(SdkError<QueryError, HttpResponse>);
-impl_from_err_to_app_internal_err!(SdkError<BatchGetItemError, HttpResponse>);
-impl_from_err_to_app_internal_err!(SdkError<BatchWriteItemError, HttpResponse>);
-impl_from_err_to_app_internal_err!(SdkError<TransactWriteItemsError, HttpResponse>);
+#[derive(Debug, Clone, Copy, Eq, PartialEq, derive_more::Display)]
+pub enum ErrorClass {
+    Retryable,
+    ConditionalCheckFailed,
+    Validation,
+    ResourceNotFound,
+    Other,
+}
+
+pub fn classify<E: ProvideErrorMetadata>(err: &SdkError<E, HttpResponse>) -> ErrorClass {
+    match err {
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError(_) => {
+            ErrorClass::Retryable
+        }
+        SdkError::ServiceError(_) => match err.code() {
+            Some(
+                "ProvisionedThroughputExceededException"
+                | "ThrottlingException"
+                | "RequestLimitExceeded"
+                | "TransactionConflictException"
+                | "InternalServerError"
+                | "ServiceUnavailable",
+            ) => ErrorClass::Retryable,
+            Some("ConditionalCheckFailedException") => ErrorClass::ConditionalCheckFailed,
+            // トランザクション内のどれかの条件が満たされなかった場合
+            Some("TransactionCanceledException")
+                if err
+                    .message()
+                    .is_some_and(|v| v.contains("ConditionalCheckFailed")) =>
+            {
+                ErrorClass::ConditionalCheckFailed
+            }
+            Some("ValidationException") => ErrorClass::Validation,
+            Some("ResourceNotFoundException") => ErrorClass::ResourceNotFound,
+            _ => ErrorClass::Other,
+        },
+        _ => ErrorClass::Other,
+    }
+}
+
+fn from_sdk_err<E>(err: SdkError<E, HttpResponse>) -> AppError
+where
+    E: ProvideErrorMetadata + std::error::Error + 'static,
+{
+    let class = classify(&err);
+    let msg = format!("dynamodb {}: {}", class, DisplayErrorContext(&err));
+    match class {
+        ErrorClass::Retryable => Unavailable.with(msg),
+        // 利用者に返すメッセージにはテーブルや条件式の詳細を含めず、ログにだけ残す
+        ErrorClass::ConditionalCheckFailed => {
+            eprintln!("{}", msg);
+            Conflict.with("更新が競合しました")
+        }
+        // リクエストの組み立ての誤りなので利用者の入力の誤りとはしない
+        ErrorClass::Validation => Internal.with(msg),
+        // テーブルが存在しない場合なので、アイテムのNotFoundと区別して内部エラーとする
+        ErrorClass::ResourceNotFound => Internal.with(msg),
+        ErrorClass::Other => Internal.with(msg),
+    }
+}
+
+macro_rules! impl_from_sdk_err_to_app_err {
+    ($($T:ty),*) => {
+        $(
+            impl From<SdkError<$T, HttpResponse>> for AppError {
+                fn from(v: SdkError<$T, HttpResponse>) -> Self {
+                    from_sdk_err(v)
+                }
+            }
+        )*
+    };
+}
+impl_from_sdk_err_to_app_err!(
+    GetItemError,
+    PutItemError,
+    UpdateItemError,
+    DeleteItemError,
+    QueryError,
+    ScanError,
+    BatchGetItemError,
+    BatchWriteItemError,
+    TransactWriteItemsError
+);
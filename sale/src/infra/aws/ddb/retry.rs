@@ -0,0 +1,47 @@
+use crate::infra::aws::ddb::errors::{classify, ErrorClass};
+use aws_sdk_dynamodb::config::http::HttpResponse;
+use aws_sdk_dynamodb::error::{ProvideErrorMetadata, SdkError};
+use rand::Rng;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::sleep;
+
+pub const MAX_ATTEMPTS: u32 = 6;
+const BASE_DELAY_MS: u64 = 50;
+const MAX_DELAY_MS: u64 = 5_000;
+
+// https://aws.amazon.com/jp/blogs/architecture/exponential-backoff-and-jitter/
+// Full Jitter
+pub fn backoff(attempt: u32) -> Duration {
+    let ceil = BASE_DELAY_MS
+        .saturating_mul(1 << attempt.min(16))
+        .min(MAX_DELAY_MS);
+    Duration::from_millis(rand::thread_rng().gen_range(0..=ceil))
+}
+
+// スロットリングなど再試行で解消しうるエラーの場合のみ、バックオフしながら再送する
+pub async fn send_with_retry<T, E, F, Fut>(mut send: F) -> Result<T, SdkError<E, HttpResponse>>
+where
+    E: ProvideErrorMetadata,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, SdkError<E, HttpResponse>>>,
+{
+    let mut attempt = 0;
+    loop {
+        match send().await {
+            Ok(v) => return Ok(v),
+            Err(err) if attempt + 1 < MAX_ATTEMPTS && classify(&err) == ErrorClass::Retryable => {
+                let delay = backoff(attempt);
+                eprintln!(
+                    "dynamodb request failed, retrying in {:?} (attempt {}/{})",
+                    delay,
+                    attempt + 1,
+                    MAX_ATTEMPTS
+                );
+                sleep(delay).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
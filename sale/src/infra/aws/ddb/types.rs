@@ -1,12 +1,25 @@
-use crate::domain::time::LocalDateTime;
+use crate::domain::time::{Date, LocalDateTime};
 use crate::domain::Id;
 use crate::infra::aws::ddb::{anchor_attr_value, HasTypeName};
 use aws_sdk_dynamodb::types::AttributeValue;
 use chrono::{Local, TimeZone};
-use std::collections::HashMap;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
+pub mod api_key;
+pub mod audit_event;
+pub mod category;
+pub mod crawl_failure;
+pub mod migration;
 pub mod product;
+pub mod product_event;
+pub mod product_group;
+pub mod product_stat;
+pub mod rate_limit;
+pub mod shop;
 
 pub trait ToAttrValue {
     fn into_attr(self) -> AttributeValue;
@@ -21,62 +34,387 @@ impl ToAttrValue for &str {
         AttributeValue::S(self.to_string())
     }
 }
-impl ToAttrValue for Vec<String> {
+impl ToAttrValue for bool {
+    fn into_attr(self) -> AttributeValue {
+        AttributeValue::Bool(self)
+    }
+}
+impl<T: ToAttrValue> ToAttrValue for Vec<T> {
     fn into_attr(self) -> AttributeValue {
         AttributeValue::L(self.into_iter().map(|v| v.into_attr()).collect())
     }
 }
+impl<T: ToAttrValue> ToAttrValue for HashMap<String, T> {
+    fn into_attr(self) -> AttributeValue {
+        AttributeValue::M(self.into_iter().map(|(k, v)| (k, v.into_attr())).collect())
+    }
+}
+// DynamoDBは空のSSを保存できないため、空集合はNULLとして扱う
+impl ToAttrValue for HashSet<String> {
+    fn into_attr(self) -> AttributeValue {
+        if self.is_empty() {
+            return AttributeValue::Null(true);
+        }
+        AttributeValue::Ss(self.into_iter().collect())
+    }
+}
+impl<T: ToAttrValue> ToAttrValue for Option<T> {
+    fn into_attr(self) -> AttributeValue {
+        match self {
+            Some(v) => v.into_attr(),
+            None => AttributeValue::Null(true),
+        }
+    }
+}
 impl ToAttrValue for LocalDateTime {
     fn into_attr(self) -> AttributeValue {
         let v = self.timestamp_nanos_opt().unwrap();
         AttributeValue::N(v.to_string())
     }
 }
+impl ToAttrValue for Date {
+    fn into_attr(self) -> AttributeValue {
+        AttributeValue::S(self.format("%Y-%m-%d").to_string())
+    }
+}
+impl ToAttrValue for url::Url {
+    fn into_attr(self) -> AttributeValue {
+        AttributeValue::S(self.to_string())
+    }
+}
+impl<E: HasTypeName> ToAttrValue for Id<E> {
+    fn into_attr(self) -> AttributeValue {
+        self.into()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AttrError {
+    path: Vec<String>,
+    msg: String,
+}
+impl AttrError {
+    pub fn new(msg: impl Into<String>) -> Self {
+        Self {
+            path: vec![],
+            msg: msg.into(),
+        }
+    }
+
+    fn expected(expected: &str, actual: &AttributeValue) -> Self {
+        Self::new(format!(
+            "expected {}, got {}",
+            expected,
+            attr_type_name(actual)
+        ))
+    }
+
+    pub fn at(mut self, name: impl Into<String>) -> Self {
+        self.path.insert(0, name.into());
+        self
+    }
+}
+impl Display for AttrError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.msg)
+        } else {
+            write!(f, "attribute `{}`: {}", self.path.join("."), self.msg)
+        }
+    }
+}
+impl From<AttrError> for String {
+    fn from(v: AttrError) -> Self {
+        v.to_string()
+    }
+}
+
+fn attr_type_name(v: &AttributeValue) -> &'static str {
+    match v {
+        AttributeValue::B(_) => "B",
+        AttributeValue::Bool(_) => "BOOL",
+        AttributeValue::Bs(_) => "BS",
+        AttributeValue::L(_) => "L",
+        AttributeValue::M(_) => "M",
+        AttributeValue::N(_) => "N",
+        AttributeValue::Ns(_) => "NS",
+        AttributeValue::Null(_) => "NULL",
+        AttributeValue::S(_) => "S",
+        AttributeValue::Ss(_) => "SS",
+        _ => "unknown",
+    }
+}
 
-pub trait FromAttrValue {
-    fn to_s(self) -> Result<String, String>;
-    fn to_s_list(self) -> Result<Vec<String>, String>;
-    fn to_date_time(self) -> Result<LocalDateTime, String>;
+pub trait FromAttrValue: Sized {
+    fn from_attr(v: AttributeValue) -> Result<Self, AttrError>;
 }
 impl FromAttrValue for AttributeValue {
-    fn to_s(self) -> Result<String, String> {
-        self.as_s()
-            .map(|v| v.to_string())
-            .map_err(|_| "cannot convert string".to_string())
+    fn from_attr(v: AttributeValue) -> Result<Self, AttrError> {
+        Ok(v)
+    }
+}
+impl FromAttrValue for String {
+    fn from_attr(v: AttributeValue) -> Result<Self, AttrError> {
+        match v {
+            AttributeValue::S(v) => Ok(v),
+            v => Err(AttrError::expected("S", &v)),
+        }
+    }
+}
+impl FromAttrValue for bool {
+    fn from_attr(v: AttributeValue) -> Result<Self, AttrError> {
+        match v {
+            AttributeValue::Bool(v) => Ok(v),
+            v => Err(AttrError::expected("BOOL", &v)),
+        }
+    }
+}
+impl<T: FromAttrValue> FromAttrValue for Vec<T> {
+    fn from_attr(v: AttributeValue) -> Result<Self, AttrError> {
+        match v {
+            AttributeValue::L(v) => v
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| T::from_attr(v).map_err(|e| e.at(i.to_string())))
+                .collect(),
+            v => Err(AttrError::expected("L", &v)),
+        }
+    }
+}
+impl<T: FromAttrValue> FromAttrValue for HashMap<String, T> {
+    fn from_attr(v: AttributeValue) -> Result<Self, AttrError> {
+        match v {
+            AttributeValue::M(v) => v
+                .into_iter()
+                .map(|(k, v)| match T::from_attr(v) {
+                    Ok(v) => Ok((k, v)),
+                    Err(e) => Err(e.at(k)),
+                })
+                .collect(),
+            v => Err(AttrError::expected("M", &v)),
+        }
+    }
+}
+impl FromAttrValue for HashSet<String> {
+    fn from_attr(v: AttributeValue) -> Result<Self, AttrError> {
+        match v {
+            AttributeValue::Ss(v) => Ok(v.into_iter().collect()),
+            AttributeValue::Null(_) => Ok(HashSet::new()),
+            v => Err(AttrError::expected("SS", &v)),
+        }
+    }
+}
+impl<T: FromAttrValue> FromAttrValue for Option<T> {
+    fn from_attr(v: AttributeValue) -> Result<Self, AttrError> {
+        match v {
+            AttributeValue::Null(_) => Ok(None),
+            v => T::from_attr(v).map(Some),
+        }
+    }
+}
+impl FromAttrValue for LocalDateTime {
+    fn from_attr(v: AttributeValue) -> Result<Self, AttrError> {
+        let v = i64::from_attr(v)?;
+        Ok(Local.timestamp_nanos(v))
+    }
+}
+impl FromAttrValue for Date {
+    fn from_attr(v: AttributeValue) -> Result<Self, AttrError> {
+        let v = String::from_attr(v)?;
+        Date::parse_from_str(&v, "%Y-%m-%d")
+            .map_err(|_| AttrError::new(format!("invalid date: {}", v)))
+    }
+}
+impl FromAttrValue for url::Url {
+    fn from_attr(v: AttributeValue) -> Result<Self, AttrError> {
+        let v = String::from_attr(v)?;
+        url::Url::parse(&v).map_err(|_| AttrError::new(format!("invalid url: {}", v)))
     }
+}
+impl<E: HasTypeName> FromAttrValue for Id<E> {
+    fn from_attr(v: AttributeValue) -> Result<Self, AttrError> {
+        Id::try_from(v).map_err(AttrError::new)
+    }
+}
 
-    fn to_s_list(self) -> Result<Vec<String>, String> {
-        let v = self
-            .as_l()
-            .map_err(|_| "cannot convert string list".to_string())?
-            .clone();
-        v.into_iter()
-            .map(|v| v.to_s())
-            .collect::<Result<Vec<String>, String>>()
+macro_rules! impl_attr_value_for_number {
+    ($($T:ty),*) => {
+        $(
+            impl ToAttrValue for $T {
+                fn into_attr(self) -> AttributeValue {
+                    AttributeValue::N(self.to_string())
+                }
+            }
+            impl FromAttrValue for $T {
+                fn from_attr(v: AttributeValue) -> Result<Self, AttrError> {
+                    match v {
+                        AttributeValue::N(v) => <$T>::from_str(&v).map_err(|_| {
+                            AttrError::new(format!(
+                                "invalid number for {}: {}",
+                                stringify!($T),
+                                v
+                            ))
+                        }),
+                        v => Err(AttrError::expected("N", &v)),
+                    }
+                }
+            }
+        )*
+    };
+}
+impl_attr_value_for_number!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+
+// strum_macros::{EnumString, Display} を実装したenumを S として扱う
+macro_rules! impl_attr_value_for_enum {
+    ($($T:ty),*) => {
+        $(
+            impl crate::infra::aws::ddb::types::ToAttrValue for $T {
+                fn into_attr(self) -> aws_sdk_dynamodb::types::AttributeValue {
+                    aws_sdk_dynamodb::types::AttributeValue::S(self.to_string())
+                }
+            }
+            impl crate::infra::aws::ddb::types::FromAttrValue for $T {
+                fn from_attr(
+                    v: aws_sdk_dynamodb::types::AttributeValue,
+                ) -> Result<Self, crate::infra::aws::ddb::types::AttrError> {
+                    let v = <String as crate::infra::aws::ddb::types::FromAttrValue>::from_attr(v)?;
+                    <$T as std::str::FromStr>::from_str(&v).map_err(|_| {
+                        crate::infra::aws::ddb::types::AttrError::new(format!(
+                            "invalid {}: {}",
+                            stringify!($T),
+                            v
+                        ))
+                    })
+                }
+            }
+        )*
+    };
+}
+pub(crate) use impl_attr_value_for_enum;
+
+// serdeで表現できる構造体を M / L などに変換して入れ子で保存する
+#[derive(Debug, Clone)]
+pub struct Nested<T>(pub T);
+// Serialize は失敗しうるため、ToAttrValue ではなく Result を返す
+impl<T: Serialize> Nested<T> {
+    pub fn try_into_attr(self) -> Result<AttributeValue, AttrError> {
+        serde_json::to_value(&self.0)
+            .map(json_to_attr)
+            .map_err(|e| AttrError::new(e.to_string()))
+    }
+}
+impl<T: DeserializeOwned> FromAttrValue for Nested<T> {
+    fn from_attr(v: AttributeValue) -> Result<Self, AttrError> {
+        let v = attr_to_json(v)?;
+        serde_json::from_value(v)
+            .map(Nested)
+            .map_err(|e| AttrError::new(e.to_string()))
+    }
+}
+
+pub fn json_to_attr(v: serde_json::Value) -> AttributeValue {
+    match v {
+        serde_json::Value::Null => AttributeValue::Null(true),
+        serde_json::Value::Bool(v) => AttributeValue::Bool(v),
+        serde_json::Value::Number(v) => AttributeValue::N(v.to_string()),
+        serde_json::Value::String(v) => AttributeValue::S(v),
+        serde_json::Value::Array(v) => AttributeValue::L(v.into_iter().map(json_to_attr).collect()),
+        serde_json::Value::Object(v) => {
+            AttributeValue::M(v.into_iter().map(|(k, v)| (k, json_to_attr(v))).collect())
+        }
+    }
+}
+
+pub fn attr_to_json(v: AttributeValue) -> Result<serde_json::Value, AttrError> {
+    fn number(v: String) -> Result<serde_json::Value, AttrError> {
+        serde_json::from_str::<serde_json::Number>(&v)
+            .map(serde_json::Value::Number)
+            .map_err(|_| AttrError::new(format!("invalid number: {}", v)))
     }
 
-    fn to_date_time(self) -> Result<LocalDateTime, String> {
+    Ok(match v {
+        AttributeValue::Null(_) => serde_json::Value::Null,
+        AttributeValue::Bool(v) => serde_json::Value::Bool(v),
+        AttributeValue::N(v) => number(v)?,
+        AttributeValue::S(v) => serde_json::Value::String(v),
+        AttributeValue::Ss(v) => {
+            serde_json::Value::Array(v.into_iter().map(serde_json::Value::String).collect())
+        }
+        AttributeValue::Ns(v) => {
+            serde_json::Value::Array(v.into_iter().map(number).collect::<Result<Vec<_>, _>>()?)
+        }
+        AttributeValue::L(v) => serde_json::Value::Array(
+            v.into_iter()
+                .enumerate()
+                .map(|(i, v)| attr_to_json(v).map_err(|e| e.at(i.to_string())))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        AttributeValue::M(v) => serde_json::Value::Object(
+            v.into_iter()
+                .map(|(k, v)| match attr_to_json(v) {
+                    Ok(v) => Ok((k, v)),
+                    Err(e) => Err(e.at(k)),
+                })
+                .collect::<Result<serde_json::Map<_, _>, _>>()?,
+        ),
+        v => return Err(AttrError::expected("json compatible value", &v)),
+    })
+}
+
+// エクスポート・インポート用に、アイテムをプレーンなJSONオブジェクトと相互変換する
+pub fn item_to_json(
+    item: HashMap<String, AttributeValue>,
+) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+    item.into_iter()
+        .map(|(k, v)| match attr_to_json(v) {
+            Ok(v) => Ok((k, v)),
+            Err(e) => Err(e.at(k).to_string()),
+        })
+        .collect()
+}
+
+pub fn item_from_json(
+    item: serde_json::Map<String, serde_json::Value>,
+) -> HashMap<String, AttributeValue> {
+    item.into_iter()
+        .map(|(k, v)| (k, json_to_attr(v)))
+        .collect()
+}
+
+// アイテムから属性を取り出す。エラーには属性名が含まれる
+pub trait AttrMap {
+    fn take<T: FromAttrValue>(&mut self, name: &str) -> Result<T, String>;
+    fn take_opt<T: FromAttrValue>(&mut self, name: &str) -> Result<Option<T>, String>;
+}
+impl AttrMap for HashMap<String, AttributeValue> {
+    fn take<T: FromAttrValue>(&mut self, name: &str) -> Result<T, String> {
         let v = self
-            .as_n()
-            .map_err(|_| "cannot convert timestamp".to_string())?
-            .clone();
-        Ok(Local.timestamp_nanos(i64::from_str(&v).map_err(|_| "invalid timestamp")?))
+            .remove(name)
+            .ok_or_else(|| AttrError::new("missing value").at(name))?;
+        Ok(T::from_attr(v).map_err(|e| e.at(name))?)
+    }
+
+    fn take_opt<T: FromAttrValue>(&mut self, name: &str) -> Result<Option<T>, String> {
+        match self.remove(name) {
+            None => Ok(None),
+            Some(v) => Ok(Option::<T>::from_attr(v).map_err(|e| e.at(name))?),
+        }
     }
 }
 
-impl<E: HasTypeName> Into<AttributeValue> for Id<E> {
-    fn into(self) -> AttributeValue {
-        AttributeValue::S(format!("{}#{}", E::type_name(), self.as_str()))
+impl<E: HasTypeName> From<Id<E>> for AttributeValue {
+    fn from(id: Id<E>) -> Self {
+        AttributeValue::S(format!("{}#{}", E::type_name(), id.as_str()))
     }
 }
 impl<E: HasTypeName> TryFrom<AttributeValue> for Id<E> {
     type Error = String;
 
     fn try_from(value: AttributeValue) -> Result<Self, Self::Error> {
-        let v = value.to_s()?;
+        let v = String::from_attr(value)?;
         let v = v
             .strip_prefix(&format!("{}#", E::type_name()))
-            .ok_or_else(|| "invalid id".to_string())?;
+            .ok_or_else(|| format!("invalid {} id: {}", E::type_name(), v))?;
         Ok(Self::new(v))
     }
 }
@@ -0,0 +1,123 @@
+use crate::domain::api_key::{ApiKey, Id, Scope, Status};
+use crate::domain::time::LocalDateTime;
+use crate::errors::Kind::{Internal, NotFound};
+use crate::infra::aws::ddb::retry::send_with_retry;
+use crate::infra::aws::ddb::types::{impl_attr_value_for_enum, AttrMap};
+use crate::infra::aws::ddb::{
+    anchor_attr_value, parallel_scan, HasTableName, HasTypeName, TableRepository, ToAttrValue,
+};
+use crate::AppResult;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+
+impl_attr_value_for_enum!(Scope, Status);
+
+impl TryFrom<HashMap<String, AttributeValue>> for ApiKey {
+    type Error = String;
+    fn try_from(mut v: HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: v.take("pk")?,
+            name: v.take("name")?,
+            secret_hash: v.take("secretHash")?,
+            scopes: v.take("scopes")?,
+            status: v.take("status")?,
+            created_at: v.take("createdAt")?,
+            updated_at: v.take("updatedAt")?,
+            last_used_at: v.take_opt("lastUsedAt")?,
+            revoked_at: v.take_opt("revokedAt")?,
+        })
+    }
+}
+impl From<ApiKey> for HashMap<String, AttributeValue> {
+    fn from(v: ApiKey) -> Self {
+        [
+            ("pk", Some(v.id.into_attr())),
+            ("sk", Some(anchor_attr_value())),
+            ("name", Some(v.name.into_attr())),
+            ("secretHash", Some(v.secret_hash.into_attr())),
+            ("scopes", Some(v.scopes.into_attr())),
+            ("status", Some(v.status.into_attr())),
+            ("createdAt", Some(v.created_at.into_attr())),
+            ("updatedAt", Some(v.updated_at.into_attr())),
+            ("lastUsedAt", v.last_used_at.map(|v| v.into_attr())),
+            ("revokedAt", v.revoked_at.map(|v| v.into_attr())),
+            ("glk", Some(ApiKey::type_name().into_attr())),
+        ]
+        .into_iter()
+        .flat_map(|(k, v)| v.map(|v| (k.into(), v)))
+        .collect()
+    }
+}
+impl HasTableName for ApiKey {
+    fn table_name() -> String {
+        "api-key".to_string()
+    }
+}
+impl HasTypeName for ApiKey {
+    fn type_name() -> String {
+        "ApiKey".to_string()
+    }
+}
+
+pub type Repository = TableRepository<ApiKey>;
+impl Repository {
+    // 件数は管理者の数程度なのでScanで全件取得する
+    pub async fn find_all(&self) -> AppResult<Vec<ApiKey>> {
+        let mut rx = parallel_scan(
+            self.cli.scan().table_name(self.table_name()),
+            1,
+            ApiKey::try_from,
+        );
+
+        let mut items: Vec<ApiKey> = vec![];
+        while let Some(page) = rx.recv().await {
+            items.append(&mut page?);
+        }
+        items.sort_by_key(|v| std::cmp::Reverse(v.created_at));
+
+        Ok(items)
+    }
+
+    pub async fn get(&self, id: &Id) -> AppResult<ApiKey> {
+        let q = self
+            .cli
+            .get_item()
+            .table_name(self.table_name())
+            .set_key(Some(HashMap::from([
+                ("pk".into(), id.clone().into()),
+                ("sk".into(), anchor_attr_value()),
+            ])));
+        let res = send_with_retry(|| q.clone().send()).await?;
+
+        res.item.map_or(Err(NotFound.into()), |v| {
+            ApiKey::try_from(v).map_err(Internal.withf())
+        })
+    }
+
+    pub async fn put(&self, item: ApiKey) -> AppResult<()> {
+        let q = self
+            .cli
+            .put_item()
+            .table_name(self.table_name())
+            .set_item(Some(item.into()));
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+
+    // 最終利用日時だけを更新する。発行や失効と競合しても他の項目は上書きしない
+    pub async fn touch(&self, id: &Id, now: LocalDateTime) -> AppResult<()> {
+        let q = self
+            .cli
+            .update_item()
+            .table_name(self.table_name())
+            .set_key(Some(HashMap::from([
+                ("pk".into(), id.clone().into()),
+                ("sk".into(), anchor_attr_value()),
+            ])))
+            .condition_expression("attribute_exists(pk)")
+            .update_expression("SET lastUsedAt = :lastUsedAt")
+            .expression_attribute_values(":lastUsedAt", now.into_attr());
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use crate::domain::audit_event::{AuditEvent, AuditResult};
+use crate::errors::Kind::Internal;
+use crate::infra::aws::ddb::cursor::{entity_with_cursor_conv_from, Cursor, WithCursor};
+use crate::infra::aws::ddb::index::{
+    EvaluateKeyNamesProvider, SecondaryIndex, GENERAL_PRIMARY_INDEX,
+};
+use crate::infra::aws::ddb::retry::send_with_retry;
+use crate::infra::aws::ddb::types::{impl_attr_value_for_enum, AttrMap};
+use crate::infra::aws::ddb::{
+    anchor_attr_value, condition_eq, query, EntityWithCursor, HasTableName, HasTypeName,
+    TableRepository, ToAttrValue,
+};
+use crate::AppResult;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+
+impl_attr_value_for_enum!(AuditResult);
+
+impl TryFrom<HashMap<String, AttributeValue>> for AuditEvent {
+    type Error = String;
+    fn try_from(mut v: HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: v.take("pk")?,
+            actor: v.take("actor")?,
+            api_key_id: v.take_opt("apiKeyId")?,
+            operation_name: v.take_opt("operationName")?,
+            fields: v.take("fields")?,
+            // 以前の記録には引数ではなく変数(variables)だけが残っている
+            arguments: match v.take_opt("arguments")? {
+                Some(arguments) => arguments,
+                None => v.take("variables")?,
+            },
+            result: v.take("result")?,
+            errors: v.take("errors")?,
+            duration_ms: v.take("durationMs")?,
+            created_at: v.take("createdAt")?,
+        })
+    }
+}
+impl From<AuditEvent> for HashMap<String, AttributeValue> {
+    fn from(v: AuditEvent) -> Self {
+        [
+            ("pk", Some(v.id.into_attr())),
+            ("sk", Some(anchor_attr_value())),
+            ("actor", Some(v.actor.into_attr())),
+            ("apiKeyId", v.api_key_id.map(|v| v.into_attr())),
+            ("operationName", v.operation_name.map(|v| v.into_attr())),
+            ("fields", Some(v.fields.into_attr())),
+            ("arguments", Some(v.arguments.into_attr())),
+            ("result", Some(v.result.into_attr())),
+            ("errors", Some(v.errors.into_attr())),
+            ("durationMs", Some(v.duration_ms.into_attr())),
+            ("createdAt", Some(v.created_at.into_attr())),
+            ("glk", Some(AuditEvent::type_name().into_attr())),
+        ]
+        .into_iter()
+        .flat_map(|(k, v)| v.map(|v| (k.into(), v)))
+        .collect()
+    }
+}
+impl HasTableName for AuditEvent {
+    fn table_name() -> String {
+        "audit-event".to_string()
+    }
+}
+impl HasTypeName for AuditEvent {
+    fn type_name() -> String {
+        "AuditEvent".to_string()
+    }
+}
+
+const INDEX_GLK_CREATED_AT: SecondaryIndex = SecondaryIndex {
+    name: "glk-createdAt-index",
+    hash_key: "glk",
+    range_key: Some("createdAt"),
+    primary_index: &GENERAL_PRIMARY_INDEX,
+};
+
+pub type Repository = TableRepository<AuditEvent>;
+impl Repository {
+    // 新しいものから順に返す
+    pub async fn find_latest(
+        &self,
+        cursor: Option<Cursor>,
+        limit: Option<i32>,
+    ) -> AppResult<Vec<EntityWithCursor<AuditEvent>>> {
+        let index = &INDEX_GLK_CREATED_AT;
+
+        query(
+            self.cli
+                .query()
+                .table_name(self.table_name())
+                .index_name(index.name)
+                .key_conditions(
+                    index.hash_key,
+                    condition_eq(AuditEvent::type_name().into_attr()),
+                )
+                .scan_index_forward(false)
+                .with_cursor(cursor)
+                .map_err(|v| Internal.with(v))?,
+            limit,
+            entity_with_cursor_conv_from(index.evaluate_key_names(), AuditEvent::try_from),
+        )
+        .await
+    }
+
+    pub async fn put(&self, item: AuditEvent) -> AppResult<()> {
+        let q = self
+            .cli
+            .put_item()
+            .table_name(self.table_name())
+            .set_item(Some(item.into()));
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+}
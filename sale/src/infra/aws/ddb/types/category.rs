@@ -0,0 +1,119 @@
+use crate::domain::category::{Category, Id};
+use crate::errors::Kind::{Internal, NotFound};
+use crate::infra::aws::ddb::retry::send_with_retry;
+use crate::infra::aws::ddb::types::AttrMap;
+use crate::infra::aws::ddb::{
+    anchor_attr_value, condition_eq, parallel_scan, HasTableName, HasTypeName, TableRepository,
+    ToAttrValue,
+};
+use crate::AppResult;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+
+impl TryFrom<HashMap<String, AttributeValue>> for Category {
+    type Error = String;
+    fn try_from(mut v: HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: v.take("pk")?,
+            name: v.take("name")?,
+            parent_id: v.take_opt("parentId")?,
+            path: v.take("path")?,
+            genre_id: v.take_opt("genreId")?,
+            product_count: v.take_opt("productCount")?.unwrap_or_default(),
+            created_at: v.take("createdAt")?,
+            updated_at: v.take("updatedAt")?,
+        })
+    }
+}
+impl From<Category> for HashMap<String, AttributeValue> {
+    fn from(v: Category) -> Self {
+        [
+            ("pk", Some(v.id.into_attr())),
+            ("sk", Some(anchor_attr_value())),
+            ("name", Some(v.name.into_attr())),
+            ("parentId", v.parent_id.map(|v| v.into_attr())),
+            ("path", Some(v.path.into_attr())),
+            ("genreId", v.genre_id.map(|v| v.into_attr())),
+            ("productCount", Some(v.product_count.into_attr())),
+            ("createdAt", Some(v.created_at.into_attr())),
+            ("updatedAt", Some(v.updated_at.into_attr())),
+            ("glk", Some(Category::type_name().into_attr())),
+        ]
+        .into_iter()
+        .flat_map(|(k, v)| v.map(|v| (k.into(), v)))
+        .collect()
+    }
+}
+impl HasTableName for Category {
+    fn table_name() -> String {
+        "category".to_string()
+    }
+}
+impl HasTypeName for Category {
+    fn type_name() -> String {
+        "Category".to_string()
+    }
+}
+
+const SCAN_SEGMENTS: i32 = 2;
+
+pub type Repository = TableRepository<Category>;
+impl Repository {
+    // カテゴリの数は多くないため、木を作るときは全件を取得する
+    pub async fn find_all(&self) -> AppResult<Vec<Category>> {
+        let mut rx = parallel_scan(
+            self.cli
+                .scan()
+                .table_name(self.table_name())
+                .scan_filter("sk", condition_eq(anchor_attr_value())),
+            SCAN_SEGMENTS,
+            Category::try_from,
+        );
+
+        let mut items: Vec<Category> = vec![];
+        while let Some(page) = rx.recv().await {
+            items.append(&mut page?);
+        }
+        items.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(items)
+    }
+
+    pub async fn get(&self, id: &Id) -> AppResult<Category> {
+        let q = self
+            .cli
+            .get_item()
+            .table_name(self.table_name())
+            .set_key(Some(HashMap::from([
+                ("pk".into(), id.clone().into()),
+                ("sk".into(), anchor_attr_value()),
+            ])));
+        let res = send_with_retry(|| q.clone().send()).await?;
+
+        res.item.map_or(Err(NotFound.into()), |v| {
+            Category::try_from(v).map_err(Internal.withf())
+        })
+    }
+
+    pub async fn put(&self, item: Category) -> AppResult<()> {
+        let q = self
+            .cli
+            .put_item()
+            .table_name(self.table_name())
+            .set_item(Some(item.into()));
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, id: &Id) -> AppResult<()> {
+        let q = self
+            .cli
+            .delete_item()
+            .table_name(self.table_name())
+            .set_key(Some(HashMap::from([
+                ("pk".into(), id.clone().into()),
+                ("sk".into(), anchor_attr_value()),
+            ])));
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use crate::domain::crawl_failure::{CrawlFailure, Id, Status, Target};
+use crate::errors::Kind::{Internal, NotFound};
+use crate::infra::aws::ddb::cursor::{entity_with_cursor_conv_from, Cursor, WithCursor};
+use crate::infra::aws::ddb::index::{
+    EvaluateKeyNamesProvider, SecondaryIndex, GENERAL_PRIMARY_INDEX,
+};
+use crate::infra::aws::ddb::retry::send_with_retry;
+use crate::infra::aws::ddb::types::{impl_attr_value_for_enum, AttrMap};
+use crate::infra::aws::ddb::{
+    anchor_attr_value, condition_eq, query, EntityWithCursor, HasTableName, HasTypeName,
+    TableRepository, ToAttrValue,
+};
+use crate::AppResult;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+
+impl_attr_value_for_enum!(Target, Status);
+
+impl TryFrom<HashMap<String, AttributeValue>> for CrawlFailure {
+    type Error = String;
+    fn try_from(mut v: HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: v.take("pk")?,
+            target: v.take("target")?,
+            payload: v.take("payload")?,
+            product_id: v.take_opt("productId")?,
+            error: v.take("error")?,
+            attempts: v.take("attempts")?,
+            status: v.take("status")?,
+            created_at: v.take("createdAt")?,
+            updated_at: v.take("updatedAt")?,
+            replayed_at: v.take_opt("replayedAt")?,
+            last_delivery_id: v.take_opt("lastDeliveryId")?,
+        })
+    }
+}
+impl From<CrawlFailure> for HashMap<String, AttributeValue> {
+    fn from(v: CrawlFailure) -> Self {
+        [
+            ("pk", Some(v.id.into_attr())),
+            ("sk", Some(anchor_attr_value())),
+            ("target", Some(v.target.into_attr())),
+            ("payload", Some(v.payload.into_attr())),
+            ("productId", v.product_id.map(|v| v.into_attr())),
+            ("error", Some(v.error.into_attr())),
+            ("attempts", Some(v.attempts.into_attr())),
+            ("status", Some(v.status.into_attr())),
+            ("createdAt", Some(v.created_at.into_attr())),
+            ("updatedAt", Some(v.updated_at.into_attr())),
+            ("replayedAt", v.replayed_at.map(|v| v.into_attr())),
+            ("lastDeliveryId", v.last_delivery_id.map(|v| v.into_attr())),
+            ("glk", Some(CrawlFailure::type_name().into_attr())),
+        ]
+        .into_iter()
+        .flat_map(|(k, v)| v.map(|v| (k.into(), v)))
+        .collect()
+    }
+}
+impl HasTableName for CrawlFailure {
+    fn table_name() -> String {
+        "crawl-failure".to_string()
+    }
+}
+impl HasTypeName for CrawlFailure {
+    fn type_name() -> String {
+        "CrawlFailure".to_string()
+    }
+}
+
+const INDEX_STATUS_UPDATED_AT: SecondaryIndex = SecondaryIndex {
+    name: "status-updatedAt-index",
+    hash_key: "status",
+    range_key: Some("updatedAt"),
+    primary_index: &GENERAL_PRIMARY_INDEX,
+};
+
+pub type Repository = TableRepository<CrawlFailure>;
+impl Repository {
+    // 新しく失敗したものから順に返す
+    pub async fn find_by_status(
+        &self,
+        status: Status,
+        cursor: Option<Cursor>,
+        limit: Option<i32>,
+    ) -> AppResult<Vec<EntityWithCursor<CrawlFailure>>> {
+        let index = &INDEX_STATUS_UPDATED_AT;
+
+        query(
+            self.cli
+                .query()
+                .table_name(self.table_name())
+                .index_name(index.name)
+                .key_conditions(index.hash_key, condition_eq(status.to_string().into_attr()))
+                .scan_index_forward(false)
+                .with_cursor(cursor)
+                .map_err(|v| Internal.with(v))?,
+            limit,
+            entity_with_cursor_conv_from(index.evaluate_key_names(), CrawlFailure::try_from),
+        )
+        .await
+    }
+
+    pub async fn get(&self, id: &Id) -> AppResult<CrawlFailure> {
+        let q = self
+            .cli
+            .get_item()
+            .table_name(self.table_name())
+            .set_key(Some(HashMap::from([
+                ("pk".into(), id.clone().into()),
+                ("sk".into(), anchor_attr_value()),
+            ])));
+        let res = send_with_retry(|| q.clone().send()).await?;
+
+        res.item.map_or(Err(NotFound.into()), |v| {
+            CrawlFailure::try_from(v).map_err(Internal.withf())
+        })
+    }
+
+    pub async fn put(&self, item: CrawlFailure) -> AppResult<()> {
+        let q = self
+            .cli
+            .put_item()
+            .table_name(self.table_name())
+            .set_item(Some(item.into()));
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use crate::domain::migration::{Id, Migration, Status};
+use crate::errors::Kind::{Internal, NotFound};
+use crate::infra::aws::ddb::retry::send_with_retry;
+use crate::infra::aws::ddb::types::{impl_attr_value_for_enum, AttrMap};
+use crate::infra::aws::ddb::{
+    anchor_attr_value, parallel_scan, HasTableName, HasTypeName, TableRepository, ToAttrValue,
+};
+use crate::AppResult;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+
+impl_attr_value_for_enum!(Status);
+
+impl TryFrom<HashMap<String, AttributeValue>> for Migration {
+    type Error = String;
+    fn try_from(mut v: HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: v.take("pk")?,
+            name: v.take("name")?,
+            version: v.take("version")?,
+            dry_run: v.take("dryRun")?,
+            status: v.take("status")?,
+            cursor: v.take_opt("cursor")?,
+            chunks: v.take("chunks")?,
+            processed: v.take("processed")?,
+            touched: v.take("touched")?,
+            error: v.take_opt("error")?,
+            started_at: v.take("startedAt")?,
+            updated_at: v.take("updatedAt")?,
+            completed_at: v.take_opt("completedAt")?,
+            revision: v.take_opt("revision")?.unwrap_or_default(),
+        })
+    }
+}
+impl From<Migration> for HashMap<String, AttributeValue> {
+    fn from(v: Migration) -> Self {
+        [
+            ("pk", Some(v.id.into_attr())),
+            ("sk", Some(anchor_attr_value())),
+            ("name", Some(v.name.into_attr())),
+            ("version", Some(v.version.into_attr())),
+            ("dryRun", Some(v.dry_run.into_attr())),
+            ("status", Some(v.status.into_attr())),
+            ("cursor", v.cursor.map(|v| v.into_attr())),
+            ("chunks", Some(v.chunks.into_attr())),
+            ("processed", Some(v.processed.into_attr())),
+            ("touched", Some(v.touched.into_attr())),
+            ("error", v.error.map(|v| v.into_attr())),
+            ("startedAt", Some(v.started_at.into_attr())),
+            ("updatedAt", Some(v.updated_at.into_attr())),
+            ("completedAt", v.completed_at.map(|v| v.into_attr())),
+            ("revision", Some(v.revision.into_attr())),
+            ("glk", Some(Migration::type_name().into_attr())),
+        ]
+        .into_iter()
+        .flat_map(|(k, v)| v.map(|v| (k.into(), v)))
+        .collect()
+    }
+}
+impl HasTableName for Migration {
+    fn table_name() -> String {
+        "migration".to_string()
+    }
+}
+impl HasTypeName for Migration {
+    fn type_name() -> String {
+        "Migration".to_string()
+    }
+}
+
+pub type Repository = TableRepository<Migration>;
+impl Repository {
+    // 件数は定義済みのマイグレーション数程度なのでScanで全件取得する
+    pub async fn find_all(&self) -> AppResult<Vec<Migration>> {
+        let mut rx = parallel_scan(
+            self.cli.scan().table_name(self.table_name()),
+            1,
+            Migration::try_from,
+        );
+
+        let mut items: Vec<Migration> = vec![];
+        while let Some(page) = rx.recv().await {
+            items.append(&mut page?);
+        }
+        items.sort_by_key(|v| std::cmp::Reverse(v.started_at));
+
+        Ok(items)
+    }
+
+    pub async fn get(&self, id: &Id) -> AppResult<Migration> {
+        let q = self
+            .cli
+            .get_item()
+            .table_name(self.table_name())
+            .set_key(Some(HashMap::from([
+                ("pk".into(), id.clone().into()),
+                ("sk".into(), anchor_attr_value()),
+            ])));
+        let res = send_with_retry(|| q.clone().send()).await?;
+
+        res.item.map_or(Err(NotFound.into()), |v| {
+            Migration::try_from(v).map_err(Internal.withf())
+        })
+    }
+
+    // 楽観ロック。読み込んだ時点からrevisionが進んでいればConflictを返す
+    // revisionを持たない以前の記録は、最初の保存でrevisionを付ける
+    pub async fn put(&self, item: Migration) -> AppResult<Migration> {
+        let revision = item.revision;
+        let item = Migration {
+            revision: revision + 1,
+            ..item
+        };
+        let q = self
+            .cli
+            .put_item()
+            .table_name(self.table_name())
+            .set_item(Some(item.clone().into()));
+        let q = if revision == 0 {
+            q.condition_expression("attribute_not_exists(pk) OR attribute_not_exists(revision)")
+        } else {
+            q.condition_expression("revision = :revision")
+                .expression_attribute_values(":revision", revision.into_attr())
+        };
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(item)
+    }
+}
@@ -1,96 +1,149 @@
-use crate::domain::product::{Id, Product, Source, Status};
+use crate::domain::category::Category;
+use crate::domain::product::{DealScore, Id, MirroredImage, Product, Source, Status};
+use crate::domain::{category, product_group, shop};
 use crate::errors::Kind::{Internal, NotFound};
 use crate::infra::aws::ddb::cursor::{entity_with_cursor_conv_from, Cursor, WithCursor};
 use crate::infra::aws::ddb::index::{
     EvaluateKeyNamesProvider, SecondaryIndex, GENERAL_PRIMARY_INDEX,
 };
 use crate::infra::aws::ddb::prelude::BatchGet;
+use crate::infra::aws::ddb::retry::send_with_retry;
+use crate::infra::aws::ddb::types::{impl_attr_value_for_enum, AttrError, AttrMap, Nested};
 use crate::infra::aws::ddb::{
-    anchor_attr_value, batch_get, condition_eq, query, EntityWithCursor, FromAttrValue,
-    HasTableName, HasTypeName, TableRepository, ToAttrValue,
+    anchor_attr_value, batch_get, condition_begins_with, condition_eq, parallel_scan, query,
+    EntityWithCursor, HasTableName, HasTypeName, TableRepository, ToAttrValue,
 };
-use crate::{AppResult, MustPresent};
+use crate::AppResult;
 use async_trait::async_trait;
 use aws_sdk_dynamodb::types::AttributeValue;
 use std::collections::HashMap;
-use std::str::FromStr;
+use tokio::sync::mpsc;
+
+impl_attr_value_for_enum!(Source, Status);
 
 impl TryFrom<HashMap<String, AttributeValue>> for Product {
     type Error = String;
     fn try_from(mut v: HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
-        let source = Source::from_str(&v.remove("source").must_present()?.to_s()?)
-            .map_err(|_| "invalid enum")?;
-        let status = Status::from_str(&v.remove("status").must_present()?.to_s()?)
-            .map_err(|_| "invalid enum")?;
-        let detail_url = v.remove("detailUrl").must_present()?.to_s()?;
-        let image_urls = v
-            .remove("imageUrls")
-            .map(|v| v.to_s_list())
-            .transpose()?
-            .unwrap_or_default();
-
         Ok(Self {
-            id: v.remove("pk").must_present()?.try_into()?,
-            source,
-            status,
-            detail_url: url::Url::parse(&detail_url).map_err(|_| "invalid url".to_string())?,
-            title: v.remove("title").map(|v| v.to_s()).transpose()?,
-            image_urls: image_urls
-                .iter()
-                .map(|v| url::Url::parse(v).map_err(|_| "invalid url".to_string()))
-                .collect::<Result<Vec<url::Url>, String>>()?,
-            retail_price: v.remove("retailPrice").map(|v| v.to_s()).transpose()?,
-            actual_price: v.remove("actualPrice").map(|v| v.to_s()).transpose()?,
-            retail_off: v.remove("retailOff").map(|v| v.to_s()).transpose()?,
-            breadcrumb: v
-                .remove("breadcrumb")
-                .map(|v| v.to_s_list())
-                .transpose()?
+            id: v.take("pk")?,
+            source: v.take("source")?,
+            shop_id: v.take_opt("shopId")?,
+            item_id: v.take_opt("itemId")?,
+            status: v.take("status")?,
+            detail_url: v.take("detailUrl")?,
+            affiliate_url: v.take_opt("affiliateUrl")?,
+            title: v.take_opt("title")?,
+            image_urls: v.take_opt("imageUrls")?.unwrap_or_default(),
+            retail_price: v.take_opt("retailPrice")?,
+            actual_price: v.take_opt("actualPrice")?,
+            retail_off: v.take_opt("retailOff")?,
+            breadcrumb: v.take_opt("breadcrumb")?.unwrap_or_default(),
+            points: v.take_opt("points")?,
+            lowest_price: v.take_opt("lowestPrice")?,
+            deal_score: v.take_opt("dealScore")?.map(DealScore),
+            brand: v.take_opt("brand")?,
+            currency: v.take_opt("currency")?,
+            rating: v.take_opt("rating")?,
+            review_count: v.take_opt("reviewCount")?,
+            failure_count: v.take_opt("failureCount")?.unwrap_or_default(),
+            expires_at: v.take_opt("expiresAt")?,
+            charset: v.take_opt("charset")?,
+            fingerprint: v.take_opt("fingerprint")?,
+            etag: v.take_opt("etag")?,
+            last_modified: v.take_opt("lastModified")?,
+            last_checked_at: v.take_opt("lastCheckedAt")?,
+            next_check_at: v.take_opt("nextCheckAt")?,
+            unchanged_count: v.take_opt("unchangedCount")?.unwrap_or_default(),
+            mirrored_images: v
+                .take_opt::<Nested<Vec<MirroredImage>>>("mirroredImages")?
+                .map(|v| v.0)
                 .unwrap_or_default(),
-            points: v.remove("points").map(|v| v.to_s()).transpose()?,
-            created_at: v.remove("createdAt").must_present()?.to_date_time()?,
-            updated_at: v.remove("updatedAt").must_present()?.to_date_time()?,
+            group_id: v.take_opt("groupId")?,
+            genre_id: v.take_opt("genreId")?,
+            category_ids: v.take_opt("categoryIds")?.unwrap_or_default(),
+            created_at: v.take("createdAt")?,
+            updated_at: v.take("updatedAt")?,
         })
     }
 }
-impl Into<HashMap<String, AttributeValue>> for Product {
-    fn into(self) -> HashMap<String, AttributeValue> {
-        [
-            ("pk", Some(self.id.into())),
+impl TryFrom<Product> for HashMap<String, AttributeValue> {
+    type Error = AttrError;
+    fn try_from(v: Product) -> Result<Self, Self::Error> {
+        Ok([
+            ("pk", Some(v.id.into_attr())),
             ("sk", Some(anchor_attr_value())),
-            ("source", Some(self.source.to_string().into_attr())),
-            ("status", Some(self.status.to_string().into_attr())),
-            ("detailUrl", Some(self.detail_url.to_string().into_attr())),
-            ("title", self.title.map(|v| v.into_attr())),
+            ("source", Some(v.source.into_attr())),
             (
-                "imageUrls",
+                "shop_status",
+                v.shop_id
+                    .as_ref()
+                    .map(|id| format!("{}-{}", id.as_str(), v.status).into_attr()),
+            ),
+            ("shopId", v.shop_id.map(|v| v.into_attr())),
+            ("itemId", v.item_id.map(|v| v.into_attr())),
+            ("status", Some(v.status.into_attr())),
+            ("detailUrl", Some(v.detail_url.into_attr())),
+            ("affiliateUrl", v.affiliate_url.map(|v| v.into_attr())),
+            ("title", v.title.map(|v| v.into_attr())),
+            ("imageUrls", Some(v.image_urls.into_attr())),
+            ("retailPrice", v.retail_price.map(|v| v.into_attr())),
+            ("actualPrice", v.actual_price.map(|v| v.into_attr())),
+            ("retailOff", v.retail_off.map(|v| v.into_attr())),
+            ("breadcrumb", Some(v.breadcrumb.into_attr())),
+            ("points", v.points.map(|v| v.into_attr())),
+            ("lowestPrice", v.lowest_price.map(|v| v.into_attr())),
+            // 値が無い商品はお得度順の索引に載せない
+            ("dealScore", v.deal_score.map(|v| v.0.into_attr())),
+            ("brand", v.brand.map(|v| v.into_attr())),
+            ("currency", v.currency.map(|v| v.into_attr())),
+            ("rating", v.rating.map(|v| v.into_attr())),
+            ("reviewCount", v.review_count.map(|v| v.into_attr())),
+            ("failureCount", Some(v.failure_count.into_attr())),
+            ("expiresAt", v.expires_at.map(|v| v.into_attr())),
+            // DynamoDBのTTLはエポック秒で指定する
+            ("ttl", v.expires_at.map(|v| v.timestamp().into_attr())),
+            ("charset", v.charset.map(|v| v.into_attr())),
+            ("fingerprint", v.fingerprint.map(|v| v.into_attr())),
+            ("etag", v.etag.map(|v| v.into_attr())),
+            ("lastModified", v.last_modified.map(|v| v.into_attr())),
+            ("lastCheckedAt", v.last_checked_at.map(|v| v.into_attr())),
+            ("nextCheckAt", v.next_check_at.map(|v| v.into_attr())),
+            ("unchangedCount", Some(v.unchanged_count.into_attr())),
+            (
+                "mirroredImages",
                 Some(
-                    self.image_urls
-                        .into_iter()
-                        .map(|v| v.to_string())
-                        .collect::<Vec<_>>()
-                        .into_attr(),
+                    Nested(v.mirrored_images)
+                        .try_into_attr()
+                        .map_err(|e| e.at("mirroredImages"))?,
                 ),
             ),
-            ("retailPrice", self.retail_price.map(|v| v.into_attr())),
-            ("actualPrice", self.actual_price.map(|v| v.into_attr())),
-            ("retailOff", self.retail_off.map(|v| v.into_attr())),
-            ("breadcrumb", Some(self.breadcrumb.into_attr())),
-            ("points", self.points.map(|v| v.into_attr())),
-            ("createdAt", Some(self.created_at.into_attr())),
-            ("updatedAt", Some(self.updated_at.into_attr())),
+            ("groupId", v.group_id.map(|v| v.into_attr())),
+            ("genreId", v.genre_id.map(|v| v.into_attr())),
+            // 空のリストは保存しない。categoryPath はカテゴリでの絞り込み用
+            (
+                "categoryPath",
+                Some(Category::path_key_of(&v.category_ids))
+                    .filter(|v| !v.is_empty())
+                    .map(|v| v.into_attr()),
+            ),
+            (
+                "categoryIds",
+                Some(v.category_ids)
+                    .filter(|v| !v.is_empty())
+                    .map(|v| v.into_attr()),
+            ),
+            ("createdAt", Some(v.created_at.into_attr())),
+            ("updatedAt", Some(v.updated_at.into_attr())),
             ("glk", Some(Product::type_name().into_attr())),
             // for index
             (
                 "source_status",
-                Some(
-                    format!("{}-{}", self.source.to_string(), self.status.to_string()).into_attr(),
-                ),
+                Some(format!("{}-{}", v.source, v.status).into_attr()),
             ),
         ]
         .into_iter()
         .flat_map(|(k, v)| v.map(|v| (k.into(), v)))
-        .collect()
+        .collect())
     }
 }
 impl HasTableName for Product {
@@ -129,9 +182,58 @@ const INDEX_SOURCE_STATUS_CREATED_AT: SecondaryIndex = SecondaryIndex {
     primary_index: &GENERAL_PRIMARY_INDEX,
 };
 
+const INDEX_SHOP_STATUS_CREATED_AT: SecondaryIndex = SecondaryIndex {
+    name: "shop_status-createdAt-index",
+    hash_key: "shop_status",
+    range_key: Some("createdAt"),
+    primary_index: &GENERAL_PRIMARY_INDEX,
+};
+const INDEX_STATUS_CATEGORY_PATH: SecondaryIndex = SecondaryIndex {
+    name: "status-categoryPath-index",
+    hash_key: "status",
+    range_key: Some("categoryPath"),
+    primary_index: &GENERAL_PRIMARY_INDEX,
+};
+const INDEX_STATUS_DEAL_SCORE: SecondaryIndex = SecondaryIndex {
+    name: "status-dealScore-index",
+    hash_key: "status",
+    range_key: Some("dealScore"),
+    primary_index: &GENERAL_PRIMARY_INDEX,
+};
+
+const SCAN_SEGMENTS: i32 = 8;
+
 pub type Repository = TableRepository<Product>;
 impl Repository {
     pub async fn find_all(&self) -> AppResult<Vec<Product>> {
+        let mut rx = self.scan(SCAN_SEGMENTS);
+
+        let mut items: Vec<Product> = vec![];
+        while let Some(page) = rx.recv().await {
+            items.append(&mut page?);
+        }
+        items.sort_by_key(|v| std::cmp::Reverse(v.created_at));
+
+        Ok(items)
+    }
+
+    // テーブル全体をセグメント並列でScanし、ページ単位で受け取る
+    pub fn scan(&self, total_segments: i32) -> mpsc::Receiver<AppResult<Vec<Product>>> {
+        parallel_scan(
+            self.cli
+                .scan()
+                .table_name(self.table_name())
+                .scan_filter("sk", condition_eq(anchor_attr_value())),
+            total_segments,
+            Product::try_from,
+        )
+    }
+
+    pub async fn find_all_with_cursor(
+        &self,
+        cursor: Option<Cursor>,
+        limit: Option<i32>,
+    ) -> AppResult<Vec<EntityWithCursor<Product>>> {
         let index = &INDEX_SK_CREATED_AT;
 
         query(
@@ -141,13 +243,12 @@ impl Repository {
                 .index_name(index.name)
                 .key_conditions(index.hash_key, condition_eq(anchor_attr_value()))
                 .scan_index_forward(false)
-                .with_cursor(None)
+                .with_cursor(cursor)
                 .map_err(|v| Internal.with(v))?,
-            None,
-            Product::try_from,
+            limit,
+            entity_with_cursor_conv_from(index.evaluate_key_names(), Product::try_from),
         )
         .await
-        .map_err(|v| Internal.with(v))
     }
 
     pub async fn find_by_source(
@@ -171,7 +272,6 @@ impl Repository {
             entity_with_cursor_conv_from(index.evaluate_key_names(), Product::try_from),
         )
         .await
-        .map_err(|v| Internal.with(v))
     }
 
     pub async fn find_by_status(
@@ -195,7 +295,30 @@ impl Repository {
             entity_with_cursor_conv_from(index.evaluate_key_names(), Product::try_from),
         )
         .await
-        .map_err(|v| Internal.with(v))
+    }
+
+    // お得度の高い順に返す。お得度が無い商品は含まない
+    pub async fn find_by_status_deal_score(
+        &self,
+        status: Status,
+        cursor: Option<Cursor>,
+        limit: Option<i32>,
+    ) -> AppResult<Vec<EntityWithCursor<Product>>> {
+        let index = &INDEX_STATUS_DEAL_SCORE;
+
+        query(
+            self.cli
+                .query()
+                .table_name(self.table_name())
+                .index_name(index.name)
+                .key_conditions(index.hash_key, condition_eq(status.to_string().into_attr()))
+                .scan_index_forward(false)
+                .with_cursor(cursor)
+                .map_err(|v| Internal.with(v))?,
+            limit,
+            entity_with_cursor_conv_from(index.evaluate_key_names(), Product::try_from),
+        )
+        .await
     }
 
     pub async fn find_by_source_status(
@@ -214,9 +337,7 @@ impl Repository {
                 .index_name(index.name)
                 .key_conditions(
                     index.hash_key,
-                    condition_eq(
-                        format!("{}-{}", source.to_string(), status.to_string()).into_attr(),
-                    ),
+                    condition_eq(format!("{}-{}", source, status).into_attr()),
                 )
                 .scan_index_forward(false)
                 .with_cursor(cursor)
@@ -225,46 +346,229 @@ impl Repository {
             entity_with_cursor_conv_from(index.evaluate_key_names(), Product::try_from),
         )
         .await
-        .map_err(|v| Internal.with(v))
+    }
+
+    pub async fn find_by_shop_status(
+        &self,
+        shop_id: &shop::Id,
+        status: Status,
+        cursor: Option<Cursor>,
+        limit: Option<i32>,
+    ) -> AppResult<Vec<EntityWithCursor<Product>>> {
+        let index = &INDEX_SHOP_STATUS_CREATED_AT;
+
+        query(
+            self.cli
+                .query()
+                .table_name(self.table_name())
+                .index_name(index.name)
+                .key_conditions(
+                    index.hash_key,
+                    condition_eq(format!("{}-{}", shop_id.as_str(), status).into_attr()),
+                )
+                .scan_index_forward(false)
+                .with_cursor(cursor)
+                .map_err(|v| Internal.with(v))?,
+            limit,
+            entity_with_cursor_conv_from(index.evaluate_key_names(), Product::try_from),
+        )
+        .await
+    }
+
+    // 子孫のカテゴリの商品も含めるため、カテゴリのパスで前方一致する
+    // 並び順はカテゴリのパス順になる
+    pub async fn find_by_status_category(
+        &self,
+        status: Status,
+        category: &Category,
+        cursor: Option<Cursor>,
+        limit: Option<i32>,
+    ) -> AppResult<Vec<EntityWithCursor<Product>>> {
+        let index = &INDEX_STATUS_CATEGORY_PATH;
+
+        query(
+            self.cli
+                .query()
+                .table_name(self.table_name())
+                .index_name(index.name)
+                .key_conditions(index.hash_key, condition_eq(status.to_string().into_attr()))
+                .key_conditions(
+                    index.range_key.unwrap(),
+                    condition_begins_with(category.path_key()),
+                )
+                .with_cursor(cursor)
+                .map_err(|v| Internal.with(v))?,
+            limit,
+            entity_with_cursor_conv_from(index.evaluate_key_names(), Product::try_from),
+        )
+        .await
     }
 
     pub async fn get(&self, id: &Id) -> AppResult<Product> {
-        let res = self
+        let q = self
             .cli
             .get_item()
             .table_name(self.table_name())
             .set_key(Some(HashMap::from([
                 ("pk".into(), id.clone().into()),
                 ("sk".into(), anchor_attr_value()),
-            ])))
-            .send()
-            .await?;
+            ])));
+        let res = send_with_retry(|| q.clone().send()).await?;
 
         res.item.map_or(Err(NotFound.into()), |v| {
-            Ok(Product::try_from(v).map_err(Internal.withf())?)
+            Product::try_from(v).map_err(Internal.withf())
         })
     }
 
     pub async fn put(&self, item: Product) -> AppResult<()> {
-        self.cli
+        let q = self
+            .cli
             .put_item()
             .table_name(self.table_name())
-            .set_item(Some(item.clone().into()))
-            .send()
-            .await?;
+            .set_item(Some(item.try_into().map_err(Internal.withf())?));
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+
+    // 内容が変わっていない場合は確認日時と検証子だけを更新する
+    pub async fn touch(&self, item: &Product) -> AppResult<()> {
+        let values: Vec<(&str, Option<AttributeValue>)> = vec![
+            ("lastCheckedAt", item.last_checked_at.map(|v| v.into_attr())),
+            ("nextCheckAt", item.next_check_at.map(|v| v.into_attr())),
+            ("unchangedCount", Some(item.unchanged_count.into_attr())),
+            ("etag", item.etag.clone().map(|v| v.into_attr())),
+            (
+                "lastModified",
+                item.last_modified.clone().map(|v| v.into_attr()),
+            ),
+        ];
+        let values: Vec<(&str, AttributeValue)> = values
+            .into_iter()
+            .flat_map(|(k, v)| v.map(|v| (k, v)))
+            .collect();
+        let expression = values
+            .iter()
+            .map(|(k, _)| format!("{} = :{}", k, k))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let q = self
+            .cli
+            .update_item()
+            .table_name(self.table_name())
+            .set_key(Some(HashMap::from([
+                ("pk".into(), item.id.clone().into()),
+                ("sk".into(), anchor_attr_value()),
+            ])))
+            .update_expression(format!("SET {}", expression))
+            .condition_expression("attribute_exists(pk)")
+            .set_expression_attribute_values(Some(
+                values
+                    .into_iter()
+                    .map(|(k, v)| (format!(":{}", k), v))
+                    .collect(),
+            ));
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+
+    // 画像の複製はクロールと並行しうるため、複製結果だけを更新する
+    pub async fn set_mirrored_images(&self, id: &Id, images: Vec<MirroredImage>) -> AppResult<()> {
+        let q = self
+            .cli
+            .update_item()
+            .table_name(self.table_name())
+            .set_key(Some(HashMap::from([
+                ("pk".into(), id.clone().into()),
+                ("sk".into(), anchor_attr_value()),
+            ])))
+            .update_expression("SET mirroredImages = :mirroredImages")
+            .condition_expression("attribute_exists(pk)")
+            .expression_attribute_values(
+                ":mirroredImages",
+                Nested(images).try_into_attr().map_err(Internal.withf())?,
+            );
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+
+    // グループの再計算で所属が変わった商品だけを更新する
+    pub async fn set_group(&self, id: &Id, group_id: Option<product_group::Id>) -> AppResult<()> {
+        let q = self
+            .cli
+            .update_item()
+            .table_name(self.table_name())
+            .set_key(Some(HashMap::from([
+                ("pk".into(), id.clone().into()),
+                ("sk".into(), anchor_attr_value()),
+            ])))
+            .condition_expression("attribute_exists(pk)");
+        let q = match group_id {
+            Some(group_id) => q
+                .update_expression("SET groupId = :groupId")
+                .expression_attribute_values(":groupId", group_id.into_attr()),
+            None => q.update_expression("REMOVE groupId"),
+        };
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+
+    // お得度の再計算で値が変わった商品だけを更新する
+    pub async fn set_deal_score(&self, id: &Id, deal_score: Option<DealScore>) -> AppResult<()> {
+        let q = self
+            .cli
+            .update_item()
+            .table_name(self.table_name())
+            .set_key(Some(HashMap::from([
+                ("pk".into(), id.clone().into()),
+                ("sk".into(), anchor_attr_value()),
+            ])))
+            .condition_expression("attribute_exists(pk)");
+        let q = match deal_score {
+            Some(deal_score) => q
+                .update_expression("SET dealScore = :dealScore")
+                .expression_attribute_values(":dealScore", deal_score.0.into_attr()),
+            None => q.update_expression("REMOVE dealScore"),
+        };
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+
+    // カテゴリの再計算で所属が変わった商品だけを更新する
+    pub async fn set_categories(&self, id: &Id, category_ids: Vec<category::Id>) -> AppResult<()> {
+        let q = self
+            .cli
+            .update_item()
+            .table_name(self.table_name())
+            .set_key(Some(HashMap::from([
+                ("pk".into(), id.clone().into()),
+                ("sk".into(), anchor_attr_value()),
+            ])))
+            .condition_expression("attribute_exists(pk)");
+        let q = if category_ids.is_empty() {
+            q.update_expression("REMOVE categoryIds, categoryPath")
+        } else {
+            q.update_expression("SET categoryIds = :categoryIds, categoryPath = :categoryPath")
+                .expression_attribute_values(
+                    ":categoryPath",
+                    Category::path_key_of(&category_ids).into_attr(),
+                )
+                .expression_attribute_values(":categoryIds", category_ids.into_attr())
+        };
+        send_with_retry(|| q.clone().send()).await?;
         Ok(())
     }
 
     pub async fn delete(&self, id: &Id) -> AppResult<()> {
-        self.cli
+        let q = self
+            .cli
             .delete_item()
             .table_name(self.table_name())
             .set_key(Some(HashMap::from([
                 ("pk".into(), id.clone().into()),
                 ("sk".into(), anchor_attr_value()),
-            ])))
-            .send()
-            .await?;
+            ])));
+        send_with_retry(|| q.clone().send()).await?;
         Ok(())
     }
 }
@@ -272,13 +576,11 @@ impl Repository {
 impl BatchGet<Product, Id> for Repository {
     async fn batch_get(&self, ids: &[Id]) -> AppResult<HashMap<Id, Product>> {
         let keys = ids
-            .into_iter()
+            .iter()
             .map(|v| v.clone().into_attr_map())
             .collect::<Vec<HashMap<String, AttributeValue>>>();
 
-        let res = batch_get(&self.cli, self.table_name(), &*keys, Product::try_from)
-            .await
-            .map_err(|v| Internal.with(v))?;
+        let res = batch_get(&self.cli, self.table_name(), &keys, Product::try_from).await?;
 
         Ok(res.into_iter().map(|v| (v.id.clone(), v)).collect())
     }
@@ -0,0 +1,85 @@
+use crate::domain::product_event::{EventRecord, ProductEvent};
+use crate::errors::Kind::Internal;
+use crate::infra::aws::ddb::retry::send_with_retry;
+use crate::infra::aws::ddb::types::{AttrError, AttrMap, Nested};
+use crate::infra::aws::ddb::{
+    condition_eq, condition_gt, query, HasTableName, HasTypeName, TableRepository, ToAttrValue,
+};
+use crate::AppResult;
+use aws_sdk_dynamodb::types::AttributeValue;
+use chrono::Duration;
+use std::collections::HashMap;
+
+// 購読中のインスタンスが読み出すまでの間だけ残す
+const RETENTION_HOURS: i64 = 1;
+
+impl TryFrom<HashMap<String, AttributeValue>> for EventRecord {
+    type Error = String;
+    fn try_from(mut v: HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            seq: v.take("sk")?,
+            event: v.take::<Nested<ProductEvent>>("event")?.0,
+            created_at: v.take("createdAt")?,
+        })
+    }
+}
+impl TryFrom<EventRecord> for HashMap<String, AttributeValue> {
+    type Error = AttrError;
+    fn try_from(v: EventRecord) -> Result<Self, Self::Error> {
+        let ttl = (v.created_at + Duration::hours(RETENTION_HOURS)).timestamp();
+        Ok([
+            // 全ての出来事を1つのパーティションに入れ、seq の順に読み出す
+            ("pk", Some(EventRecord::type_name().into_attr())),
+            ("sk", Some(v.seq.into_attr())),
+            (
+                "event",
+                Some(Nested(v.event).try_into_attr().map_err(|e| e.at("event"))?),
+            ),
+            ("createdAt", Some(v.created_at.into_attr())),
+            ("ttl", Some(ttl.into_attr())),
+        ]
+        .into_iter()
+        .flat_map(|(k, v)| v.map(|v| (k.into(), v)))
+        .collect())
+    }
+}
+impl HasTableName for EventRecord {
+    fn table_name() -> String {
+        "product-event".to_string()
+    }
+}
+impl HasTypeName for EventRecord {
+    fn type_name() -> String {
+        "ProductEvent".to_string()
+    }
+}
+
+pub type Repository = TableRepository<EventRecord>;
+impl Repository {
+    pub async fn append(&self, item: EventRecord) -> AppResult<()> {
+        let q = self
+            .cli
+            .put_item()
+            .table_name(self.table_name())
+            .set_item(Some(item.try_into().map_err(Internal.withf())?));
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+
+    // 指定した seq より後の出来事を古い順に返す
+    // 書き込んだ直後の出来事を読み落とさないよう強い整合性で読む
+    pub async fn find_after(&self, seq: &str, limit: Option<i32>) -> AppResult<Vec<EventRecord>> {
+        query(
+            self.cli
+                .query()
+                .table_name(self.table_name())
+                .consistent_read(true)
+                .key_conditions("pk", condition_eq(EventRecord::type_name().into_attr()))
+                .key_conditions("sk", condition_gt(seq.into_attr()))
+                .scan_index_forward(true),
+            limit,
+            EventRecord::try_from,
+        )
+        .await
+    }
+}
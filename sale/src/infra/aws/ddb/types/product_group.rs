@@ -0,0 +1,124 @@
+use crate::domain::product_group::{Id, ProductGroup};
+use crate::errors::Kind::{Internal, NotFound};
+use crate::infra::aws::ddb::prelude::BatchGet;
+use crate::infra::aws::ddb::retry::send_with_retry;
+use crate::infra::aws::ddb::types::AttrMap;
+use crate::infra::aws::ddb::{
+    anchor_attr_value, batch_get, condition_eq, parallel_scan, HasTableName, HasTypeName,
+    TableRepository, ToAttrValue,
+};
+use crate::AppResult;
+use async_trait::async_trait;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+
+impl TryFrom<HashMap<String, AttributeValue>> for ProductGroup {
+    type Error = String;
+    fn try_from(mut v: HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: v.take("pk")?,
+            product_ids: v.take("productIds")?,
+            created_at: v.take("createdAt")?,
+            updated_at: v.take("updatedAt")?,
+        })
+    }
+}
+impl From<ProductGroup> for HashMap<String, AttributeValue> {
+    fn from(v: ProductGroup) -> Self {
+        [
+            ("pk", Some(v.id.into_attr())),
+            ("sk", Some(anchor_attr_value())),
+            ("productIds", Some(v.product_ids.into_attr())),
+            ("createdAt", Some(v.created_at.into_attr())),
+            ("updatedAt", Some(v.updated_at.into_attr())),
+            ("glk", Some(ProductGroup::type_name().into_attr())),
+        ]
+        .into_iter()
+        .flat_map(|(k, v)| v.map(|v| (k.into(), v)))
+        .collect()
+    }
+}
+impl HasTableName for ProductGroup {
+    fn table_name() -> String {
+        "product-group".to_string()
+    }
+}
+impl HasTypeName for ProductGroup {
+    fn type_name() -> String {
+        "ProductGroup".to_string()
+    }
+}
+
+const SCAN_SEGMENTS: i32 = 4;
+
+pub type Repository = TableRepository<ProductGroup>;
+impl Repository {
+    pub async fn find_all(&self) -> AppResult<Vec<ProductGroup>> {
+        let mut rx = parallel_scan(
+            self.cli
+                .scan()
+                .table_name(self.table_name())
+                .scan_filter("sk", condition_eq(anchor_attr_value())),
+            SCAN_SEGMENTS,
+            ProductGroup::try_from,
+        );
+
+        let mut items: Vec<ProductGroup> = vec![];
+        while let Some(page) = rx.recv().await {
+            items.append(&mut page?);
+        }
+        Ok(items)
+    }
+
+    pub async fn get(&self, id: &Id) -> AppResult<ProductGroup> {
+        let q = self
+            .cli
+            .get_item()
+            .table_name(self.table_name())
+            .set_key(Some(HashMap::from([
+                ("pk".into(), id.clone().into()),
+                ("sk".into(), anchor_attr_value()),
+            ])));
+        let res = send_with_retry(|| q.clone().send()).await?;
+
+        res.item.map_or(Err(NotFound.into()), |v| {
+            ProductGroup::try_from(v).map_err(Internal.withf())
+        })
+    }
+
+    pub async fn put(&self, item: ProductGroup) -> AppResult<()> {
+        let q = self
+            .cli
+            .put_item()
+            .table_name(self.table_name())
+            .set_item(Some(item.into()));
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, id: &Id) -> AppResult<()> {
+        let q = self
+            .cli
+            .delete_item()
+            .table_name(self.table_name())
+            .set_key(Some(HashMap::from([
+                ("pk".into(), id.clone().into()),
+                ("sk".into(), anchor_attr_value()),
+            ])));
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+}
+#[async_trait]
+impl BatchGet<ProductGroup, Id> for Repository {
+    async fn batch_get(&self, ids: &[Id]) -> AppResult<HashMap<Id, ProductGroup>> {
+        let keys = ids
+            .iter()
+            .map(|v| v.clone().into_attr_map())
+            .collect::<Vec<HashMap<String, AttributeValue>>>();
+
+        let res = batch_get(&self.cli, self.table_name(), &keys, ProductGroup::try_from).await?;
+
+        Ok(res.into_iter().map(|v| (v.id.clone(), v)).collect())
+    }
+}
@@ -0,0 +1,181 @@
+use crate::domain::product;
+use crate::domain::product_stat::{rank, EventKind, Popularity, ProductStat, Window};
+use crate::domain::time::{Date, LocalDateTime};
+use crate::infra::aws::ddb::index::{SecondaryIndex, GENERAL_PRIMARY_INDEX};
+use crate::infra::aws::ddb::prelude::BatchGet;
+use crate::infra::aws::ddb::retry::send_with_retry;
+use crate::infra::aws::ddb::types::AttrMap;
+use crate::infra::aws::ddb::{
+    batch_get, condition_eq, query, HasTableName, HasTypeName, TableRepository, ToAttrValue,
+};
+use crate::AppResult;
+use async_trait::async_trait;
+use aws_sdk_dynamodb::types::AttributeValue;
+use chrono::{Duration, NaiveTime};
+use std::collections::{HashMap, HashSet};
+
+// 集計の期間より長めに残し、古い日別の件数はTTLで削除する
+const RETENTION_DAYS: i64 = 90;
+
+impl TryFrom<HashMap<String, AttributeValue>> for ProductStat {
+    type Error = String;
+    fn try_from(mut v: HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            product_id: v.take("pk")?,
+            date: v.take("sk")?,
+            views: v.take_opt("views")?.unwrap_or_default(),
+            clicks: v.take_opt("clicks")?.unwrap_or_default(),
+            favorites: v.take_opt("favorites")?.unwrap_or_default(),
+            score: v.take_opt("score")?.unwrap_or_default(),
+        })
+    }
+}
+// 通常は record で加算するため、インポートで丸ごと書き戻す場合だけ使う
+impl From<ProductStat> for HashMap<String, AttributeValue> {
+    fn from(v: ProductStat) -> Self {
+        let ttl = (v.date + Duration::days(RETENTION_DAYS))
+            .and_time(NaiveTime::MIN)
+            .and_utc()
+            .timestamp();
+        [
+            ("pk", Some(v.product_id.into_attr())),
+            ("sk", Some(v.date.into_attr())),
+            ("date", Some(v.date.into_attr())),
+            ("views", Some(v.views.into_attr())),
+            ("clicks", Some(v.clicks.into_attr())),
+            ("favorites", Some(v.favorites.into_attr())),
+            ("score", Some(v.score.into_attr())),
+            ("ttl", Some(ttl.into_attr())),
+            ("glk", Some(ProductStat::type_name().into_attr())),
+        ]
+        .into_iter()
+        .flat_map(|(k, v)| v.map(|v| (k.into(), v)))
+        .collect()
+    }
+}
+impl HasTableName for ProductStat {
+    fn table_name() -> String {
+        "product-stat".to_string()
+    }
+}
+impl HasTypeName for ProductStat {
+    fn type_name() -> String {
+        "ProductStat".to_string()
+    }
+}
+
+fn key_of(product_id: &product::Id, date: Date) -> HashMap<String, AttributeValue> {
+    HashMap::from([
+        ("pk".into(), product_id.clone().into()),
+        ("sk".into(), date.into_attr()),
+    ])
+}
+
+const INDEX_DATE_SCORE: SecondaryIndex = SecondaryIndex {
+    name: "date-score-index",
+    hash_key: "date",
+    range_key: Some("score"),
+    primary_index: &GENERAL_PRIMARY_INDEX,
+};
+
+// 1日あたりのランキングの候補数。期間内の合計はこの中から求める
+const CANDIDATES_PER_DAY: i32 = 200;
+
+pub type Repository = TableRepository<ProductStat>;
+impl Repository {
+    // 読み込まずにADDで加算するため、同時に記録されても件数が欠けない
+    pub async fn record(
+        &self,
+        product_id: &product::Id,
+        kind: EventKind,
+        now: LocalDateTime,
+    ) -> AppResult<()> {
+        let date = now.date_naive();
+        let ttl = (now + Duration::days(RETENTION_DAYS)).timestamp();
+        let q = self
+            .cli
+            .update_item()
+            .table_name(self.table_name())
+            .set_key(Some(key_of(product_id, date)))
+            .update_expression(
+                "ADD #counter :one, score :weight SET #date = :date, #ttl = :ttl, glk = :glk",
+            )
+            .expression_attribute_names("#counter", kind.counter_name())
+            .expression_attribute_names("#date", "date")
+            .expression_attribute_names("#ttl", "ttl")
+            .expression_attribute_values(":one", 1u64.into_attr())
+            .expression_attribute_values(":weight", kind.weight().into_attr())
+            .expression_attribute_values(":date", date.into_attr())
+            .expression_attribute_values(":ttl", ttl.into_attr())
+            .expression_attribute_values(":glk", ProductStat::type_name().into_attr());
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+
+    // その日のスコアが高い順に返す
+    pub async fn find_by_date(
+        &self,
+        date: Date,
+        limit: Option<i32>,
+    ) -> AppResult<Vec<ProductStat>> {
+        let index = &INDEX_DATE_SCORE;
+
+        query(
+            self.cli
+                .query()
+                .table_name(self.table_name())
+                .index_name(index.name)
+                .key_conditions(index.hash_key, condition_eq(date.into_attr()))
+                .scan_index_forward(false),
+            limit,
+            ProductStat::try_from,
+        )
+        .await
+    }
+
+    // 日ごとの上位から期間内の合計を求める。下位の商品の合計は実際より小さくなることがある
+    pub async fn find_popular(
+        &self,
+        window: Window,
+        now: LocalDateTime,
+    ) -> AppResult<Vec<(product::Id, Popularity)>> {
+        let mut stats = vec![];
+        for date in window.dates(now) {
+            stats.append(&mut self.find_by_date(date, Some(CANDIDATES_PER_DAY)).await?);
+        }
+        Ok(rank(&stats))
+    }
+}
+
+// 商品と期間の組ごとに、期間内の日別の件数をまとめて取得して合計する
+#[async_trait]
+impl BatchGet<Popularity, (product::Id, Window)> for Repository {
+    async fn batch_get(
+        &self,
+        ids: &[(product::Id, Window)],
+    ) -> AppResult<HashMap<(product::Id, Window), Popularity>> {
+        let now = crate::domain::time::now();
+        // 同じ商品を別の期間で取得する場合もあるため、キーの重複を除く
+        let keys = ids
+            .iter()
+            .flat_map(|(id, window)| window.dates(now).into_iter().map(|v| (id.clone(), v)))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(|(id, date)| key_of(&id, date))
+            .collect::<Vec<HashMap<String, AttributeValue>>>();
+
+        let stats = batch_get(&self.cli, self.table_name(), &keys, ProductStat::try_from).await?;
+
+        Ok(ids
+            .iter()
+            .map(|(id, window)| {
+                let since = window.since(now);
+                let popularity = stats
+                    .iter()
+                    .filter(|v| v.product_id == *id && v.date >= since)
+                    .fold(Popularity::default(), |acc, v| acc.accumulate(v));
+                ((id.clone(), *window), popularity)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,95 @@
+use crate::domain::rate_limit::{Bucket, Id};
+use crate::errors::Kind::{Internal, NotFound};
+use crate::infra::aws::ddb::retry::send_with_retry;
+use crate::infra::aws::ddb::types::AttrMap;
+use crate::infra::aws::ddb::{
+    anchor_attr_value, HasTableName, HasTypeName, TableRepository, ToAttrValue,
+};
+use crate::AppResult;
+use aws_sdk_dynamodb::types::AttributeValue;
+use chrono::Duration;
+use std::collections::HashMap;
+
+// 使われなくなったバケットはTTLで削除する
+const RETENTION_DAYS: i64 = 1;
+
+impl TryFrom<HashMap<String, AttributeValue>> for Bucket {
+    type Error = String;
+    fn try_from(mut v: HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: v.take("pk")?,
+            tokens: v.take("tokens")?,
+            refilled_at: v.take("refilledAt")?,
+            blocked_until: v.take_opt("blockedUntil")?,
+            version: v.take("version")?,
+        })
+    }
+}
+impl From<Bucket> for HashMap<String, AttributeValue> {
+    fn from(v: Bucket) -> Self {
+        let ttl = (v.refilled_at + Duration::days(RETENTION_DAYS)).timestamp();
+        [
+            ("pk", Some(v.id.into_attr())),
+            ("sk", Some(anchor_attr_value())),
+            ("tokens", Some(v.tokens.into_attr())),
+            ("refilledAt", Some(v.refilled_at.into_attr())),
+            ("blockedUntil", v.blocked_until.map(|v| v.into_attr())),
+            ("version", Some(v.version.into_attr())),
+            ("ttl", Some(ttl.into_attr())),
+            ("glk", Some(Bucket::type_name().into_attr())),
+        ]
+        .into_iter()
+        .flat_map(|(k, v)| v.map(|v| (k.into(), v)))
+        .collect()
+    }
+}
+impl HasTableName for Bucket {
+    fn table_name() -> String {
+        "rate-limit".to_string()
+    }
+}
+impl HasTypeName for Bucket {
+    fn type_name() -> String {
+        "RateLimitBucket".to_string()
+    }
+}
+
+pub type Repository = TableRepository<Bucket>;
+impl Repository {
+    pub async fn get(&self, id: &Id) -> AppResult<Bucket> {
+        let q = self
+            .cli
+            .get_item()
+            .table_name(self.table_name())
+            .set_key(Some(HashMap::from([
+                ("pk".into(), id.clone().into()),
+                ("sk".into(), anchor_attr_value()),
+            ])))
+            .consistent_read(true);
+        let res = send_with_retry(|| q.clone().send()).await?;
+
+        res.item.map_or(Err(NotFound.into()), |v| {
+            Bucket::try_from(v).map_err(Internal.withf())
+        })
+    }
+
+    // 楽観ロック。読み込んだ時点からversionが進んでいればConflictを返す
+    pub async fn put(&self, item: Bucket) -> AppResult<()> {
+        let version = item.version;
+        let q = self
+            .cli
+            .put_item()
+            .table_name(self.table_name())
+            .set_item(Some(
+                Bucket {
+                    version: version + 1,
+                    ..item
+                }
+                .into(),
+            ))
+            .condition_expression("attribute_not_exists(pk) OR version = :version")
+            .expression_attribute_values(":version", version.into_attr());
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use crate::domain::shop::{Id, Shop};
+use crate::domain::time::LocalDateTime;
+use crate::errors::AppError;
+use crate::errors::Kind::{Conflict, Internal, NotFound};
+use crate::infra::aws::ddb::prelude::BatchGet;
+use crate::infra::aws::ddb::retry::send_with_retry;
+use crate::infra::aws::ddb::types::AttrMap;
+use crate::infra::aws::ddb::{
+    anchor_attr_value, batch_get, HasTableName, HasTypeName, TableRepository, ToAttrValue,
+};
+use crate::AppResult;
+use async_trait::async_trait;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+
+impl TryFrom<HashMap<String, AttributeValue>> for Shop {
+    type Error = String;
+    fn try_from(mut v: HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: v.take("pk")?,
+            source: v.take("source")?,
+            code: v.take("code")?,
+            name: v.take_opt("name")?,
+            url: v.take_opt("url")?,
+            created_at: v.take("createdAt")?,
+            updated_at: v.take("updatedAt")?,
+        })
+    }
+}
+impl From<Shop> for HashMap<String, AttributeValue> {
+    fn from(v: Shop) -> Self {
+        [
+            ("pk", Some(v.id.into_attr())),
+            ("sk", Some(anchor_attr_value())),
+            ("source", Some(v.source.into_attr())),
+            ("code", Some(v.code.into_attr())),
+            ("name", v.name.map(|v| v.into_attr())),
+            ("url", v.url.map(|v| v.into_attr())),
+            ("createdAt", Some(v.created_at.into_attr())),
+            ("updatedAt", Some(v.updated_at.into_attr())),
+            ("glk", Some(Shop::type_name().into_attr())),
+        ]
+        .into_iter()
+        .flat_map(|(k, v)| v.map(|v| (k.into(), v)))
+        .collect()
+    }
+}
+impl HasTableName for Shop {
+    fn table_name() -> String {
+        "shop".to_string()
+    }
+}
+impl HasTypeName for Shop {
+    fn type_name() -> String {
+        "Shop".to_string()
+    }
+}
+
+pub type Repository = TableRepository<Shop>;
+impl Repository {
+    pub async fn get(&self, id: &Id) -> AppResult<Shop> {
+        let q = self
+            .cli
+            .get_item()
+            .table_name(self.table_name())
+            .set_key(Some(HashMap::from([
+                ("pk".into(), id.clone().into()),
+                ("sk".into(), anchor_attr_value()),
+            ])));
+        let res = send_with_retry(|| q.clone().send()).await?;
+
+        res.item.map_or(Err(NotFound.into()), |v| {
+            Shop::try_from(v).map_err(Internal.withf())
+        })
+    }
+
+    pub async fn put(&self, item: Shop) -> AppResult<()> {
+        let q = self
+            .cli
+            .put_item()
+            .table_name(self.table_name())
+            .set_item(Some(item.into()));
+        send_with_retry(|| q.clone().send()).await?;
+        Ok(())
+    }
+
+    // 一覧のクロールで見つかった店舗を登録する。登録済みなら何もしない
+    pub async fn put_if_absent(&self, item: Shop) -> AppResult<()> {
+        let q = self
+            .cli
+            .put_item()
+            .table_name(self.table_name())
+            .set_item(Some(item.into()))
+            .condition_expression("attribute_not_exists(pk)");
+        match send_with_retry(|| q.clone().send())
+            .await
+            .map_err(AppError::from)
+        {
+            Err(err) if err.kind == Conflict => Ok(()),
+            res => res.map(|_| ()),
+        }
+    }
+
+    // 店舗名が変わった場合のみ更新する
+    pub async fn rename(&self, id: &Id, name: String, now: LocalDateTime) -> AppResult<()> {
+        let q = self
+            .cli
+            .update_item()
+            .table_name(self.table_name())
+            .set_key(Some(HashMap::from([
+                ("pk".into(), id.clone().into()),
+                ("sk".into(), anchor_attr_value()),
+            ])))
+            .update_expression("SET #name = :name, updatedAt = :updatedAt")
+            .condition_expression(
+                "attribute_exists(pk) AND (attribute_not_exists(#name) OR #name <> :name)",
+            )
+            .expression_attribute_names("#name", "name")
+            .expression_attribute_values(":name", name.into_attr())
+            .expression_attribute_values(":updatedAt", now.into_attr());
+        match send_with_retry(|| q.clone().send())
+            .await
+            .map_err(AppError::from)
+        {
+            Err(err) if err.kind == Conflict => Ok(()),
+            res => res.map(|_| ()),
+        }
+    }
+}
+#[async_trait]
+impl BatchGet<Shop, Id> for Repository {
+    async fn batch_get(&self, ids: &[Id]) -> AppResult<HashMap<Id, Shop>> {
+        let keys = ids
+            .iter()
+            .map(|v| v.clone().into_attr_map())
+            .collect::<Vec<HashMap<String, AttributeValue>>>();
+
+        let res = batch_get(&self.cli, self.table_name(), &keys, Shop::try_from).await?;
+
+        Ok(res.into_iter().map(|v| (v.id.clone(), v)).collect())
+    }
+}
@@ -35,7 +35,7 @@ impl Adapter {
         let payload = resp.payload.unwrap();
         let payload = String::from_utf8(payload.into_inner()).ok().unwrap();
 
-        if let Some(error) = serde_json::from_str::<ErrorResponse>(&payload).ok() {
+        if let Ok(error) = serde_json::from_str::<ErrorResponse>(&payload) {
             return Err(AppError::deserialize(error.error_message));
         }
         let output = serde_json::from_str(&payload).map_err(Internal.from_srcf())?;
@@ -1,2 +1,3 @@
 pub mod crawler_rakuten;
+pub mod migrator;
 pub mod sns;
@@ -1,22 +1,50 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Request {
     pub body: RequestBody,
+    // 失敗したメッセージを再実行する場合に、元の失敗のIDを渡す
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_id: Option<String>,
 }
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum RequestBody {
     CrawlEntrypoint,
     CrawlList(CrawlListRequest),
+    CrawlApi(CrawlApiRequest),
     CrawlDetail(CrawlDetailRequest),
+    CrawlProduct(CrawlProductRequest),
+    MirrorImages(MirrorImagesRequest),
+    // 他店舗の同じ商品をまとめる
+    GroupProducts,
+    // パンくずからカテゴリの木を作る
+    BuildCategories,
+    // 全商品のお得度を計算し直す
+    ScoreDeals,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CrawlListRequest {
     pub url: String,
 }
-#[derive(Serialize, Deserialize, Debug)]
+// 楽天市場商品検索APIでジャンルの一覧を取得する
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CrawlApiRequest {
+    pub genre_id: String,
+    pub page: u32,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CrawlDetailRequest {
     pub cursor: Option<String>,
     pub only_preparing: bool,
 }
+// 1商品だけを詳細クロールする
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CrawlProductRequest {
+    pub product_id: String,
+}
+// 商品画像を自前のストレージに複製する
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MirrorImagesRequest {
+    pub product_id: String,
+}
@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Request {
+    pub body: RequestBody,
+}
+#[derive(Serialize, Deserialize, Debug)]
+pub enum RequestBody {
+    RunMigration(RunMigrationRequest),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RunMigrationRequest {
+    pub name: String,
+    pub dry_run: bool,
+    pub chunk_size: Option<i32>,
+    // 実行権を得た記録のrevision。指定があれば、その記録を引き継いで続きを実行する
+    pub revision: Option<u64>,
+}
@@ -12,6 +12,8 @@ pub struct Record {
 }
 #[derive(Serialize, Deserialize)]
 pub struct Sns {
+    #[serde(rename = "MessageId")]
+    pub message_id: Option<String>,
     #[serde(rename = "Message")]
     pub message: String,
 }
@@ -0,0 +1,97 @@
+use crate::errors::Kind::*;
+use crate::AppResult;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use std::path::Path;
+use tokio::io::AsyncWriteExt;
+
+#[derive(Clone, Debug)]
+pub struct Adapter {
+    client: Client,
+    bucket_name: String,
+}
+
+impl Adapter {
+    pub fn new(client: Client, bucket_name: String) -> Self {
+        Self {
+            client,
+            bucket_name,
+        }
+    }
+
+    pub async fn upload_file(&self, key: &str, path: &Path) -> AppResult<()> {
+        let body = ByteStream::from_path(path)
+            .await
+            .map_err(Internal.from_srcf())?;
+        self.client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .body(body)
+            .send()
+            .await
+            .map_err(Internal.from_srcf())?;
+
+        Ok(())
+    }
+
+    pub async fn put_object(&self, key: &str, body: Vec<u8>, content_type: &str) -> AppResult<()> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .content_type(content_type)
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(Internal.from_srcf())?;
+
+        Ok(())
+    }
+
+    pub async fn get_object(&self, key: &str) -> AppResult<Vec<u8>> {
+        let resp = self
+            .client
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await
+            .map_err(|err| match err.as_service_error() {
+                Some(v) if v.is_no_such_key() => NotFound.with(format!("s3 key: {}", key)),
+                _ => Internal.from_src(err),
+            })?;
+        let body = resp.body.collect().await.map_err(Internal.from_srcf())?;
+
+        Ok(body.into_bytes().to_vec())
+    }
+
+    pub fn bucket_name(&self) -> &str {
+        &self.bucket_name
+    }
+
+    pub async fn download_file(&self, key: &str, path: &Path) -> AppResult<()> {
+        let resp = self
+            .client
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await
+            .map_err(|err| match err.as_service_error() {
+                Some(v) if v.is_no_such_key() => NotFound.with(format!("s3 key: {}", key)),
+                _ => Internal.from_src(err),
+            })?;
+
+        let mut file = tokio::fs::File::create(path)
+            .await
+            .map_err(Internal.from_srcf())?;
+        let mut body = resp.body;
+        while let Some(bytes) = body.try_next().await.map_err(Internal.from_srcf())? {
+            file.write_all(&bytes).await.map_err(Internal.from_srcf())?;
+        }
+        file.flush().await.map_err(Internal.from_srcf())?;
+
+        Ok(())
+    }
+}
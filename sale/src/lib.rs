@@ -1,19 +1,17 @@
 use crate::errors::AppError;
 
+pub mod affiliate;
+pub mod bus;
+pub mod crawl;
+pub mod deal;
 pub mod di;
 pub mod domain;
 pub mod env;
 pub mod errors;
 pub mod infra;
+pub mod migration;
+pub mod rate_limit;
+pub mod storage;
 mod sync;
 
 pub type AppResult<T> = Result<T, AppError>;
-
-trait MustPresent<T> {
-    fn must_present(self) -> Result<T, String>;
-}
-impl<T> MustPresent<T> for Option<T> {
-    fn must_present(self) -> Result<T, String> {
-        self.ok_or_else(|| "missing value".to_string())
-    }
-}
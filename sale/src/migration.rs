@@ -0,0 +1,159 @@
+use crate::domain::migration::{Migration, Status};
+use crate::domain::time;
+use crate::errors::Kind::{Conflict, NotFound};
+use crate::errors::NotFoundToNone;
+use crate::infra::aws::ddb;
+use crate::infra::aws::ddb::cursor::Cursor;
+use crate::AppResult;
+use async_trait::async_trait;
+use std::time::{Duration, Instant};
+
+mod backfill_image_hashes;
+mod backfill_product_shop;
+mod refresh_product_attributes;
+mod rekey_products;
+
+pub const DEFAULT_CHUNK_SIZE: i32 = 100;
+
+pub struct ChunkResult {
+    pub processed: u64,
+    pub touched: u64,
+    pub next_cursor: Option<Cursor>,
+}
+
+// 名前とバージョンで識別されるマイグレーション
+// カーソル単位のチャンクで処理し、チャンクごとに進捗を保存するので途中から再開できる
+#[async_trait]
+pub trait Definition: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn version(&self) -> u32;
+    fn description(&self) -> &'static str;
+    async fn run_chunk(
+        &self,
+        cursor: Option<Cursor>,
+        chunk_size: i32,
+        dry_run: bool,
+    ) -> AppResult<ChunkResult>;
+}
+
+pub fn definitions() -> Vec<Box<dyn Definition>> {
+    vec![
+        Box::new(refresh_product_attributes::RefreshProductAttributes),
+        Box::new(backfill_product_shop::BackfillProductShop),
+        Box::new(backfill_image_hashes::BackfillImageHashes),
+        Box::new(rekey_products::RekeyProducts),
+    ]
+}
+
+pub fn find_definition(name: &str) -> AppResult<Box<dyn Definition>> {
+    definitions()
+        .into_iter()
+        .find(|v| v.name() == name)
+        .ok_or_else(|| NotFound.with(format!("migration {} is not defined", name)))
+}
+
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    pub dry_run: bool,
+    pub chunk_size: i32,
+    // 指定した時間を超えたらチャンクの区切りで中断する
+    pub time_budget: Option<Duration>,
+}
+
+// start の結果。他の実行が進めている場合や完了済みの場合は、新たに始めずに既存の記録を返す
+pub enum Start {
+    Started(Migration),
+    Existing(Migration),
+}
+impl Start {
+    pub fn into_inner(self) -> Migration {
+        match self {
+            Start::Started(v) | Start::Existing(v) => v,
+        }
+    }
+}
+
+// 実行記録を Running にして実行権を得る
+// 完了済みの本実行はそのまま返す。ドライランは完了済みでも最初からやり直す
+// 同時に呼ばれても条件付き書き込みにより1つだけが Started になる
+pub async fn start(
+    def: &dyn Definition,
+    migration_repo: &ddb::types::migration::Repository,
+    dry_run: bool,
+) -> AppResult<Start> {
+    let now = time::now();
+    let id = Migration::id_of(def.name(), def.version(), dry_run);
+    let migration = match migration_repo.get(&id).await.not_found_to_none()? {
+        Some(v) if v.is_completed() && !dry_run => return Ok(Start::Existing(v)),
+        Some(v) if v.is_in_progress(now) => return Ok(Start::Existing(v)),
+        Some(v) if !v.is_completed() => v.resume(now),
+        Some(v) => Migration {
+            revision: v.revision,
+            ..Migration::new(def.name().to_string(), def.version(), dry_run, now)
+        },
+        None => Migration::new(def.name().to_string(), def.version(), dry_run, now),
+    };
+    match migration_repo.put(migration).await {
+        Ok(v) => Ok(Start::Started(v)),
+        Err(err) if err.kind == Conflict => Ok(Start::Existing(migration_repo.get(&id).await?)),
+        Err(err) => Err(err),
+    }
+}
+
+// start で実行権を得た記録について、進捗を記録しつつチャンクを順に実行する
+// 時間切れの場合は Paused にして止める
+pub async fn run(
+    def: &dyn Definition,
+    migration_repo: &ddb::types::migration::Repository,
+    migration: Migration,
+    opts: RunOptions,
+) -> AppResult<Migration> {
+    let started = Instant::now();
+    let id = migration.id.clone();
+    let mut migration = migration;
+
+    loop {
+        let cursor = migration.cursor.clone().map(Cursor::from);
+        let next = match def.run_chunk(cursor, opts.chunk_size, opts.dry_run).await {
+            Ok(res) => migration.progress(
+                res.next_cursor.map(|v| v.to_string()),
+                res.processed,
+                res.touched,
+                time::now(),
+            ),
+            Err(err) => migration.fail(err.to_string(), time::now()),
+        };
+        let next = if next.status == Status::Running
+            && opts.time_budget.is_some_and(|v| started.elapsed() >= v)
+        {
+            next.pause(time::now())
+        } else {
+            next
+        };
+        // 途中で他の実行に引き継がれた場合はConflictになるので、そちらに任せて止める
+        migration = migration_repo.put(next).await.map_err(|err| {
+            if err.kind == Conflict {
+                Conflict.with(format!(
+                    "migration {} was taken over by another run",
+                    id.as_str()
+                ))
+            } else {
+                err
+            }
+        })?;
+
+        if migration.status != Status::Running {
+            break;
+        }
+    }
+
+    println!(
+        "migration {}: status={}, processed={}, touched={}",
+        migration.id.as_str(),
+        migration.status,
+        migration.processed,
+        migration.touched
+    );
+
+    Ok(migration)
+}
@@ -0,0 +1,67 @@
+use crate::crawl::image_mirror;
+use crate::di;
+use crate::infra::aws::ddb::cursor::Cursor;
+use crate::migration::{ChunkResult, Definition};
+use crate::AppResult;
+use async_trait::async_trait;
+
+// 知覚ハッシュの導入前に複製した画像のハッシュを埋める
+// 画像の複製は一度きりのため、このマイグレーションを流さないと同一商品の検出に使われない
+pub struct BackfillImageHashes;
+
+#[async_trait]
+impl Definition for BackfillImageHashes {
+    fn name(&self) -> &'static str {
+        "backfill-image-hashes"
+    }
+
+    fn version(&self) -> u32 {
+        1
+    }
+
+    fn description(&self) -> &'static str {
+        "複製済みの画像から知覚ハッシュ(perceptualHash)を計算して埋める"
+    }
+
+    async fn run_chunk(
+        &self,
+        cursor: Option<Cursor>,
+        chunk_size: i32,
+        dry_run: bool,
+    ) -> AppResult<ChunkResult> {
+        let product_repo = di::DB_PRODUCT_REPOSITORY.get().await.clone();
+        let storage = di::IMAGE_STORAGE.get().await.clone();
+
+        let products = product_repo
+            .find_all_with_cursor(cursor, Some(chunk_size))
+            .await?;
+        let next_cursor = products.last().map(|v| v.cursor.clone());
+        let processed = products.len() as u64;
+
+        let mut touched = 0;
+        for product in products.into_iter().map(|v| v.entity) {
+            if product
+                .mirrored_images
+                .iter()
+                .all(|v| v.perceptual_hash.is_some())
+            {
+                continue;
+            }
+            touched += 1;
+            if dry_run {
+                continue;
+            }
+
+            let images = image_mirror::backfill_hashes(storage.as_ref(), &product).await?;
+            product_repo
+                .set_mirrored_images(&product.id, images)
+                .await?;
+        }
+
+        Ok(ChunkResult {
+            processed,
+            touched,
+            next_cursor,
+        })
+    }
+}
@@ -0,0 +1,71 @@
+use crate::di;
+use crate::domain::shop::Shop;
+use crate::domain::time;
+use crate::infra::aws::ddb::cursor::Cursor;
+use crate::migration::{ChunkResult, Definition};
+use crate::AppResult;
+use async_trait::async_trait;
+
+// 商品IDから店舗ID・商品IDを取り出して保存し、店舗を登録する
+pub struct BackfillProductShop;
+
+#[async_trait]
+impl Definition for BackfillProductShop {
+    fn name(&self) -> &'static str {
+        "backfill-product-shop"
+    }
+
+    fn version(&self) -> u32 {
+        1
+    }
+
+    fn description(&self) -> &'static str {
+        "商品IDから店舗ID(shopId)と商品ID(itemId)を埋め、店舗を登録する"
+    }
+
+    async fn run_chunk(
+        &self,
+        cursor: Option<Cursor>,
+        chunk_size: i32,
+        dry_run: bool,
+    ) -> AppResult<ChunkResult> {
+        let product_repo = di::DB_PRODUCT_REPOSITORY.get().await.clone();
+        let shop_repo = di::DB_SHOP_REPOSITORY.get().await.clone();
+
+        let products = product_repo
+            .find_all_with_cursor(cursor, Some(chunk_size))
+            .await?;
+        let next_cursor = products.last().map(|v| v.cursor.clone());
+        let processed = products.len() as u64;
+
+        let mut touched = 0;
+        for product in products.into_iter().map(|v| v.entity) {
+            if product.shop_id.is_some() {
+                continue;
+            }
+            let Some((code, item_id)) = product.parse_id() else {
+                continue;
+            };
+            touched += 1;
+            if dry_run {
+                continue;
+            }
+
+            let shop = Shop::new(
+                product.source,
+                code,
+                Shop::url_of(&product.detail_url),
+                time::now(),
+            );
+            let product = product.with_shop(shop.id.clone(), item_id);
+            shop_repo.put_if_absent(shop).await?;
+            product_repo.put(product).await?;
+        }
+
+        Ok(ChunkResult {
+            processed,
+            touched,
+            next_cursor,
+        })
+    }
+}
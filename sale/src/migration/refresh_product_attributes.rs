@@ -0,0 +1,50 @@
+use crate::di;
+use crate::infra::aws::ddb::cursor::Cursor;
+use crate::migration::{ChunkResult, Definition};
+use crate::AppResult;
+use async_trait::async_trait;
+
+// 全商品を再保存して source_status などの派生属性を最新の形式にする
+pub struct RefreshProductAttributes;
+
+#[async_trait]
+impl Definition for RefreshProductAttributes {
+    fn name(&self) -> &'static str {
+        "refresh-product-attributes"
+    }
+
+    fn version(&self) -> u32 {
+        1
+    }
+
+    fn description(&self) -> &'static str {
+        "全商品を再保存して派生属性(source_statusなど)を更新する"
+    }
+
+    async fn run_chunk(
+        &self,
+        cursor: Option<Cursor>,
+        chunk_size: i32,
+        dry_run: bool,
+    ) -> AppResult<ChunkResult> {
+        let product_repo = di::DB_PRODUCT_REPOSITORY.get().await.clone();
+
+        let products = product_repo
+            .find_all_with_cursor(cursor, Some(chunk_size))
+            .await?;
+        let next_cursor = products.last().map(|v| v.cursor.clone());
+        let processed = products.len() as u64;
+
+        if !dry_run {
+            for product in products {
+                product_repo.put(product.entity).await?;
+            }
+        }
+
+        Ok(ChunkResult {
+            processed,
+            touched: processed,
+            next_cursor,
+        })
+    }
+}
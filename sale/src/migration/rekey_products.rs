@@ -0,0 +1,83 @@
+use crate::di;
+use crate::domain::product::Product;
+use crate::domain::shop::Shop;
+use crate::errors::NotFoundToNone;
+use crate::infra::aws::ddb::cursor::Cursor;
+use crate::migration::{ChunkResult, Definition};
+use crate::AppResult;
+use async_trait::async_trait;
+
+// 商品IDを詳細URLの店舗コード・商品番号から作る <販売元>-<店舗コード>:<商品番号> に付け替える
+// 以前は一覧の data-shop-id・data-id や - 区切りで作っていたため、APIモードと同じ商品が別のIDになっていた
+pub struct RekeyProducts;
+
+#[async_trait]
+impl Definition for RekeyProducts {
+    fn name(&self) -> &'static str {
+        "rekey-products"
+    }
+
+    fn version(&self) -> u32 {
+        1
+    }
+
+    fn description(&self) -> &'static str {
+        "商品IDを詳細URLから作る <販売元>-<店舗コード>:<商品番号> に付け替え、商品グループの所属も書き換える"
+    }
+
+    async fn run_chunk(
+        &self,
+        cursor: Option<Cursor>,
+        chunk_size: i32,
+        dry_run: bool,
+    ) -> AppResult<ChunkResult> {
+        let product_repo = di::DB_PRODUCT_REPOSITORY.get().await.clone();
+        let group_repo = di::DB_PRODUCT_GROUP_REPOSITORY.get().await.clone();
+
+        let products = product_repo
+            .find_all_with_cursor(cursor, Some(chunk_size))
+            .await?;
+        let next_cursor = products.last().map(|v| v.cursor.clone());
+        let processed = products.len() as u64;
+
+        let mut touched = 0;
+        for product in products.into_iter().map(|v| v.entity) {
+            let Some((shop_code, item_code)) = Product::codes_of(&product.detail_url) else {
+                continue;
+            };
+            let id = Product::id_of(product.source, &shop_code, &item_code);
+            if id == product.id {
+                continue;
+            }
+            touched += 1;
+            if dry_run {
+                continue;
+            }
+
+            let old_id = product.id.clone();
+            // 新しいIDで既にクロールされている場合は、そちらを残して古い記録だけを消す
+            if product_repo.get(&id).await.not_found_to_none()?.is_none() {
+                let shop_id = Shop::id_of(product.source, &shop_code);
+                let product = product.rekey(id.clone()).with_shop(shop_id, item_code);
+                if let Some(group_id) = &product.group_id {
+                    if let Some(mut group) = group_repo.get(group_id).await.not_found_to_none()? {
+                        for v in group.product_ids.iter_mut().filter(|v| **v == old_id) {
+                            *v = id.clone();
+                        }
+                        group.product_ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+                        group.product_ids.dedup();
+                        group_repo.put(group).await?;
+                    }
+                }
+                product_repo.put(product).await?;
+            }
+            product_repo.delete(&old_id).await?;
+        }
+
+        Ok(ChunkResult {
+            processed,
+            touched,
+            next_cursor,
+        })
+    }
+}
@@ -0,0 +1,174 @@
+use crate::domain::rate_limit::{Bucket, Id};
+use crate::domain::time;
+use crate::domain::time::LocalDateTime;
+use crate::errors::Kind::{Conflict, Unavailable};
+use crate::errors::NotFoundToNone;
+use crate::infra::aws::ddb;
+use crate::AppResult;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+
+// 楽観ロックの競合時に読み直す上限
+const MAX_CONFLICT_RETRIES: u32 = 10;
+// acquire で待つ上限。Retry-Afterで止められている間などはLambdaのタイムアウトまで待ち続けず、
+// Unavailableを返して呼び出し元で後回しにする
+const MAX_ACQUIRE_WAIT: Duration = Duration::from_secs(5);
+
+// バケットの保存先。Lambdaの同時実行間で共有する場合はDynamoDBを使う
+#[async_trait]
+pub trait BucketStore: Send + Sync {
+    // トークンを1つ取得する。取得できなければ待ち時間を返す
+    async fn try_acquire(&self, key: &str, rate: f64, burst: f64) -> AppResult<Option<Duration>>;
+    // トークンを消費せず、取得できなければ待ち時間を返す
+    async fn check(&self, key: &str, rate: f64, burst: f64) -> AppResult<Option<Duration>>;
+    async fn block(&self, key: &str, until: LocalDateTime) -> AppResult<()>;
+}
+
+#[derive(Default)]
+pub struct InMemoryBucketStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+#[async_trait]
+impl BucketStore for InMemoryBucketStore {
+    async fn try_acquire(&self, key: &str, rate: f64, burst: f64) -> AppResult<Option<Duration>> {
+        let now = time::now();
+        let mut buckets = self.buckets.lock().await;
+        let bucket = buckets
+            .remove(key)
+            .unwrap_or_else(|| Bucket::new(Id::new(key), burst, now));
+        let (bucket, wait) = match bucket.clone().take(rate, burst, now) {
+            Ok(v) => (v, None),
+            Err(wait) => (bucket, Some(wait)),
+        };
+        buckets.insert(key.to_string(), bucket);
+        Ok(wait)
+    }
+
+    async fn check(&self, key: &str, rate: f64, burst: f64) -> AppResult<Option<Duration>> {
+        let now = time::now();
+        let buckets = self.buckets.lock().await;
+        Ok(buckets
+            .get(key)
+            .and_then(|v| v.clone().take(rate, burst, now).err()))
+    }
+
+    async fn block(&self, key: &str, until: LocalDateTime) -> AppResult<()> {
+        let mut buckets = self.buckets.lock().await;
+        let bucket = buckets
+            .remove(key)
+            .unwrap_or_else(|| Bucket::new(Id::new(key), 0.0, time::now()));
+        buckets.insert(key.to_string(), bucket.block(until));
+        Ok(())
+    }
+}
+
+pub struct DdbBucketStore {
+    repo: ddb::types::rate_limit::Repository,
+}
+impl DdbBucketStore {
+    pub fn new(repo: ddb::types::rate_limit::Repository) -> Self {
+        Self { repo }
+    }
+
+    async fn update<F>(&self, key: &str, init: f64, f: F) -> AppResult<Option<Duration>>
+    where
+        F: Fn(Bucket, LocalDateTime) -> Result<Bucket, Duration> + Send + Sync,
+    {
+        let id = Id::new(key);
+        let mut attempt = 0;
+        loop {
+            let now = time::now();
+            let bucket = self
+                .repo
+                .get(&id)
+                .await
+                .not_found_to_none()?
+                .unwrap_or_else(|| Bucket::new(id.clone(), init, now));
+            let bucket = match f(bucket, now) {
+                Ok(v) => v,
+                Err(wait) => return Ok(Some(wait)),
+            };
+            match self.repo.put(bucket).await {
+                Ok(_) => return Ok(None),
+                Err(err) if err.kind == Conflict && attempt + 1 < MAX_CONFLICT_RETRIES => {
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+#[async_trait]
+impl BucketStore for DdbBucketStore {
+    async fn try_acquire(&self, key: &str, rate: f64, burst: f64) -> AppResult<Option<Duration>> {
+        self.update(key, burst, |bucket, now| bucket.take(rate, burst, now))
+            .await
+    }
+
+    async fn check(&self, key: &str, rate: f64, burst: f64) -> AppResult<Option<Duration>> {
+        let now = time::now();
+        Ok(self
+            .repo
+            .get(&Id::new(key))
+            .await
+            .not_found_to_none()?
+            .and_then(|v| v.take(rate, burst, now).err()))
+    }
+
+    async fn block(&self, key: &str, until: LocalDateTime) -> AppResult<()> {
+        self.update(key, 0.0, |bucket, _| Ok(bucket.block(until)))
+            .await?;
+        Ok(())
+    }
+}
+
+// キーごとのトークンバケットでリクエスト間隔を制御する
+#[derive(Clone)]
+pub struct RateLimiter {
+    store: Arc<dyn BucketStore>,
+}
+impl RateLimiter {
+    pub fn new(store: Arc<dyn BucketStore>) -> Self {
+        Self { store }
+    }
+
+    // トークンが取得できなければ待ち時間を返す
+    pub async fn try_acquire(
+        &self,
+        key: &str,
+        rate: f64,
+        burst: f64,
+    ) -> AppResult<Option<Duration>> {
+        self.store.try_acquire(key, rate, burst).await
+    }
+
+    // トークンを消費せず、取得できなければ待ち時間を返す
+    pub async fn check(&self, key: &str, rate: f64, burst: f64) -> AppResult<Option<Duration>> {
+        self.store.check(key, rate, burst).await
+    }
+
+    // トークンが取得できるまで待つ。MAX_ACQUIRE_WAIT を超える場合はUnavailableを返す
+    pub async fn acquire(&self, key: &str, rate: f64, burst: f64) -> AppResult<()> {
+        let mut waited = Duration::ZERO;
+        while let Some(wait) = self.store.try_acquire(key, rate, burst).await? {
+            if waited.saturating_add(wait) > MAX_ACQUIRE_WAIT {
+                return Err(Unavailable.with(format!(
+                    "rate limited: {} (retry after {}s)",
+                    key,
+                    wait.as_secs_f64().ceil()
+                )));
+            }
+            sleep(wait).await;
+            waited += wait;
+        }
+        Ok(())
+    }
+
+    pub async fn block(&self, key: &str, until: LocalDateTime) -> AppResult<()> {
+        self.store.block(key, until).await
+    }
+}
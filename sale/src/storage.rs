@@ -0,0 +1,98 @@
+use crate::errors::Kind::{Internal, NotFound};
+use crate::infra::aws::s3;
+use crate::AppResult;
+use async_trait::async_trait;
+use std::path::PathBuf;
+
+// 公開するファイルの保存先。ローカル実行ではファイルシステムに置き換える
+#[async_trait]
+pub trait ObjectStorage: Send + Sync {
+    async fn put(&self, key: &str, body: Vec<u8>, content_type: &str) -> AppResult<()>;
+    // 存在しない場合はNotFound
+    async fn get(&self, key: &str) -> AppResult<Vec<u8>>;
+    fn public_url(&self, key: &str) -> String;
+    // public_url の逆。この保存先のURLでなければNone
+    fn key_of(&self, public_url: &str) -> Option<String>;
+}
+
+#[derive(Clone, Debug)]
+pub struct S3Storage {
+    adapter: s3::Adapter,
+    base_url: url::Url,
+}
+impl S3Storage {
+    pub fn new(adapter: s3::Adapter, base_url: url::Url) -> Self {
+        Self { adapter, base_url }
+    }
+}
+#[async_trait]
+impl ObjectStorage for S3Storage {
+    async fn put(&self, key: &str, body: Vec<u8>, content_type: &str) -> AppResult<()> {
+        self.adapter.put_object(key, body, content_type).await
+    }
+
+    async fn get(&self, key: &str) -> AppResult<Vec<u8>> {
+        self.adapter.get_object(key).await
+    }
+
+    fn public_url(&self, key: &str) -> String {
+        join_url(&self.base_url, key)
+    }
+
+    fn key_of(&self, public_url: &str) -> Option<String> {
+        split_url(&self.base_url, public_url)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LocalStorage {
+    root: PathBuf,
+    base_url: url::Url,
+}
+impl LocalStorage {
+    pub fn new(root: PathBuf, base_url: url::Url) -> Self {
+        Self { root, base_url }
+    }
+}
+#[async_trait]
+impl ObjectStorage for LocalStorage {
+    async fn put(&self, key: &str, body: Vec<u8>, _content_type: &str) -> AppResult<()> {
+        let path = self.root.join(key);
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .map_err(Internal.from_srcf())?;
+        }
+        tokio::fs::write(&path, body)
+            .await
+            .map_err(Internal.from_srcf())
+    }
+
+    async fn get(&self, key: &str) -> AppResult<Vec<u8>> {
+        tokio::fs::read(self.root.join(key))
+            .await
+            .map_err(|err| match err.kind() {
+                std::io::ErrorKind::NotFound => NotFound.with(format!("file: {}", key)),
+                _ => Internal.from_src(err),
+            })
+    }
+
+    fn public_url(&self, key: &str) -> String {
+        join_url(&self.base_url, key)
+    }
+
+    fn key_of(&self, public_url: &str) -> Option<String> {
+        split_url(&self.base_url, public_url)
+    }
+}
+
+fn join_url(base_url: &url::Url, key: &str) -> String {
+    format!("{}/{}", base_url.as_str().trim_end_matches('/'), key)
+}
+
+fn split_url(base_url: &url::Url, public_url: &str) -> Option<String> {
+    public_url
+        .strip_prefix(base_url.as_str().trim_end_matches('/'))?
+        .strip_prefix('/')
+        .map(|v| v.to_string())
+}
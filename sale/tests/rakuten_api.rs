@@ -0,0 +1,129 @@
+// 楽天市場商品検索APIのクライアントを、記録したレスポンスを返すモックサーバーに向けて確認する
+use sale::crawl::rakuten_api::{RakutenApi, SearchParams};
+use sale::domain::product::Status;
+use sale::domain::time;
+use sale::errors::Kind::BadRequest;
+use sale::rate_limit::{InMemoryBucketStore, RateLimiter};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+
+const SEARCH: &str = include_str!("fixtures/rakuten_item_search.json");
+const SEARCH_ERROR: &str = include_str!("fixtures/rakuten_item_search_error.json");
+
+// 1リクエストだけ受け付けて固定のレスポンスを返し、リクエスト行を送り返す
+async fn serve_once(
+    status: &'static str,
+    body: &'static str,
+) -> (url::Url, oneshot::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = oneshot::channel();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buf = vec![0u8; 8192];
+        let n = socket.read(&mut buf).await.unwrap();
+        let request = String::from_utf8_lossy(&buf[..n]).to_string();
+        let _ = tx.send(request.lines().next().unwrap_or_default().to_string());
+
+        let response = format!(
+            "HTTP/1.1 {}\r\ncontent-type: application/json; charset=utf-8\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+        socket.shutdown().await.unwrap();
+    });
+    let url = url::Url::parse(&format!(
+        "http://{}/services/api/IchibaItem/Search/20220601",
+        addr
+    ))
+    .unwrap();
+    (url, rx)
+}
+
+fn api(base_url: url::Url) -> RakutenApi {
+    RakutenApi::new(
+        base_url,
+        "app-id".to_string(),
+        Some("affiliate-id".to_string()),
+        RateLimiter::new(Arc::new(InMemoryBucketStore::default())),
+    )
+    .unwrap()
+}
+
+fn params() -> SearchParams {
+    SearchParams {
+        genre_id: "551177".to_string(),
+        page: 1,
+        hits: 30,
+    }
+}
+
+#[tokio::test]
+async fn search_sends_paging_params_and_parses_items() {
+    let (base_url, request) = serve_once("200 OK", SEARCH).await;
+
+    let res = api(base_url).search(&params()).await.unwrap();
+
+    let request = request.await.unwrap();
+    for param in [
+        "applicationId=app-id",
+        "affiliateId=affiliate-id",
+        "formatVersion=2",
+        "genreId=551177",
+        "page=1",
+        "hits=30",
+    ] {
+        assert!(request.contains(param), "{} not in {}", param, request);
+    }
+    assert_eq!(res.count, 45);
+    assert_eq!(res.page_count, 23);
+    assert_eq!(res.next_page(), Some(2));
+    assert_eq!(res.items.len(), 2);
+    assert_eq!(res.items[1].item_id(), "shirt-001");
+    assert_eq!(
+        res.items[1].product_id().as_str(),
+        "Rakuten-e-life:shirt-001"
+    );
+}
+
+#[tokio::test]
+async fn items_map_into_products() {
+    let (base_url, _request) = serve_once("200 OK", SEARCH).await;
+    let res = api(base_url).search(&params()).await.unwrap();
+    let now = time::now();
+
+    let product = res.items[0].clone().into_product(None, now);
+    assert_eq!(product.id.as_str(), "Rakuten-tab11:john013");
+    assert_eq!(product.shop_id.as_ref().unwrap().as_str(), "Rakuten-tab11");
+    assert_eq!(product.item_id.as_deref(), Some("john013"));
+    assert_eq!(product.status, Status::Active);
+    assert_eq!(product.actual_price.as_deref(), Some("12980"));
+    assert_eq!(product.points.as_deref(), Some("ポイント10倍"));
+    assert_eq!(product.rating, Some(4.36));
+    assert_eq!(product.review_count, Some(128));
+    assert_eq!(product.genre_id.as_deref(), Some("551177"));
+    assert!(product.affiliate_url.is_some());
+    assert_eq!(
+        product.image_urls[0].as_str(),
+        "https://thumbnail.image.rakuten.co.jp/@0_mall/tab11/cabinet/john013.jpg"
+    );
+
+    let sold_out = res.items[1].clone().into_product(None, now);
+    assert_eq!(sold_out.status, Status::SoldOut);
+    assert_eq!(sold_out.rating, None);
+    assert_eq!(sold_out.affiliate_url, None);
+}
+
+#[tokio::test]
+async fn error_response_maps_to_app_error() {
+    let (base_url, _request) = serve_once("400 Bad Request", SEARCH_ERROR).await;
+
+    let err = api(base_url).search(&params()).await.unwrap_err();
+
+    assert_eq!(err.kind, BadRequest);
+    assert!(err.msg.unwrap().contains("wrong_parameter"));
+}
@@ -24,7 +24,7 @@ async fn main() -> Result<(), Error> {
     } else {
         let args: Vec<String> = env::args().collect();
         let json = args.get(1).expect("json params is required");
-        let body: Request = serde_json::from_str(&json).unwrap();
+        let body: Request = serde_json::from_str(json).unwrap();
         if let Err(err) = handler(body).await {
             eprintln!("error: {:?}", err);
             return Err(anyhow!(err).into());
@@ -77,6 +77,7 @@ async fn handler(req: Request) -> AppResult<()> {
             sns.publish(
                 lambda::types::crawler_rakuten::Request {
                     body: lambda::types::crawler_rakuten::RequestBody::CrawlEntrypoint,
+                    failure_id: None,
                 },
                 sns_arn.clone(),
             )
@@ -92,6 +93,7 @@ async fn handler(req: Request) -> AppResult<()> {
                             only_preparing: false,
                         },
                     ),
+                    failure_id: None,
                 },
                 sns_arn.clone(),
             )